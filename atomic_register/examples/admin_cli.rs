@@ -0,0 +1,55 @@
+use std::{io::BufRead, sync::Arc};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}};
+
+// Starts a local cluster and reads admin commands from stdin, one per line:
+//   status <node>          state of a node
+//   write <key> <value>    client write
+//   read <key>             client read
+//   down <node>, up <node> drop or deliver messages to a node
+//   quit
+// Example: printf 'write a 1\nstatus 1\n' | cargo run --example admin_cli -- 3
+
+fn main() {
+    let node_count = std::env::args().nth(1).map(|arg| arg.parse().unwrap()).unwrap_or(3);
+    let network = Arc::new(Network::local(node_count, 1));
+
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, node_count / 2 + 1, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.unwrap();
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["status", node] => match client.status(&NodeId(node.parse().unwrap())) {
+                Some(status) => {
+                    println!("> node {:?}: {:?}, {:?}, epoch {}", status.id, status.node_state, status.quorum_state, status.configuration.epoch);
+                    println!(">   inbox {}, in flight {:?}", status.inbox_len, status.in_flight);
+                    println!(">   peers {:?}", status.peers);
+                    println!(">   {} registers", status.register_count);
+                    for (key, data) in status.registers {
+                        println!(">   {:?} = {:?}", key, data);
+                    }
+                },
+                None => println!("> node {} does not answer", node),
+            },
+            ["write", key, value] => {
+                client.with_key(key).write(value.to_string());
+                println!("> ok");
+            },
+            ["read", key] => println!("> {:?}", client.with_key(key).read().value()),
+            ["down", node] => network.set_reachable(&NodeId(node.parse().unwrap()), false),
+            ["up", node] => network.set_reachable(&NodeId(node.parse().unwrap()), true),
+            ["quit"] => break,
+            [] => {},
+            _ => println!("> unknown command {:?}", line),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{anti_entropy::AntiEntropyConfig, atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(5, 1));
+
+    for node_id in network.node_ids() {
+        let config = AntiEntropyConfig { interval: Duration::from_millis(50), max_bytes_per_interval: 1024 };
+        let mut node = Node::new(node_id, 3, Arc::clone(&network)).with_anti_entropy(config);
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    for i in 0..3 {
+        println!("Client starts write operation.");
+        client.write(format!("Data {}", i));
+    }
+
+    // A write that only reached node 4, the other replicas catch up through gossip
+    let data = NodeData::new("Data 3".to_string(), 4);
+    network.send_to_node(&NodeId(4), Message::CoordinatorWriteRequest((NodeId(4), "".to_string(), data, 0, 0)));
+
+    std::thread::sleep(Duration::from_secs(1));
+
+    println!("Client starts read operation.");
+    client.read();
+}
\ No newline at end of file
@@ -1,17 +1,9 @@
-use std::{collections::HashMap, sync::{mpsc, Arc, Mutex}};
+use std::sync::Arc;
 
-use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeId}};
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}};
 
 fn main() {
-    let (node_senders, node_receivers) = create_node_network(3);
-    let (client_senders, client_receivers) = create_client_network(2);
-
-    let network = Arc::new(Network::new(
-        node_senders,
-        node_receivers,
-        client_senders,
-        client_receivers,
-    ));
+    let network = Arc::new(Network::local(3, 2));
 
     let client1 = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
     let client2 = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network));
@@ -53,30 +45,4 @@ fn main() {
 
     handle1.join().unwrap();
     handle2.join().unwrap();
-}
-
-fn create_node_network(node_count: usize) -> (HashMap<NodeId, mpsc::Sender<Message>>, HashMap<NodeId, Arc<Mutex<mpsc::Receiver<Message>>>>) {
-    let mut node_senders = HashMap::new();
-    let mut node_receivers = HashMap::new();
-
-    for i in 0..node_count {
-        let (tx, rx) = mpsc::channel();
-        node_senders.insert(NodeId(i as i32), tx);
-        node_receivers.insert(NodeId(i as i32), Arc::new(Mutex::new(rx)));
-    }
-
-    (node_senders, node_receivers)
-}
-
-fn create_client_network(client_count: usize) -> (HashMap<ClientId, mpsc::Sender<Message>>, HashMap<ClientId, Arc<Mutex<mpsc::Receiver<Message>>>>) {
-    let mut client_senders = HashMap::new();
-    let mut client_receivers = HashMap::new();
-
-    for i in 0..client_count {
-        let (tx, rx) = mpsc::channel();
-        client_senders.insert(ClientId(i as i32), tx);
-        client_receivers.insert(ClientId(i as i32), Arc::new(Mutex::new(rx)));
-    }
-
-    (client_senders, client_receivers)
-}
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{anti_entropy::AntiEntropyConfig, atomic_register_client::{AtomicRegisterClinent, ClientId}, crdt::{CrdtMode, VersionVector}, network::Network, node::{Node, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network)).with_anti_entropy(AntiEntropyConfig::default());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client1 = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let client2 = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network));
+
+    // Only node 0 is up, a quorum write would hang but CRDT writes are accepted
+    network.set_reachable(&NodeId(1), false);
+    network.set_reachable(&NodeId(2), false);
+
+    client1.with_key("status").with_crdt(CrdtMode::LastWriterWins).crdt_write("away".to_string(), &VersionVector::new()).unwrap();
+    client1.with_key("cart").with_crdt(CrdtMode::MultiValue).crdt_write("apple".to_string(), &VersionVector::new()).unwrap();
+
+    // Now only node 1 is up, client 2 has not seen the writes on node 0
+    network.set_reachable(&NodeId(0), false);
+    network.set_reachable(&NodeId(1), true);
+
+    client2.with_key("status").with_crdt(CrdtMode::LastWriterWins).crdt_write("online".to_string(), &VersionVector::new()).unwrap();
+    client2.with_key("cart").with_crdt(CrdtMode::MultiValue).crdt_write("pear".to_string(), &VersionVector::new()).unwrap();
+
+    // The partition heals and anti-entropy merges the registers
+    network.set_reachable(&NodeId(0), true);
+    network.set_reachable(&NodeId(2), true);
+    std::thread::sleep(Duration::from_millis(500));
+
+    let status = client1.with_key("status").with_crdt(CrdtMode::LastWriterWins).crdt_read().unwrap();
+    assert_eq!(status.values, vec!["online".to_string()]);
+
+    let cart = client1.with_key("cart").with_crdt(CrdtMode::MultiValue);
+    let mut value = cart.crdt_read().unwrap();
+    value.values.sort();
+    println!("Concurrent cart values: {:?}", value.values);
+    assert_eq!(value.values, vec!["apple".to_string(), "pear".to_string()]);
+
+    // Writing with the read context replaces both values
+    cart.crdt_write("apple, pear".to_string(), &value.context).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    for i in 0..3 {
+        network.set_reachable(&NodeId(i), false);
+    }
+    network.set_reachable(&NodeId(2), true);
+    let value = client2.with_key("cart").with_crdt(CrdtMode::MultiValue).crdt_read().unwrap();
+    assert_eq!(value.values, vec!["apple, pear".to_string()]);
+    println!("Node 2 converged to {:?}", value.values);
+}
\ No newline at end of file
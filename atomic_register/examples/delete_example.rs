@@ -0,0 +1,52 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{anti_entropy::AntiEntropyConfig, atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+
+    let mut nodes = Vec::new();
+    for i in 0..3 {
+        let node = Node::new(NodeId(i), 2, Arc::clone(&network))
+            .with_tombstone_gc(Duration::from_millis(100))
+            .with_anti_entropy(AntiEntropyConfig::default());
+        nodes.push(node.clone());
+
+        let mut node = node;
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let cart = client.with_key("cart");
+
+    // An empty value is still a value, a deleted one is absent
+    cart.write("".to_string());
+    assert_eq!(cart.read().value(), Some(""));
+
+    // Node 2 misses the delete, so the tombstone has to stay until anti-entropy brings it there
+    network.set_reachable(&NodeId(2), false);
+    cart.delete();
+
+    let data = cart.read();
+    println!("Read after delete: {:?}", data);
+    assert!(data.is_tombstone() && data.value().is_none());
+
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(nodes[0].stored("cart").is_tombstone());
+
+    network.set_reachable(&NodeId(2), true);
+    std::thread::sleep(Duration::from_millis(1000));
+
+    // Only the version of the tombstone stays behind
+    for node in &nodes {
+        assert!(node.status().registers.is_empty());
+        assert_eq!(node.stored("cart").version(), 2);
+    }
+    println!("Tombstone collected on every node");
+
+    cart.write("apple".to_string());
+    let data = cart.read();
+    assert_eq!((data.value(), data.version()), (Some("apple"), 3));
+}
\ No newline at end of file
@@ -0,0 +1,49 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, hint::HintConfig, network::Network, node::{Node, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+
+    let config = HintConfig {
+        replay_interval: Duration::from_millis(50),
+        max_age: Duration::from_secs(5),
+        max_hints_per_node: 4,
+    };
+
+    let mut nodes = Vec::new();
+    for i in 0..3 {
+        let node = Node::new(NodeId(i), 2, Arc::clone(&network)).with_hinted_handoff(config.clone());
+        nodes.push(node.clone());
+
+        let mut node = node;
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    // Node 2 goes down, the write quorum is nodes 0 and 1
+    network.set_reachable(&NodeId(2), false);
+
+    for i in 0..5 {
+        client.with_key(&format!("key-{}", i)).write(format!("Data {}", i));
+    }
+
+    let (pending, dropped) = nodes[0].hint_counts();
+    println!("Coordinator holds {} hints, dropped {}", pending, dropped);
+    assert_eq!((pending, dropped), (4, 1));
+    assert_eq!(nodes[2].stored("key-0").version(), 0);
+
+    network.set_reachable(&NodeId(2), true);
+    std::thread::sleep(Duration::from_millis(300));
+
+    // The hints were handed off, the key over the limit still has to be repaired by a read
+    for i in 0..4 {
+        assert_eq!(nodes[2].stored(&format!("key-{}", i)).data(), format!("Data {}", i));
+    }
+    assert_eq!(nodes[2].stored("key-4").version(), 0);
+    assert_eq!(nodes[0].hint_counts().0, 0);
+    println!("Node 2 caught up through hinted handoff");
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, lease::LeaseConfig, network::Network, node::Node};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+
+    for node_id in network.node_ids() {
+        let config = LeaseConfig { duration: Duration::from_millis(200), max_clock_drift: 0.05 };
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_lease(config);
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    println!("Client starts write operation.");
+    client.write("Data 1".to_string());
+
+    // The first reads go through the quorum and request the lease, once a quorum read under the lease
+    // found the value on a quorum the next ones are served locally
+    for _ in 0..5 {
+        println!("Client starts read operation.");
+        client.read();
+    }
+
+    // After the lease ran out the read falls back to the quorum path
+    std::thread::sleep(Duration::from_millis(300));
+    println!("Client starts read operation.");
+    client.read();
+}
\ No newline at end of file
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}};
+
+fn start_node(network: &Arc<Network>, node_id: NodeId) {
+    let mut node = Node::new(node_id, 2, Arc::clone(network));
+    std::thread::spawn(move || {
+        node.run();
+    });
+}
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    for i in 0..3 {
+        start_node(&network, NodeId(i));
+    }
+
+    let client1 = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let client2 = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network));
+
+    client1.write("Data 1".to_string());
+
+    // Two new replicas join, the coordinator transfers the registers to them before switching
+    for i in 3..5 {
+        network.add_node(NodeId(i));
+        start_node(&network, NodeId(i));
+    }
+
+    let writer = std::thread::spawn(move || {
+        for i in 2..6 {
+            client2.write(format!("Data {}", i));
+        }
+    });
+
+    let configuration = client1.reconfigure((0..5).map(NodeId).collect()).unwrap();
+    assert_eq!(configuration.members.len(), 5);
+
+    writer.join().unwrap();
+
+    // Nodes 1 and 2 leave, the remaining quorum must still return the last write
+    let configuration = client1.reconfigure(vec![NodeId(0), NodeId(3), NodeId(4)]).unwrap();
+    assert_eq!(configuration.epoch, 2);
+
+    client1.read();
+
+    // The coordinator can not be removed
+    let configuration = client1.reconfigure(vec![NodeId(3), NodeId(4)]).unwrap();
+    assert_eq!(configuration.epoch, 2);
+}
\ No newline at end of file
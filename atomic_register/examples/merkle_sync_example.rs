@@ -0,0 +1,32 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{anti_entropy::AntiEntropyConfig, atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+
+    for node_id in network.node_ids() {
+        let config = AntiEntropyConfig { interval: Duration::from_millis(50), max_bytes_per_interval: 16 * 1024 };
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_anti_entropy(config);
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    for i in 0..200 {
+        client.with_key(&format!("key-{}", i)).write(format!("Data {}", i));
+    }
+
+    // Writes that only reached node 2, anti-entropy finds the divergent leaves and repairs only these keys
+    for i in [7, 42, 150] {
+        let data = NodeData::new(format!("Data {} from node 2", i), 2);
+        network.send_to_node(&NodeId(2), Message::CoordinatorWriteRequest((NodeId(2), format!("key-{}", i), data, 0, 0)));
+    }
+
+    std::thread::sleep(Duration::from_secs(1));
+
+    println!("Client starts read operation.");
+    client.with_key("key-42").read();
+}
\ No newline at end of file
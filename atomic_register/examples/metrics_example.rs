@@ -0,0 +1,46 @@
+use std::{io::{Read, Write}, net::TcpStream, sync::Arc};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, metrics::{CLIENT_OPERATION_SECONDS, MESSAGES_RECEIVED_TOTAL, MESSAGES_SENT_TOTAL}, network::Network, node::{Node, NodeId}, trace::NoopSink};
+
+// Serves the metrics of a small run on a local port and dumps them to a file
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let address = Arc::clone(network.metrics()).serve("127.0.0.1:0").unwrap();
+    println!("Metrics on http://{}/metrics", address);
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("x");
+    for i in 0..10 {
+        client.write(i.to_string());
+        client.read();
+    }
+
+    // Node 2 misses a write, a read that asks it can write the value back
+    network.set_reachable(&NodeId(2), false);
+    client.write("missed".to_string());
+    network.set_reachable(&NodeId(2), true);
+    client.read();
+
+    let metrics = network.metrics();
+    assert_eq!(metrics.counter(CLIENT_OPERATION_SECONDS), 22);
+    assert!(metrics.counter(MESSAGES_SENT_TOTAL) >= metrics.counter(MESSAGES_RECEIVED_TOTAL));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    println!("{}", response.split("\r\n\r\n").nth(1).unwrap());
+
+    let path = std::env::temp_dir().join("atomic_register_metrics.prom");
+    metrics.dump(&path).unwrap();
+    println!("Dumped to {:?}", path);
+}
\ No newline at end of file
@@ -0,0 +1,45 @@
+use std::time::Instant;
+
+use atomic_register::{model::{self, ModelConfig, ModelOperation::{Read, Write}, ModelReport}, node::RegisterSemantics};
+
+fn report(name: &str, config: &ModelConfig) -> ModelReport {
+    let started = Instant::now();
+    let report = model::check(config);
+    println!("{}: {} states in {:?}, complete {}", name, report.states, started.elapsed(), report.complete);
+
+    if let Some(violation) = &report.violation {
+        println!("  {} after {} steps:", violation.error, violation.trace.len());
+        for (i, step) in violation.trace.iter().enumerate() {
+            println!("  {:>3}. {}", i + 1, step);
+        }
+    }
+    report
+}
+
+// Checks 3 nodes with a client that writes and reads, a concurrent writer and one crash, then two clients
+// that write and read on their own coordinators, and last reads without the write-back, which are not linearizable
+fn main() {
+    let single = report("One coordinator", &ModelConfig::default());
+    assert!(single.complete && single.violation.is_none());
+
+    let two_coordinators = ModelConfig {
+        nodes: 2,
+        quorum: 2,
+        coordinators: vec![0, 1],
+        workload: vec![vec![Write(1), Read], vec![Write(2), Read]],
+        max_crashes: 0,
+        ..ModelConfig::default()
+    };
+    let two = report("Two coordinators", &two_coordinators);
+    assert!(two.complete && two.violation.is_none());
+
+    let regular = ModelConfig {
+        coordinators: vec![0, 1],
+        workload: vec![vec![Write(1)], vec![Read, Read]],
+        max_crashes: 0,
+        semantics: RegisterSemantics::Regular,
+        ..ModelConfig::default()
+    };
+    let regular = report("Regular reads", &regular);
+    assert!(regular.violation.is_some());
+}
\ No newline at end of file
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::ClientId, network::Network, raft::RaftNode, raft_client::RaftClient};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    let mut client1 = RaftClient::new(ClientId(0), Arc::clone(&network));
+    let mut client2 = RaftClient::new(ClientId(1), Arc::clone(&network));
+
+    for node_id in network.node_ids() {
+        let mut node = RaftNode::new(node_id, network.node_ids(), Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let handle1 = std::thread::spawn(move || {
+        println!("Client1 starts read operation.");
+        client1.read();
+
+        println!("Client1 starts write operation.");
+        client1.write("Data 1".to_string());
+
+        println!("Client1 starts read operation.");
+        client1.read();
+    });
+
+    let handle2 = std::thread::spawn(move || {
+        println!("Client2 starts read operation.");
+        client2.read();
+
+        println!("Client2 starts write operation.");
+        client2.write("Data 2".to_string());
+
+        println!("Client2 starts read operation.");
+        client2.read();
+    });
+
+    handle1.join().unwrap();
+    handle2.join().unwrap();
+}
\ No newline at end of file
@@ -0,0 +1,101 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, RegisterSemantics}, raft::RaftNode, raft_client::RaftClient};
+
+// Runs the same workload against the ABD register and the Raft state machine.
+// Usage: cargo run --example register_benchmark -- [abd|regular|safe|raft] [clients] [operations per client]
+// regular and safe run the ABD register with the weaker read semantics.
+
+const NODE_COUNT: usize = 3;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let protocol = args.get(1).cloned().unwrap_or("raft".to_string());
+    let client_count = args.get(2).map(|arg| arg.parse().unwrap()).unwrap_or(2);
+    let operations = args.get(3).map(|arg| arg.parse().unwrap()).unwrap_or(50);
+
+    let elapsed = match protocol.as_str() {
+        "abd" => run_abd(client_count, operations, RegisterSemantics::Atomic),
+        "regular" => run_abd(client_count, operations, RegisterSemantics::Regular),
+        "safe" => run_abd(client_count, operations, RegisterSemantics::Safe),
+        "raft" => run_raft(client_count, operations),
+        _ => panic!("Unknown protocol {}, expected abd, regular, safe or raft", protocol),
+    };
+
+    let total = client_count * operations;
+    println!(
+        "{}: {} operations from {} clients in {:?} ({:.1} ops/s)",
+        protocol,
+        total,
+        client_count,
+        elapsed,
+        total as f64 / elapsed.as_secs_f64(),
+    );
+}
+
+fn run_abd(client_count: usize, operations: usize, semantics: RegisterSemantics) -> Duration {
+    let network = Arc::new(Network::local(NODE_COUNT, client_count));
+
+    let mut nodes = Vec::new();
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, NODE_COUNT / 2 + 1, Arc::clone(&network)).with_semantics(semantics);
+        nodes.push(node.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..client_count).map(|i| {
+        let client = AtomicRegisterClinent::new(ClientId(i as i32), Arc::clone(&network));
+        std::thread::spawn(move || {
+            for operation in 0..operations {
+                if operation % 2 == 0 {
+                    client.write(format!("Data {} {}", i, operation));
+                } else {
+                    client.read();
+                }
+            }
+        })
+    }).collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    println!("abd coordinator reads: {:?}", nodes[0].read_stats());
+
+    elapsed
+}
+
+fn run_raft(client_count: usize, operations: usize) -> Duration {
+    let network = Arc::new(Network::local(NODE_COUNT, client_count));
+
+    for node_id in network.node_ids() {
+        let mut node = RaftNode::new(node_id, network.node_ids(), Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..client_count).map(|i| {
+        let mut client = RaftClient::new(ClientId(i as i32), Arc::clone(&network));
+        std::thread::spawn(move || {
+            for operation in 0..operations {
+                if operation % 2 == 0 {
+                    client.write(format!("Data {} {}", i, operation));
+                } else {
+                    client.read();
+                }
+            }
+        })
+    }).collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    start.elapsed()
+}
\ No newline at end of file
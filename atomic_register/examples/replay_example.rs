@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, replay::{Endpoint, Replay}, trace::NoopSink};
+
+fn start_nodes(network: &Arc<Network>) -> Vec<Node> {
+    let mut nodes = Vec::new();
+    for i in 0..3 {
+        let node = Node::new(NodeId(i), 2, Arc::clone(network));
+        nodes.push(node.clone());
+
+        let mut node = node;
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    nodes
+}
+
+fn name(endpoint: &Option<Endpoint>) -> String {
+    match endpoint {
+        Some(Endpoint::Node(node_id)) => format!("node {}", node_id.0),
+        Some(Endpoint::Client(client_id)) => format!("client {}", client_id.0),
+        None => "?".to_string(),
+    }
+}
+
+// Records a run to a file and feeds it to fresh nodes one delivery at a time
+fn main() {
+    let path = std::env::temp_dir().join("atomic_register_recording.jsonl");
+
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    network.record_to(&path).unwrap();
+    let recorded_nodes = start_nodes(&network);
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("x");
+    client.write("1".to_string());
+    client.write("2".to_string());
+    client.read();
+    network.stop_recording();
+
+    let replay_network = Arc::new(Network::local(3, 1));
+    replay_network.set_trace_sink(Arc::new(NoopSink));
+    let replayed_nodes = start_nodes(&replay_network);
+
+    let mut replay = Replay::from_file(Arc::clone(&replay_network), &path).unwrap();
+    println!("Replaying {} deliveries from {:?}", replay.remaining(), path);
+
+    let mut step = 0;
+    while let Some(delivery) = replay.step() {
+        step += 1;
+        println!("{:>3}: {} -> {} {}", step, name(&delivery.from), name(&Some(delivery.to.clone())), delivery.message.type_name());
+    }
+
+    for (recorded, replayed) in recorded_nodes.iter().zip(replayed_nodes.iter()) {
+        println!("Recorded {:?}, replayed {:?}", recorded.stored("x"), replayed.stored("x"));
+        assert_eq!(recorded.stored("x"), replayed.stored("x"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::{sync::{Arc, Mutex}, time::Instant};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, history::{History, OperationKind}, network::Network, node::{Node, NodeId, RegisterSemantics}};
+use rand::Rng;
+
+const CLIENT_COUNT: usize = 3;
+const OPERATIONS: usize = 12;
+
+// Runs a random workload in every mode and checks the history with the checker of the mode
+fn main() {
+    for semantics in [RegisterSemantics::Safe, RegisterSemantics::Regular, RegisterSemantics::Atomic] {
+        let network = Arc::new(Network::local(3, CLIENT_COUNT));
+
+        for i in 0..3 {
+            let mut node = Node::new(NodeId(i), 2, Arc::clone(&network)).with_semantics(semantics);
+            std::thread::spawn(move || {
+                node.run();
+            });
+        }
+
+        let history = Arc::new(Mutex::new(History::new()));
+        let started = Instant::now();
+
+        let handles: Vec<_> = (0..CLIENT_COUNT).map(|i| {
+            let client = AtomicRegisterClinent::new(ClientId(i as i32), Arc::clone(&network));
+            let history = Arc::clone(&history);
+
+            std::thread::spawn(move || {
+                for operation in 0..OPERATIONS {
+                    let start = history.lock().unwrap().now();
+                    let kind = if rand::thread_rng().gen_bool(0.5) {
+                        let value = format!("Data {} {}", i, operation);
+                        client.write(value.clone());
+                        OperationKind::Write(value)
+                    } else {
+                        OperationKind::Read(client.read().data().to_string())
+                    };
+                    let end = history.lock().unwrap().now();
+
+                    history.lock().unwrap().add(ClientId(i as i32), kind, start, end);
+                }
+            })
+        }).collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let elapsed = started.elapsed();
+
+        let history = history.lock().unwrap();
+        println!("{:?}: {} operations in {:?}", semantics, history.operations().len(), elapsed);
+        println!("  safe: {:?}", history.check_safe());
+        println!("  regular: {:?}", history.check_regular());
+        println!("  linearizable: {:?}", history.check_linearizable());
+
+        history.check(semantics).unwrap();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, diagram::{self, DiagramFormat}, network::Network, node::{Node, NodeId}, replay, trace::NoopSink};
+
+// Usage: sequence_diagram [recording] [mermaid|plantuml|graphviz]
+// Without a recording a write and a read are recorded first. The diagram is printed to stdout.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    let path = match args.get(1) {
+        Some(path) => path.into(),
+        None => {
+            let path = std::env::temp_dir().join("atomic_register_diagram.jsonl");
+            record(&path);
+            path
+        },
+    };
+    let format = match args.get(2).map(|format| format.as_str()) {
+        Some("plantuml") => DiagramFormat::PlantUml,
+        Some("graphviz") => DiagramFormat::Graphviz,
+        Some("mermaid") | None => DiagramFormat::Mermaid,
+        Some(format) => panic!("Unknown format {}", format),
+    };
+
+    let deliveries = replay::load(&path).unwrap();
+    print!("{}", diagram::export(&deliveries, format));
+}
+
+fn record(path: &std::path::Path) {
+    let network = Arc::new(Network::local(3, 2));
+    network.set_trace_sink(Arc::new(NoopSink));
+    network.record_to(path).unwrap();
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let writer = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("x");
+    let reader = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_key("x");
+    writer.write("1".to_string());
+    reader.read();
+    network.stop_recording();
+}
\ No newline at end of file
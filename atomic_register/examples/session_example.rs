@@ -0,0 +1,49 @@
+use std::sync::{Arc, Mutex};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId, RegisterSemantics}, ring::{GroupId, HashRing, ReplicaGroup}, session::Session};
+
+// Safe reads are answered by the coordinator alone. After a failover to a node that missed a write,
+// a client without a session reads the old value, one with a session still reads its own write.
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_semantics(RegisterSemantics::Safe);
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    ring.lock().unwrap().add_group(ReplicaGroup::new(GroupId(0), vec![NodeId(0), NodeId(1), NodeId(2)]));
+
+    let session = Arc::new(Mutex::new(Session::new()));
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network))
+        .with_ring(Arc::clone(&ring))
+        .with_session(Arc::clone(&session))
+        .with_key("profile");
+    let other = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network))
+        .with_ring(Arc::clone(&ring))
+        .with_key("profile");
+
+    // Node 2 misses the write
+    network.set_reachable(&NodeId(2), false);
+    client.write("v1".to_string());
+    network.set_reachable(&NodeId(2), true);
+    println!("Session written up to timestamp {}", session.lock().unwrap().min_timestamp("profile"));
+
+    // Node 2 takes over as coordinator
+    ring.lock().unwrap().add_group(ReplicaGroup::new(GroupId(0), vec![NodeId(2), NodeId(0), NodeId(1)]));
+
+    let without_session = other.read();
+    println!("Without a session: {:?}", without_session);
+    assert_eq!(without_session.version(), 0);
+
+    let with_session = client.read();
+    println!("With a session: {:?}", with_session);
+    assert_eq!(with_session.data(), "v1");
+
+    // Later reads of the session do not go back either
+    assert_eq!(client.read().data(), "v1");
+}
\ No newline at end of file
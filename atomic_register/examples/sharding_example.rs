@@ -0,0 +1,50 @@
+use std::sync::{Arc, Mutex};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, ring::{GroupId, HashRing, ReplicaGroup}};
+
+const KEY_COUNT: usize = 60;
+
+fn start_group(network: &Arc<Network>, group: &ReplicaGroup) {
+    for node_id in &group.members {
+        let mut node = Node::new(node_id.clone(), 2, Arc::clone(network)).with_members(group.members.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+}
+
+fn main() {
+    let network = Arc::new(Network::local(9, 1));
+
+    let groups: Vec<ReplicaGroup> = (0..3)
+        .map(|i| ReplicaGroup::new(GroupId(i), (3 * i..3 * i + 3).map(NodeId).collect()))
+        .collect();
+    for group in &groups {
+        start_group(&network, group);
+    }
+
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    ring.lock().unwrap().add_group(groups[0].clone());
+    ring.lock().unwrap().add_group(groups[1].clone());
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_ring(Arc::clone(&ring));
+
+    for i in 0..KEY_COUNT {
+        client.with_key(&format!("key-{}", i)).write(format!("Data {}", i));
+    }
+
+    // The third group takes over part of the keys of the first two
+    let moved = client.rebalance(groups[2].clone()).unwrap();
+
+    let mut keys_per_group = vec![0; groups.len()];
+    for i in 0..KEY_COUNT {
+        let key = format!("key-{}", i);
+        keys_per_group[ring.lock().unwrap().group_of(&key).id.0 as usize] += 1;
+
+        let data = client.with_key(&key).read();
+        assert_eq!(data.data(), format!("Data {}", i));
+    }
+
+    println!("Moved {} of {} keys, keys per group: {:?}", moved, KEY_COUNT, keys_per_group);
+    assert_eq!(moved, keys_per_group[2]);
+}
\ No newline at end of file
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::ClientId, network::Network, node::{Node, NodeId}, sibling_client::SiblingClient};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client1 = SiblingClient::new(ClientId(0), Arc::clone(&network)).with_key("profile");
+    let client2 = SiblingClient::new(ClientId(1), Arc::clone(&network)).with_key("profile");
+
+    let initial = client1.read().unwrap();
+    client1.write("name=Ann".to_string(), &initial.context).unwrap();
+
+    // Both clients read the same version and write without seeing each other
+    let seen1 = client1.read().unwrap();
+    let seen2 = client2.read().unwrap();
+    client1.write("name=Ann, city=Oslo".to_string(), &seen1.context).unwrap();
+    client2.write("name=Ann, lang=no".to_string(), &seen2.context).unwrap();
+
+    let mut siblings = client1.read().unwrap();
+    siblings.values.sort();
+    println!("Siblings after concurrent writes: {:?}", siblings.values);
+    assert_eq!(siblings.values, vec!["name=Ann, city=Oslo".to_string(), "name=Ann, lang=no".to_string()]);
+
+    // The application merges the siblings and collapses them with the read context
+    client1.write("name=Ann, city=Oslo, lang=no".to_string(), &siblings.context).unwrap();
+
+    let resolved = client2.read().unwrap();
+    println!("Resolved value: {:?}", resolved.values);
+    assert_eq!(resolved.values, vec!["name=Ann, city=Oslo, lang=no".to_string()]);
+}
\ No newline at end of file
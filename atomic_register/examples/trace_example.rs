@@ -0,0 +1,37 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, trace::JsonLinesSink};
+
+// Writes the events of a few operations to a JSON-lines file, one object per event
+fn main() {
+    let path = std::env::temp_dir().join("atomic_register_trace.jsonl");
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(JsonLinesSink::create(&path).unwrap()));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("x");
+    client.write("1".to_string());
+    client.write("2".to_string());
+    assert_eq!(client.read().value(), Some("2"));
+    client.delete();
+
+    let trace = std::fs::read_to_string(&path).unwrap();
+    let mut phases = BTreeMap::new();
+    for line in trace.lines() {
+        let phase = line.split("\"phase\":\"").nth(1).unwrap().split('"').next().unwrap();
+        *phases.entry(phase.to_string()).or_insert(0) += 1;
+    }
+    println!("Events per phase in {:?}: {:?}", path, phases);
+
+    assert_eq!(phases["write.start"], 2);
+    // Deletes write a tombstone version
+    assert_eq!(phases["write.commit"], 3);
+    assert_eq!(phases["delete.start"], 1);
+    assert_eq!(phases["read.start"], 1);
+}
\ No newline at end of file
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, transaction::TransactionError};
+
+const TRANSFERS: usize = 5;
+
+// Moves one unit from one account to the other, retrying when another transfer got in between
+fn transfer(client: &AtomicRegisterClinent, from: &str, to: &str) -> usize {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let mut transaction = client.transaction();
+
+        let from_balance: i32 = transaction.read(from).parse().unwrap_or(0);
+        let to_balance: i32 = transaction.read(to).parse().unwrap_or(0);
+        transaction.write(from, (from_balance - 1).to_string());
+        transaction.write(to, (to_balance + 1).to_string());
+
+        match transaction.commit() {
+            Ok(()) => return attempts,
+            Err(TransactionError::Conflict) => continue,
+            Err(error) => panic!("Transfer failed: {:?}", error),
+        }
+    }
+}
+
+fn main() {
+    let network = Arc::new(Network::local(3, 3));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let setup = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    setup.with_key("alice").write("10".to_string());
+    setup.with_key("bob").write("10".to_string());
+
+    let handles: Vec<_> = [("alice", "bob"), ("bob", "alice")].into_iter().enumerate()
+        .map(|(i, (from, to))| {
+            let client = AtomicRegisterClinent::new(ClientId(i as i32 + 1), Arc::clone(&network));
+            std::thread::spawn(move || (0..TRANSFERS).map(|_| transfer(&client, from, to)).sum::<usize>())
+        })
+        .collect();
+
+    let attempts: usize = handles.into_iter().map(|handle| handle.join().unwrap()).sum();
+
+    let alice: i32 = setup.with_key("alice").read().data().parse().unwrap();
+    let bob: i32 = setup.with_key("bob").read().data().parse().unwrap();
+    println!("{} transfers took {} attempts, alice has {} and bob has {}", 2 * TRANSFERS, attempts, alice, bob);
+    assert_eq!(alice + bob, 20);
+    assert_eq!((alice, bob), (10, 10));
+}
\ No newline at end of file
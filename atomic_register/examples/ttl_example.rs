@@ -0,0 +1,40 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 1));
+
+    let mut nodes = Vec::new();
+    for i in 0..3 {
+        let node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        nodes.push(node.clone());
+
+        let mut node = node;
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("session");
+
+    client.write_with_ttl("token 1".to_string(), Duration::from_millis(300));
+    assert_eq!(client.read().value(), Some("token 1"));
+
+    std::thread::sleep(Duration::from_millis(500));
+
+    // Absent on the coordinator and dropped by every replica that got the write
+    let data = client.read();
+    println!("Read after expiry: {:?}, value {:?}", data, data.value());
+    assert_eq!(data.value(), None);
+    for node in &nodes {
+        let stored = node.stored("session");
+        assert!(stored.version() == 0 || stored.data().is_empty());
+    }
+
+    // A new write gets a version above the expired one
+    client.write("token 2".to_string());
+    let data = client.read();
+    assert_eq!((data.value(), data.version()), (Some("token 2"), 2));
+    println!("Read after new write: {:?}", data);
+}
\ No newline at end of file
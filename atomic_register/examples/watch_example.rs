@@ -0,0 +1,45 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId, Timestamp}};
+
+fn main() {
+    let network = Arc::new(Network::local(3, 2));
+
+    for i in 0..3 {
+        let mut node = Node::new(NodeId(i), 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let writer = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("config");
+    writer.write("Data 1".to_string());
+
+    // The watcher gets the current value first, then every committed write
+    let mut watch = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_key("config").watch(Timestamp::default());
+
+    writer.write("Data 2".to_string());
+    writer.write("Data 3".to_string());
+
+    for _ in 0..3 {
+        let event = watch.next().unwrap();
+        println!("Watcher saw {:?} at version {}", event.data.data(), event.data.version());
+    }
+    assert_eq!(watch.last_timestamp().version, 3);
+
+    // Reconnect: the watch is dropped, writes go on and a new watch resumes from the last seen timestamp
+    let last_timestamp = watch.last_timestamp().clone();
+    drop(watch);
+
+    writer.write("Data 4".to_string());
+    writer.write("Data 5".to_string());
+
+    let mut watch = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_key("config").watch(last_timestamp.clone());
+    // Version 4 only shows up when its notification was sent before the old watch was gone
+    while watch.last_timestamp().version < 5 {
+        let event = watch.next_timeout(Duration::from_secs(1)).unwrap();
+        println!("Resumed watcher saw {:?} at version {}", event.data.data(), event.data.version());
+        assert!(event.data.timestamp() > last_timestamp);
+    }
+    assert!(watch.next_timeout(Duration::from_millis(100)).is_none());
+}
\ No newline at end of file
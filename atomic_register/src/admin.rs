@@ -0,0 +1,65 @@
+use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{membership::Configuration, node::{NodeData, NodeId, NodeState}, quorum::{QuorumState, Round}, store::Key};
+
+// Registers a status carries at most, a node holds any number of them
+pub const STATUS_REGISTERS: usize = 100;
+
+// Snapshot of what a node is doing, answered by Node::status or an AdminStatusRequest
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeStatus {
+    pub id: NodeId,
+    pub node_state: NodeState,
+    pub quorum_state: QuorumState,
+    pub quorum_round: Round,
+    pub configuration: Configuration,
+    pub inbox_len: usize,
+    pub in_flight: Vec<String>,
+    // Members of the configuration and whether the network reaches them
+    pub peers: Vec<(NodeId, bool)>,
+    pub register_count: usize,
+    // The first STATUS_REGISTERS registers in key order
+    pub registers: Vec<(Key, NodeData)>,
+}
+
+// Client operations a coordinator is working on
+#[derive(Default)]
+pub struct InFlight {
+    next_id: u64,
+    operations: BTreeMap<u64, String>,
+}
+
+impl InFlight {
+    pub fn start(in_flight: &Arc<Mutex<InFlight>>, description: String) -> OperationGuard {
+        let mut operations = in_flight.lock().unwrap();
+        operations.next_id += 1;
+        let id = operations.next_id;
+        operations.operations.insert(id, description);
+
+        OperationGuard { in_flight: Arc::clone(in_flight), id }
+    }
+
+    pub fn descriptions(&self) -> Vec<String> {
+        self.operations.values().cloned().collect()
+    }
+}
+
+// Removes the operation when the handler returns
+pub struct OperationGuard {
+    in_flight: Arc<Mutex<InFlight>>,
+    id: u64,
+}
+
+impl OperationGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.in_flight.lock().unwrap().operations.remove(&self.id);
+    }
+}
\ No newline at end of file
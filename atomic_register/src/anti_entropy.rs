@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+pub struct AntiEntropyConfig {
+    // How often a node gossips its version to a random peer
+    pub interval: Duration,
+    // Bytes of pushed data a node may send per interval, entries over the cap wait for a later interval
+    pub max_bytes_per_interval: usize,
+}
+
+impl Default for AntiEntropyConfig {
+    fn default() -> AntiEntropyConfig {
+        AntiEntropyConfig {
+            interval: Duration::from_millis(100),
+            max_bytes_per_interval: 64 * 1024,
+        }
+    }
+}
+
+pub struct AntiEntropy {
+    pub config: AntiEntropyConfig,
+    window_start: Instant,
+    bytes_sent: usize,
+}
+
+impl AntiEntropy {
+    pub fn new(config: AntiEntropyConfig) -> AntiEntropy {
+        AntiEntropy {
+            config,
+            window_start: Instant::now(),
+            bytes_sent: 0,
+        }
+    }
+
+    // Reserves bandwidth for the longest prefix of the chunks that fits and returns its length.
+    // The first chunk of an interval always goes, so a chunk larger than the cap is not stuck forever.
+    pub fn reserve_prefix(&mut self, chunk_bytes: &[usize], now: Instant) -> usize {
+        if now.duration_since(self.window_start) >= self.config.interval {
+            self.window_start = now;
+            self.bytes_sent = 0;
+        }
+
+        let mut reserved = 0;
+        for bytes in chunk_bytes {
+            if self.bytes_sent > 0 && self.bytes_sent + bytes > self.config.max_bytes_per_interval {
+                break;
+            }
+            self.bytes_sent += bytes;
+            reserved += 1;
+        }
+        reserved
+    }
+}
\ No newline at end of file
@@ -1,13 +1,47 @@
-use std::sync::Arc;
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
 
-use crate::{network::Network, node::Message};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+use crate::{admin::NodeStatus, crdt::{CrdtMode, CrdtUpdate, CrdtValue, VersionVector}, membership::Configuration, metrics::CLIENT_OPERATION_SECONDS, network::Network, node::{Message, NodeData, NodeId, Timestamp}, replay::Endpoint, ring::{HashRing, ReplicaGroup}, session::Session, store::{Key, ReplicaState}, trace::TraceEvent, transaction::{ReadSet, Transaction, TransactionError, WriteSet}, watch::Watch};
+
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct ClientId(pub i32);
 
+// CRDT operations move on to the next node when one does not answer in time
+const CRDT_TIMEOUT: Duration = Duration::from_millis(500);
+const ADMIN_TIMEOUT: Duration = Duration::from_millis(500);
+// Transactions, reconfigurations, moves and CRDT operations wait this long when the client has no timeout of its own
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(30);
+// Pause before a refused session read is sent again
+const SESSION_RETRY: Duration = Duration::from_millis(10);
+// A session read refused this often gives up, also without a deadline
+const SESSION_READ_ATTEMPTS: usize = 200;
+// Pause before a request for a moved key is sent again, by then the ring may point to the new group
+const MOVED_RETRY: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadError {
+    // The coordinator kept refusing the session read, the newest timestamp it found is below the session
+    SessionBehind(Timestamp),
+    // The coordinator did not answer in time
+    Timeout,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestError {
+    // The node did not answer in time, the request may still take effect
+    Timeout,
+}
+
 pub struct AtomicRegisterClinent {
     id: ClientId,
     network: Arc<Network>,
+    key: Key,
+    ring: Option<Arc<Mutex<HashRing>>>,
+    crdt: Option<CrdtMode>,
+    // How long try_write and try_read wait for the coordinator, forever when None
+    timeout: Option<Duration>,
+    session: Option<Arc<Mutex<Session>>>,
 }
 
 impl AtomicRegisterClinent {
@@ -15,38 +49,407 @@ impl AtomicRegisterClinent {
         AtomicRegisterClinent {
             id,
             network,
+            key: "".to_string(),
+            ring: None,
+            crdt: None,
+            timeout: None,
+            session: None,
+        }
+    }
+
+    // Routes every key to the coordinator of its replica group, clients sharing the ring see rebalances at once
+    pub fn with_ring(self, ring: Arc<Mutex<HashRing>>) -> AtomicRegisterClinent {
+        AtomicRegisterClinent {
+            ring: Some(ring),
+            ..self
+        }
+    }
+
+    // The key is a CRDT register of the given mode, used through crdt_write and crdt_read
+    pub fn with_crdt(self, mode: CrdtMode) -> AtomicRegisterClinent {
+        AtomicRegisterClinent {
+            crdt: Some(mode),
+            ..self
+        }
+    }
+
+    // A response that comes after the timeout is still in the channel of the client id,
+    // so after a failed try_write or try_read the next operation should use another id
+    pub fn with_timeout(self, timeout: Duration) -> AtomicRegisterClinent {
+        AtomicRegisterClinent {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    // Reads and writes of every key go through the session, which can be handed to a client of another coordinator
+    pub fn with_session(self, session: Arc<Mutex<Session>>) -> AtomicRegisterClinent {
+        AtomicRegisterClinent {
+            session: Some(session),
+            ..self
+        }
+    }
+
+    // Client of another register with the same id, so it must not be used concurrently with this one
+    pub fn with_key(&self, key: &str) -> AtomicRegisterClinent {
+        AtomicRegisterClinent {
+            id: self.id.clone(),
+            network: Arc::clone(&self.network),
+            key: key.to_string(),
+            ring: self.ring.clone(),
+            crdt: self.crdt,
+            timeout: self.timeout,
+            session: self.session.clone(),
         }
     }
 
     pub fn write(&self, data: String) {
-        self.network.send(Message::ClientWriteRequest((self.id.clone(), data)));
+        self.write_key(data, None, None);
+    }
 
-        loop {
-            let acks = self.network.get(&self.id);
+    // False when the write was not acknowledged in time, it may still take effect later
+    pub fn try_write(&self, data: String) -> bool {
+        self.write_key(data, None, self.deadline())
+    }
 
-            match acks {
-                Some(Message::WriteAck(node_id)) => {
-                    println!("Client {:?} got ack from node: {:?}", self.id, node_id);
-                    break;
-                },
+    // The value reads as absent once the time to live is over
+    pub fn write_with_ttl(&self, data: String, ttl: Duration) {
+        self.write_key(data, Some(ttl), None);
+    }
+
+    // Writes a tombstone, reads report the register as absent
+    pub fn delete(&self) {
+        let started = Instant::now();
+        match &self.session {
+            Some(session) => {
+                self.request(&self.key, Message::SessionDeleteRequest((self.id.clone(), self.key.clone())), None, |response| self.accept_session_write_ack(session, response));
+            },
+            None => {
+                self.request(&self.key, Message::ClientDeleteRequest((self.id.clone(), self.key.clone())), None, |response| self.accept_write_ack(response));
+            },
+        }
+        self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "delete")], started.elapsed());
+    }
+
+    // Panics when a session read stays refused, try_read returns the error instead
+    pub fn read(&self) -> NodeData {
+        self.read_key(None).unwrap()
+    }
+
+    pub fn try_read(&self) -> Result<NodeData, ReadError> {
+        self.read_key(self.deadline())
+    }
+
+    // Pass the context of the last crdt_read so the write replaces the values read,
+    // last-writer-wins registers ignore it
+    pub fn crdt_write(&self, data: String, context: &VersionVector) -> Result<CrdtValue, RequestError> {
+        let update = CrdtUpdate {
+            mode: self.crdt.expect("Client without CRDT mode"),
+            data,
+            context: context.clone(),
+        };
+        self.crdt_request(Message::CrdtWrite((self.id.clone(), self.key.clone(), update)))
+    }
+
+    pub fn crdt_read(&self) -> Result<CrdtValue, RequestError> {
+        self.crdt_request(Message::CrdtRead((self.id.clone(), self.key.clone())))
+    }
+
+    // Tries the coordinator of the key first and then every other node, round after round until the deadline
+    fn crdt_request(&self, message: Message) -> Result<CrdtValue, RequestError> {
+        let started = Instant::now();
+        let deadline = started + self.timeout.unwrap_or(OPERATION_TIMEOUT);
+        let operation = match message {
+            Message::CrdtWrite(_) => "crdt_write",
+            _ => "crdt_read",
+        };
+        let coordinator = self.coordinator_of(&self.key);
+        let mut node_ids = vec![coordinator.clone()];
+        node_ids.extend(self.network.node_ids().into_iter().filter(|node_id| *node_id != coordinator));
+
+        for node_id in node_ids.iter().cycle() {
+            if Instant::now() >= deadline {
+                break;
+            }
+            self.send_to_node(node_id, message.clone());
+
+            let node_deadline = Some((Instant::now() + CRDT_TIMEOUT).min(deadline));
+            while let Some(response) = self.receive(node_deadline) {
+                if let Message::CrdtResponse(value) = response {
+                    self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", operation)], started.elapsed());
+                    self.network.trace(TraceEvent::new(&format!("client.{}", operation)).client(&self.id).message_type("CrdtResponse").detail(format!("{:?} from node {:?}", value.values, node_id)));
+                    return Ok(value);
+                }
+            }
+        }
+
+        Err(RequestError::Timeout)
+    }
+
+    pub fn transaction(&self) -> Transaction<'_> {
+        Transaction::new(self)
+    }
+
+    pub fn commit(&self, reads: ReadSet, writes: WriteSet) -> Result<(), TransactionError> {
+        let mut coordinators: Vec<NodeId> = reads.iter().map(|(key, _)| key)
+            .chain(writes.iter().map(|(key, _)| key))
+            .map(|key| self.coordinator_of(key))
+            .collect();
+        coordinators.sort_by_key(|node_id| node_id.0);
+        coordinators.dedup();
+        if coordinators.len() > 1 {
+            return Err(TransactionError::CrossGroup);
+        }
+
+        let coordinator = coordinators.pop().unwrap_or_else(|| self.network.coordinator_id().clone());
+        let started = Instant::now();
+        let committed = self.ask(&coordinator, Message::TransactionCommit((self.id.clone(), reads, writes)), |response| match response {
+            Message::TransactionResult(committed) => Some(committed),
+            _ => None,
+        }).map_err(|_| TransactionError::Timeout)?;
+
+        self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "transaction")], started.elapsed());
+        self.network.trace(TraceEvent::new("client.transaction").client(&self.id).message_type("TransactionResult").detail(format!("committed {}", committed)));
+        if committed { Ok(()) } else { Err(TransactionError::Conflict) }
+    }
+
+    // Notifications share the client channel with responses, so the client is used up by the watch
+    pub fn watch(self, from: Timestamp) -> Watch {
+        let coordinator = self.coordinator_of(&self.key);
+        Watch::new(self.id, self.network, coordinator, self.key, from)
+    }
+
+    // None when the node does not answer
+    pub fn status(&self, node_id: &NodeId) -> Option<NodeStatus> {
+        self.send_to_node(node_id, Message::AdminStatusRequest(self.id.clone()));
+
+        while let Some(message) = self.network.get_timeout(&self.id, ADMIN_TIMEOUT) {
+            match message {
+                Message::AdminStatusResponse(status) if status.id == *node_id => return Some(*status),
                 _ => {},
             }
         }
+        None
     }
 
-    pub fn read(&self) {
-        self.network.send(Message::ClientReadRequest(self.id.clone()));
+    // Returns the installed configuration, or the current one when the coordinator rejected the change
+    pub fn reconfigure(&self, members: Vec<NodeId>) -> Result<Configuration, RequestError> {
+        let configuration = self.ask(self.network.coordinator_id(), Message::Reconfigure((self.id.clone(), members)), |response| match response {
+            Message::ReconfigureAck(configuration) => Some(configuration),
+            _ => None,
+        })?;
+        self.network.trace(TraceEvent::new("client.reconfigure").client(&self.id).message_type("ReconfigureAck").detail(format!("{:?}", configuration)));
+        Ok(configuration)
+    }
 
-        loop {
-            let data = self.network.get(&self.id);
+    // Adds a replica group to the ring and copies the registers it takes over from the other groups.
+    // The old coordinators refuse the moved keys from the start of the copy, clients retry them
+    // at the new coordinator once the ring changed. Returns the number of moved registers.
+    // Stops at the first coordinator that does not answer in time, the registers moved so far stay moved
+    pub fn rebalance(&self, group: ReplicaGroup) -> Result<usize, RequestError> {
+        let ring = self.ring.as_ref().expect("Client without ring");
+        let old_ring = ring.lock().unwrap().clone();
+        let mut new_ring = old_ring.clone();
+        new_ring.add_group(group.clone());
 
-            match data {
-                Some(Message::ClientReadResponse(node_data)) => {
-                    println!("Client {:?} got data from node: {:?}", self.id, node_data);
-                    break;
+        let mut moved = 0;
+        let mut removals = Vec::new();
+        for old_group in old_ring.groups() {
+            let keys: Vec<Key> = self.keys_of(old_group.coordinator())?.into_iter()
+                .filter(|key| new_ring.group_of(key).id == group.id && old_ring.group_of(key).id == old_group.id)
+                .collect();
+            if keys.is_empty() {
+                continue;
+            }
+
+            // Values keep their versions, tombstones and expiry times, siblings and CRDT registers go along
+            let state = self.move_out(old_group.coordinator(), keys.clone())?;
+            moved += state.len();
+            self.ask(group.coordinator(), Message::MoveInRequest((self.id.clone(), state)), |response| self.accept_write_ack(response))?;
+            removals.push((old_group.coordinator().clone(), keys));
+        }
+
+        *ring.lock().unwrap() = new_ring;
+        for (coordinator, keys) in removals {
+            self.ask(&coordinator, Message::RemoveMovedRequest((self.id.clone(), keys)), |response| self.accept_write_ack(response))?;
+        }
+        self.network.trace(TraceEvent::new("client.rebalance").client(&self.id).detail(format!("{} registers to group {:?}", moved, group.id)));
+        Ok(moved)
+    }
+
+    fn send_to_node(&self, node_id: &NodeId, message: Message) {
+        self.network.send_to_node_from(&Endpoint::Client(self.id.clone()), node_id, message);
+    }
+
+    fn coordinator_of(&self, key: &str) -> NodeId {
+        match &self.ring {
+            Some(ring) => ring.lock().unwrap().group_of(key).coordinator().clone(),
+            None => self.network.coordinator_id().clone(),
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.timeout.map(|timeout| Instant::now() + timeout)
+    }
+
+    // Waits forever without a deadline
+    fn receive(&self, deadline: Option<Instant>) -> Option<Message> {
+        match deadline {
+            Some(deadline) => self.network.get_timeout(&self.id, deadline.saturating_duration_since(Instant::now())),
+            None => self.network.get(&self.id),
+        }
+    }
+
+    fn write_key(&self, data: String, ttl: Option<Duration>, deadline: Option<Instant>) -> bool {
+        match &self.session {
+            Some(session) => self.session_write(session, data, ttl, deadline),
+            None => self.write_to(data, ttl, deadline),
+        }
+    }
+
+    fn read_key(&self, deadline: Option<Instant>) -> Result<NodeData, ReadError> {
+        match &self.session {
+            Some(session) => self.session_read(session, deadline),
+            None => self.read_from(deadline).ok_or(ReadError::Timeout),
+        }
+    }
+
+    // Sends the message to one node and waits until accept takes an answer,
+    // for at most the timeout of the client or OPERATION_TIMEOUT
+    fn ask<T>(&self, node_id: &NodeId, message: Message, mut accept: impl FnMut(Message) -> Option<T>) -> Result<T, RequestError> {
+        self.send_to_node(node_id, message);
+
+        let deadline = Some(Instant::now() + self.timeout.unwrap_or(OPERATION_TIMEOUT));
+        while let Some(response) = self.receive(deadline) {
+            if let Some(result) = accept(response) {
+                return Ok(result);
+            }
+        }
+        Err(RequestError::Timeout)
+    }
+
+    // Sends the request to the coordinator of the key and waits until accept takes an answer.
+    // A request for a moved key is sent again to whatever coordinator the ring names by then.
+    fn request<T>(&self, key: &str, message: Message, deadline: Option<Instant>, mut accept: impl FnMut(Message) -> Option<T>) -> Option<T> {
+        self.send_to_node(&self.coordinator_of(key), message.clone());
+
+        while let Some(response) = self.receive(deadline) {
+            match response {
+                Message::KeyMoved(moved_key) if moved_key == key => {
+                    std::thread::sleep(MOVED_RETRY);
+                    self.send_to_node(&self.coordinator_of(key), message.clone());
                 },
-                _ => {},
+                response => {
+                    if let Some(result) = accept(response) {
+                        return Some(result);
+                    }
+                },
+            }
+        }
+        None
+    }
+
+    fn write_to(&self, data: String, ttl: Option<Duration>, deadline: Option<Instant>) -> bool {
+        let (operation, message) = match ttl {
+            Some(ttl) => ("write_with_ttl", Message::ClientWriteWithTtlRequest((self.id.clone(), self.key.clone(), data, ttl))),
+            None => ("write", Message::ClientWriteRequest((self.id.clone(), self.key.clone(), data))),
+        };
+        let started = Instant::now();
+        if self.request(&self.key, message, deadline, |response| self.accept_write_ack(response)).is_none() {
+            return false;
+        }
+        self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", operation)], started.elapsed());
+        true
+    }
+
+    fn accept_write_ack(&self, message: Message) -> Option<()> {
+        if let Message::WriteAck((node_id, _, _)) = message {
+            self.network.trace(TraceEvent::new("client.write_ack").client(&self.id).message_type("WriteAck").detail(format!("from node {:?}", node_id)));
+            return Some(());
+        }
+        None
+    }
+
+    fn read_from(&self, deadline: Option<Instant>) -> Option<NodeData> {
+        let started = Instant::now();
+
+        self.request(&self.key, Message::ClientReadRequest((self.id.clone(), self.key.clone())), deadline, |response| {
+            if let Message::ClientReadResponse(node_data) = response {
+                self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "read")], started.elapsed());
+                self.network.trace(TraceEvent::new("client.read_response").client(&self.id).message_type("ClientReadResponse").detail(format!("{:?}", node_data)));
+                return Some(node_data);
+            }
+            None
+        })
+    }
+
+    fn session_write(&self, session: &Mutex<Session>, data: String, ttl: Option<Duration>, deadline: Option<Instant>) -> bool {
+        let started = Instant::now();
+
+        self.request(&self.key, Message::SessionWriteRequest((self.id.clone(), self.key.clone(), data, ttl)), deadline, |response| {
+            self.accept_session_write_ack(session, response)?;
+            let operation = if ttl.is_some() { "write_with_ttl" } else { "write" };
+            self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", operation)], started.elapsed());
+            Some(())
+        }).is_some()
+    }
+
+    fn accept_session_write_ack(&self, session: &Mutex<Session>, message: Message) -> Option<()> {
+        if let Message::SessionWriteAck((key, timestamp)) = message {
+            session.lock().unwrap().written(&key, timestamp);
+            return Some(());
+        }
+        None
+    }
+
+    // A refused read is sent again until the deadline or the last attempt, by then the quorum may have the version
+    // or the ring may point to another coordinator
+    fn session_read(&self, session: &Mutex<Session>, deadline: Option<Instant>) -> Result<NodeData, ReadError> {
+        let started = Instant::now();
+        let mut newest_refused = Timestamp::default();
+
+        for _ in 0..SESSION_READ_ATTEMPTS {
+            let min_timestamp = session.lock().unwrap().min_timestamp(&self.key);
+            let coordinator = self.coordinator_of(&self.key);
+            self.send_to_node(&coordinator, Message::SessionReadRequest((self.id.clone(), self.key.clone(), min_timestamp.clone())));
+
+            loop {
+                match self.receive(deadline).ok_or(ReadError::Timeout)? {
+                    Message::ClientReadResponse(node_data) => {
+                        session.lock().unwrap().observe(&self.key, &node_data);
+                        self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "read")], started.elapsed());
+                        return Ok(node_data);
+                    },
+                    Message::SessionReadRefused((_, timestamp)) => {
+                        self.network.trace(TraceEvent::new("client.session_refused").client(&self.id).message_type("SessionReadRefused").detail(format!("timestamp {} below {}", timestamp, min_timestamp)));
+                        newest_refused = timestamp;
+                        break;
+                    },
+                    Message::KeyMoved(_) => break,
+                    _ => {},
+                }
             }
+
+            if deadline.map(|deadline| Instant::now() >= deadline).unwrap_or(false) {
+                return Err(ReadError::Timeout);
+            }
+            std::thread::sleep(SESSION_RETRY);
         }
+
+        Err(ReadError::SessionBehind(newest_refused))
+    }
+
+    fn move_out(&self, coordinator: &NodeId, keys: Vec<Key>) -> Result<ReplicaState, RequestError> {
+        self.ask(coordinator, Message::MoveOutRequest((self.id.clone(), keys)), |response| match response {
+            Message::MoveOutResponse(state) => Some(state),
+            _ => None,
+        })
+    }
+
+    fn keys_of(&self, coordinator: &NodeId) -> Result<Vec<Key>, RequestError> {
+        self.ask(coordinator, Message::KeysRequest(self.id.clone()), |response| match response {
+            Message::KeysResponse(keys) => Some(keys),
+            _ => None,
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,376 @@
+use std::{collections::HashMap, fs::File, io::Write, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, history::{History, OperationKind}, network::Network, node::{Node, NodeId}, replay::Endpoint, trace::NoopSink};
+use rand::{seq::SliceRandom, Rng};
+
+// Runs a concurrent read/write workload against a local cluster while a nemesis pauses nodes and partitions
+// the network, then checks the history of every key for linearizability.
+// Usage: cargo run --bin jepsen -- [--nodes 3] [--workers 8] [--keys 1] [--duration 10] [--read-ratio 0.5]
+//     [--timeout-ms 500] [--nemesis pause,partition] [--interval-ms 1000] [--no-fault-coordinator] [--history path]
+// Exits with 1 when a history is not linearizable.
+
+// A worker moves to a fresh client id after a timeout so late responses do not answer its next operation
+const CLIENTS_PER_WORKER: usize = 64;
+
+struct Options {
+    nodes: usize,
+    workers: usize,
+    keys: usize,
+    duration: Duration,
+    read_ratio: f64,
+    timeout: Duration,
+    nemesis: Vec<String>,
+    interval: Duration,
+    fault_coordinator: bool,
+    history: Option<String>,
+}
+
+impl Options {
+    fn parse() -> Options {
+        let mut options = Options {
+            nodes: 3,
+            workers: 8,
+            keys: 1,
+            duration: Duration::from_secs(10),
+            read_ratio: 0.5,
+            timeout: Duration::from_millis(500),
+            nemesis: vec!["pause".to_string(), "partition".to_string()],
+            interval: Duration::from_millis(1000),
+            fault_coordinator: true,
+            history: None,
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            // The coordinator is a fault target like any other node unless this is given
+            if arg == "--no-fault-coordinator" {
+                options.fault_coordinator = false;
+                continue;
+            }
+            let value = args.next().unwrap_or_else(|| panic!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--nodes" => options.nodes = value.parse().unwrap(),
+                "--workers" => options.workers = value.parse().unwrap(),
+                "--keys" => options.keys = value.parse().unwrap(),
+                "--duration" => options.duration = Duration::from_secs_f64(value.parse().unwrap()),
+                "--read-ratio" => options.read_ratio = value.parse().unwrap(),
+                "--timeout-ms" => options.timeout = Duration::from_millis(value.parse().unwrap()),
+                "--nemesis" => options.nemesis = value.split(',').filter(|name| !name.is_empty() && *name != "none").map(|name| name.to_string()).collect(),
+                "--interval-ms" => options.interval = Duration::from_millis(value.parse().unwrap()),
+                "--history" => options.history = Some(value),
+                _ => panic!("Unknown option {}", arg),
+            }
+        }
+
+        for name in &options.nemesis {
+            if name != "pause" && name != "partition" {
+                panic!("Unknown nemesis {}, expected pause, partition or none", name);
+            }
+        }
+        options
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    // The operation did not happen
+    Fail,
+    // Timed out, a write may still take effect
+    Info,
+}
+
+struct Record {
+    worker: usize,
+    client: ClientId,
+    key: usize,
+    kind: OperationKind,
+    start: Duration,
+    end: Duration,
+    outcome: Outcome,
+}
+
+enum Fault {
+    Pause(NodeId),
+    Partition,
+}
+
+fn main() {
+    let options = Options::parse();
+    let network = Arc::new(Network::local(options.nodes, options.workers * CLIENTS_PER_WORKER));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, options.nodes / 2 + 1, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    println!(
+        "{} nodes, {} workers, {} keys, {:?}, read ratio {}, timeout {:?}, nemesis [{}] every {:?}",
+        options.nodes,
+        options.workers,
+        options.keys,
+        options.duration,
+        options.read_ratio,
+        options.timeout,
+        options.nemesis.join(","),
+        options.interval,
+    );
+
+    let started = Instant::now();
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let next_client = Arc::new(AtomicUsize::new(0));
+
+    let nemesis = {
+        let network = Arc::clone(&network);
+        let stopped = Arc::clone(&stopped);
+        let nemesis = options.nemesis.clone();
+        let interval = options.interval;
+        let fault_coordinator = options.fault_coordinator;
+        std::thread::spawn(move || run_nemesis(&network, &stopped, &nemesis, interval, fault_coordinator, started))
+    };
+
+    let workers: Vec<_> = (0..options.workers).map(|worker| {
+        let network = Arc::clone(&network);
+        let records = Arc::clone(&records);
+        let next_client = Arc::clone(&next_client);
+        let keys = options.keys;
+        let read_ratio = options.read_ratio;
+        let timeout = options.timeout;
+        let deadline = started + options.duration;
+        std::thread::spawn(move || {
+            let mut rng = rand::thread_rng();
+            let mut written = 0;
+            let mut client_id = ClientId(next_client.fetch_add(1, Ordering::SeqCst) as i32);
+            let mut client = AtomicRegisterClinent::new(client_id.clone(), Arc::clone(&network)).with_timeout(timeout);
+
+            while Instant::now() < deadline {
+                let key = rng.gen_range(0..keys);
+                let keyed = client.with_key(&format!("k{}", key));
+                let start = started.elapsed();
+
+                let (kind, outcome) = if rng.gen_bool(read_ratio) {
+                    match keyed.try_read() {
+                        Ok(data) => (OperationKind::Read(data.data().to_string()), Outcome::Ok),
+                        Err(_) => (OperationKind::Read("".to_string()), Outcome::Fail),
+                    }
+                } else {
+                    written += 1;
+                    let value = format!("w{}-{}", worker, written);
+                    let outcome = if keyed.try_write(value.clone()) { Outcome::Ok } else { Outcome::Info };
+                    (OperationKind::Write(value), outcome)
+                };
+
+                records.lock().unwrap().push(Record { worker, client: client_id.clone(), key, kind, start, end: started.elapsed(), outcome });
+
+                if outcome != Outcome::Ok {
+                    let next = next_client.fetch_add(1, Ordering::SeqCst);
+                    if next >= network.client_ids().len() {
+                        println!("worker {} ran out of client ids", worker);
+                        return;
+                    }
+                    client_id = ClientId(next as i32);
+                    client = AtomicRegisterClinent::new(client_id.clone(), Arc::clone(&network)).with_timeout(timeout);
+                }
+            }
+        })
+    }).collect();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    stopped.store(true, Ordering::SeqCst);
+    let nemesis_log = nemesis.join().unwrap();
+    let elapsed = started.elapsed();
+
+    let records = records.lock().unwrap();
+    let mut histories: Vec<History> = (0..options.keys).map(|_| History::new()).collect();
+    for record in records.iter() {
+        match (record.outcome, &record.kind) {
+            (Outcome::Ok, _) => histories[record.key].add(record.client.clone(), record.kind.clone(), record.start, record.end),
+            // A write that timed out may take effect at any time after it started
+            (Outcome::Info, OperationKind::Write(_)) => histories[record.key].add(record.client.clone(), record.kind.clone(), record.start, Duration::MAX),
+            _ => {}
+        }
+    }
+
+    if let Some(path) = &options.history {
+        write_history(path, &records);
+    }
+
+    println!();
+    println!("nemesis:");
+    for (at, event) in &nemesis_log {
+        println!("  {:>8.3}s {}", at.as_secs_f64(), event);
+    }
+
+    let count = |outcome| records.iter().filter(|record| record.outcome == outcome).count();
+    println!();
+    println!(
+        "operations: {} ok, {} fail, {} info, {:.1} ok/s",
+        count(Outcome::Ok),
+        count(Outcome::Fail),
+        count(Outcome::Info),
+        count(Outcome::Ok) as f64 / elapsed.as_secs_f64(),
+    );
+
+    let mut latencies: HashMap<&str, Vec<Duration>> = HashMap::new();
+    for record in records.iter().filter(|record| record.outcome == Outcome::Ok) {
+        let name = match record.kind {
+            OperationKind::Write(_) => "write",
+            OperationKind::Read(_) => "read",
+        };
+        latencies.entry(name).or_default().push(record.end - record.start);
+    }
+    println!("latency (ms)   count     mean      p50      p95      p99      max");
+    for name in ["write", "read"] {
+        if let Some(latencies) = latencies.get_mut(name) {
+            latencies.sort();
+            let mean = latencies.iter().sum::<Duration>() / latencies.len() as u32;
+            println!(
+                "  {:<8} {:>9} {:>8.2} {:>8.2} {:>8.2} {:>8.2} {:>8.2}",
+                name,
+                latencies.len(),
+                millis(mean),
+                millis(percentile(latencies, 50.0)),
+                millis(percentile(latencies, 95.0)),
+                millis(percentile(latencies, 99.0)),
+                millis(*latencies.last().unwrap()),
+            );
+        }
+    }
+
+    println!();
+    let mut valid = true;
+    for (key, history) in histories.iter().enumerate() {
+        match history.check_linearizable() {
+            Ok(()) => println!("k{}: {} operations, linearizable", key, history.operations().len()),
+            Err(error) => {
+                valid = false;
+                println!("k{}: {} operations, NOT linearizable: {}", key, history.operations().len(), error);
+            }
+        }
+    }
+
+    println!();
+    if valid {
+        println!("PASS");
+    } else {
+        println!("FAIL");
+        std::process::exit(1);
+    }
+}
+
+// Alternates between injecting a fault and healing it, returns the log of what it did
+fn run_nemesis(network: &Network, stopped: &AtomicBool, nemesis: &[String], interval: Duration, fault_coordinator: bool, started: Instant) -> Vec<(Duration, String)> {
+    let mut rng = rand::thread_rng();
+    let mut log = Vec::new();
+    let mut fault: Option<Fault> = None;
+
+    let targets: Vec<NodeId> = network.node_ids().into_iter()
+        .filter(|node_id| fault_coordinator || node_id != network.coordinator_id())
+        .collect();
+
+    if nemesis.is_empty() || targets.is_empty() {
+        return log;
+    }
+
+    let mut next = Instant::now() + interval;
+    while !stopped.load(Ordering::SeqCst) {
+        if Instant::now() < next {
+            std::thread::sleep(Duration::from_millis(10));
+            continue;
+        }
+        next += interval;
+
+        match fault.take() {
+            Some(Fault::Pause(node_id)) => {
+                network.heal();
+                network.set_reachable(&node_id, true);
+                log.push((started.elapsed(), format!("resume {:?}", node_id)));
+            }
+            Some(Fault::Partition) => {
+                network.heal();
+                log.push((started.elapsed(), "heal".to_string()));
+            }
+            None => match nemesis.choose(&mut rng).unwrap().as_str() {
+                "pause" => {
+                    let node_id = targets.choose(&mut rng).unwrap().clone();
+                    pause(network, &node_id);
+                    log.push((started.elapsed(), format!("pause {:?}", node_id)));
+                    fault = Some(Fault::Pause(node_id));
+                }
+                _ => {
+                    let node_ids = network.node_ids();
+                    let size = rng.gen_range(1..=((node_ids.len() - 1) / 2).max(1));
+                    let minority: Vec<NodeId> = targets.choose_multiple(&mut rng, size).cloned().collect();
+                    let majority: Vec<NodeId> = node_ids.into_iter().filter(|node_id| !minority.contains(node_id)).collect();
+                    network.partition(&[minority.clone(), majority.clone()]);
+                    log.push((started.elapsed(), format!("partition {:?} from {:?}", minority, majority)));
+                    fault = Some(Fault::Partition);
+                }
+            },
+        }
+    }
+
+    if fault.is_some() {
+        network.heal();
+        for node_id in network.node_ids() {
+            network.set_reachable(&node_id, true);
+        }
+        log.push((started.elapsed(), "heal all".to_string()));
+    }
+    log
+}
+
+// A paused node neither receives nor sends and keeps its state, like a stopped process. A real crash
+// would lose the registers, which nodes without stable storage can not recover from.
+fn pause(network: &Network, node_id: &NodeId) {
+    network.set_reachable(node_id, false);
+    let from = Endpoint::Node(node_id.clone());
+    for other in network.node_ids() {
+        network.set_link(&from, &Endpoint::Node(other), false);
+    }
+    for client_id in network.client_ids() {
+        network.set_link(&from, &Endpoint::Client(client_id), false);
+    }
+}
+
+fn write_history(path: &str, records: &[Record]) {
+    let mut file = File::create(path).unwrap();
+    for record in records {
+        let (operation, value) = match &record.kind {
+            OperationKind::Write(value) => ("write", value),
+            OperationKind::Read(value) => ("read", value),
+        };
+        let outcome = match record.outcome {
+            Outcome::Ok => "ok",
+            Outcome::Fail => "fail",
+            Outcome::Info => "info",
+        };
+        writeln!(
+            file,
+            "{} {} worker={} client={} key=k{} {} {:?} {}",
+            record.start.as_micros(),
+            record.end.as_micros(),
+            record.worker,
+            record.client.0,
+            record.key,
+            operation,
+            value,
+            outcome,
+        ).unwrap();
+    }
+}
+
+fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    let index = ((sorted.len() - 1) as f64 * percent / 100.0).round() as usize;
+    sorted[index]
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
\ No newline at end of file
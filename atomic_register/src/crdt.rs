@@ -0,0 +1,172 @@
+use std::{collections::BTreeMap, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::node::NodeId;
+
+// Per key choice between the quorum register and a register that takes writes on any node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrdtMode {
+    LastWriterWins,
+    MultiValue,
+}
+
+// Highest write counter seen per node
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionVector(BTreeMap<NodeId, u64>);
+
+// The write number counter of node, identifies one written value
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dot(pub NodeId, pub u64);
+
+impl VersionVector {
+    pub fn new() -> VersionVector {
+        VersionVector(BTreeMap::new())
+    }
+
+    pub fn get(&self, node_id: &NodeId) -> u64 {
+        self.0.get(node_id).cloned().unwrap_or(0)
+    }
+
+    pub fn contains(&self, dot: &Dot) -> bool {
+        dot.1 <= self.get(&dot.0)
+    }
+
+    pub fn increment(&mut self, node_id: &NodeId) -> Dot {
+        let counter = self.get(node_id) + 1;
+        self.0.insert(node_id.clone(), counter);
+        Dot(node_id.clone(), counter)
+    }
+
+    pub fn merge(&mut self, other: &VersionVector) {
+        for (node_id, counter) in &other.0 {
+            if *counter > self.get(node_id) {
+                self.0.insert(node_id.clone(), *counter);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LwwRegister {
+    data: String,
+    // Wall clock milliseconds, the node id breaks ties
+    timestamp: (u64, i32),
+}
+
+impl LwwRegister {
+    pub fn write(&mut self, node_id: &NodeId, data: String) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        // Never go back behind a value from a node with a faster clock
+        let millis = now.max(self.timestamp.0 + 1);
+        self.data = data;
+        self.timestamp = (millis, node_id.0);
+    }
+
+    pub fn merge(&mut self, other: &LwwRegister) {
+        if other.timestamp > self.timestamp {
+            *self = other.clone();
+        }
+    }
+}
+
+// Multi-value register with dotted version vectors: a write replaces the values its context has seen,
+// values written concurrently stay side by side until a write with a context covering all of them
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MvRegister {
+    values: Vec<(Dot, String)>,
+    context: VersionVector,
+}
+
+impl MvRegister {
+    pub fn value(&self) -> CrdtValue {
+        CrdtValue {
+            values: self.values.iter().map(|(_, data)| data.clone()).collect(),
+            context: self.context.clone(),
+        }
+    }
+
+    pub fn write(&mut self, node_id: &NodeId, data: String, context: &VersionVector) {
+        self.values.retain(|(dot, _)| !context.contains(dot));
+        self.context.merge(context);
+        let dot = self.context.increment(node_id);
+        self.values.push((dot, data));
+    }
+
+    pub fn merge(&mut self, other: &MvRegister) {
+        let own: Vec<(Dot, String)> = self.values.iter()
+            .filter(|(dot, _)| other.values.iter().any(|(other_dot, _)| other_dot == dot) || !other.context.contains(dot))
+            .cloned()
+            .collect();
+        let theirs: Vec<(Dot, String)> = other.values.iter()
+            .filter(|(dot, _)| !self.values.iter().any(|(own_dot, _)| own_dot == dot) && !self.context.contains(dot))
+            .cloned()
+            .collect();
+
+        self.values = own;
+        self.values.extend(theirs);
+        self.context.merge(&other.context);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CrdtRegister {
+    LastWriterWins(LwwRegister),
+    MultiValue(MvRegister),
+}
+
+// What a client sees: all concurrent values and the context to pass to the next write
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrdtValue {
+    pub values: Vec<String>,
+    pub context: VersionVector,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrdtUpdate {
+    pub mode: CrdtMode,
+    pub data: String,
+    pub context: VersionVector,
+}
+
+impl CrdtRegister {
+    pub fn new(mode: CrdtMode) -> CrdtRegister {
+        match mode {
+            CrdtMode::LastWriterWins => CrdtRegister::LastWriterWins(LwwRegister { data: "".to_string(), timestamp: (0, 0) }),
+            CrdtMode::MultiValue => CrdtRegister::MultiValue(MvRegister::default()),
+        }
+    }
+
+    pub fn mode(&self) -> CrdtMode {
+        match self {
+            CrdtRegister::LastWriterWins(_) => CrdtMode::LastWriterWins,
+            CrdtRegister::MultiValue(_) => CrdtMode::MultiValue,
+        }
+    }
+
+    pub fn write(&mut self, node_id: &NodeId, update: CrdtUpdate) {
+        match self {
+            CrdtRegister::LastWriterWins(register) => register.write(node_id, update.data),
+            CrdtRegister::MultiValue(register) => register.write(node_id, update.data, &update.context),
+        }
+    }
+
+    // Registers of another mode are ignored, the first write decides the mode of a key
+    pub fn merge(&mut self, other: &CrdtRegister) {
+        match (self, other) {
+            (CrdtRegister::LastWriterWins(register), CrdtRegister::LastWriterWins(other)) => register.merge(other),
+            (CrdtRegister::MultiValue(register), CrdtRegister::MultiValue(other)) => register.merge(other),
+            _ => {}
+        }
+    }
+
+    pub fn value(&self) -> CrdtValue {
+        match self {
+            CrdtRegister::LastWriterWins(register) => CrdtValue {
+                values: if register.timestamp.0 == 0 { Vec::new() } else { vec![register.data.clone()] },
+                context: VersionVector::new(),
+            },
+            CrdtRegister::MultiValue(register) => register.value(),
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,236 @@
+use crate::{node::Message, replay::{Delivery, Endpoint}};
+
+// Diagrams of a recording, see Network::record_to and replay::load.
+// Client operations are marked at the request of the client (invoke) and at the answer it gets (response).
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagramFormat {
+    Mermaid,
+    PlantUml,
+    // Space-time diagram with one line per process, render with neato -n2
+    Graphviz,
+}
+
+pub fn export(deliveries: &[Delivery], format: DiagramFormat) -> String {
+    match format {
+        DiagramFormat::Mermaid => to_mermaid(deliveries),
+        DiagramFormat::PlantUml => to_plantuml(deliveries),
+        DiagramFormat::Graphviz => to_graphviz(deliveries),
+    }
+}
+
+pub fn to_mermaid(deliveries: &[Delivery]) -> String {
+    let mut text = String::from("sequenceDiagram\n");
+    for process in processes(deliveries) {
+        text += &format!("    participant {} as {}\n", id(&process), name(&process));
+    }
+
+    for delivery in deliveries {
+        let from = id(&delivery.from);
+        let to = id(&Some(delivery.to.clone()));
+        text += &format!("    {}->>{}: {}\n", from, to, mermaid_escape(&label(&delivery.message)));
+
+        if let Some(operation) = invocation(delivery) {
+            text += &format!("    Note over {}: invoke {}\n", from, mermaid_escape(&operation));
+            text += &format!("    activate {}\n", from);
+        }
+        if let Some(result) = response(delivery) {
+            text += &format!("    Note over {}: return {}\n", to, mermaid_escape(&result));
+            text += &format!("    deactivate {}\n", to);
+        }
+    }
+
+    text
+}
+
+pub fn to_plantuml(deliveries: &[Delivery]) -> String {
+    let mut text = String::from("@startuml\n");
+    for process in processes(deliveries) {
+        text += &format!("participant \"{}\" as {}\n", name(&process), id(&process));
+    }
+
+    for delivery in deliveries {
+        let from = id(&delivery.from);
+        let to = id(&Some(delivery.to.clone()));
+        text += &format!("{} -> {} : {}\n", from, to, label(&delivery.message));
+
+        if let Some(operation) = invocation(delivery) {
+            text += &format!("note over {} : invoke {}\n", from, operation);
+            text += &format!("activate {}\n", from);
+        }
+        if let Some(result) = response(delivery) {
+            text += &format!("note over {} : return {}\n", to, result);
+            text += &format!("deactivate {}\n", to);
+        }
+    }
+
+    text += "@enduml\n";
+    text
+}
+
+// Every delivery is a send event on the line of the sender and a receive event half a step later
+// on the line of the receiver, positions are in points
+pub fn to_graphviz(deliveries: &[Delivery]) -> String {
+    const STEP: usize = 60;
+    const ROW: usize = 80;
+
+    let processes = processes(deliveries);
+    // Graphviz puts y = 0 at the bottom, clients go on top
+    let row = |endpoint: &Option<Endpoint>| (processes.len() - 1 - processes.iter().position(|process| process == endpoint).unwrap()) * ROW;
+
+    let mut text = String::from("digraph spacetime {\n    node [shape=point];\n");
+    let mut events: Vec<Vec<String>> = vec![Vec::new(); processes.len()];
+
+    for (index, process) in processes.iter().enumerate() {
+        text += &format!("    {} [shape=plaintext, label=\"{}\", pos=\"0,{}!\"];\n", id(process), name(process), row(process));
+        events[index].push(id(process));
+    }
+
+    for (step, delivery) in deliveries.iter().enumerate() {
+        let to = Some(delivery.to.clone());
+        let send = format!("s{}", step);
+        let receive = format!("r{}", step);
+
+        match invocation(delivery) {
+            Some(operation) => text += &format!(
+                "    {} [shape=box, style=filled, fillcolor=lightblue, label=\"invoke {}\", pos=\"{},{}!\"];\n",
+                send, escape(&operation), (step + 1) * STEP, row(&delivery.from)
+            ),
+            None => text += &format!("    {} [pos=\"{},{}!\"];\n", send, (step + 1) * STEP, row(&delivery.from)),
+        }
+        match response(delivery) {
+            Some(result) => text += &format!(
+                "    {} [shape=box, style=filled, fillcolor=lightgreen, label=\"return {}\", pos=\"{},{}!\"];\n",
+                receive, escape(&result), (step + 1) * STEP + STEP / 2, row(&to)
+            ),
+            None => text += &format!("    {} [pos=\"{},{}!\"];\n", receive, (step + 1) * STEP + STEP / 2, row(&to)),
+        }
+        text += &format!("    {} -> {} [label=\"{}\", fontsize=8];\n", send, receive, escape(&label(&delivery.message)));
+
+        events[processes.iter().position(|process| *process == delivery.from).unwrap()].push(send);
+        events[processes.iter().position(|process| *process == to).unwrap()].push(receive);
+    }
+
+    // Process lines, the events of a process are already in time order
+    for line in events {
+        for pair in line.windows(2) {
+            text += &format!("    {} -> {} [arrowhead=none, color=gray];\n", pair[0], pair[1]);
+        }
+    }
+
+    text += "}\n";
+    text
+}
+
+// Clients first, then nodes, then the sender of messages sent without one
+fn processes(deliveries: &[Delivery]) -> Vec<Option<Endpoint>> {
+    let mut processes: Vec<Option<Endpoint>> = Vec::new();
+    for delivery in deliveries {
+        for process in [delivery.from.clone(), Some(delivery.to.clone())] {
+            if !processes.contains(&process) {
+                processes.push(process);
+            }
+        }
+    }
+
+    processes.sort_by_key(|process| match process {
+        Some(Endpoint::Client(client_id)) => (0, client_id.0),
+        Some(Endpoint::Node(node_id)) => (1, node_id.0),
+        None => (2, 0),
+    });
+    processes
+}
+
+fn id(process: &Option<Endpoint>) -> String {
+    match process {
+        Some(Endpoint::Client(client_id)) => format!("C{}", client_id.0),
+        Some(Endpoint::Node(node_id)) => format!("N{}", node_id.0),
+        None => "Unknown".to_string(),
+    }
+}
+
+fn name(process: &Option<Endpoint>) -> String {
+    match process {
+        Some(Endpoint::Client(client_id)) => format!("client {}", client_id.0),
+        Some(Endpoint::Node(node_id)) => format!("node {}", node_id.0),
+        None => "unknown".to_string(),
+    }
+}
+
+// Message type with the key and version where the message has them
+fn label(message: &Message) -> String {
+    let details = match message {
+        Message::ClientWriteRequest((_, key, _)) | Message::ClientWriteWithTtlRequest((_, key, _, _)) => format!(" {}", key),
+        Message::ClientDeleteRequest((_, key)) | Message::ClientReadRequest((_, key)) => format!(" {}", key),
+        Message::SessionDeleteRequest((_, key)) => format!(" {}", key),
+        Message::ClientReadResponse(data) => format!(" v{}", data.version()),
+        Message::SessionWriteRequest((_, key, _, _)) => format!(" {}", key),
+        Message::SessionReadRequest((_, key, min_timestamp)) => format!(" {} from v{}", key, min_timestamp),
+        Message::SessionWriteAck((key, timestamp)) | Message::SessionReadRefused((key, timestamp)) => format!(" {} v{}", key, timestamp),
+        Message::CoordinatorReadRequest((_, key, _, _)) => format!(" {}", key),
+        Message::CoordinatorReadResponse((_, key, data, _, _)) | Message::CoordinatorWriteRequest((_, key, data, _, _)) => format!(" {} v{}", key, data.version()),
+        Message::CoordinatorBatchWriteRequest((_, entries, _, _)) => format!(" {} keys", entries.len()),
+        _ => "".to_string(),
+    };
+    format!("{}{}", message.type_name(), details)
+}
+
+fn invocation(delivery: &Delivery) -> Option<String> {
+    let operation = match &delivery.message {
+        Message::ClientWriteRequest((_, key, data)) => format!("write {} = {}", key, data),
+        Message::ClientWriteWithTtlRequest((_, key, data, ttl)) => format!("write {} = {} for {:?}", key, data, ttl),
+        Message::ClientDeleteRequest((_, key)) => format!("delete {}", key),
+        Message::ClientReadRequest((_, key)) => format!("read {}", key),
+        Message::SessionWriteRequest((_, key, data, _)) => format!("session write {} = {}", key, data),
+        Message::SessionDeleteRequest((_, key)) => format!("session delete {}", key),
+        Message::SessionReadRequest((_, key, min_timestamp)) => format!("session read {} from v{}", key, min_timestamp),
+        Message::TransactionCommit((_, reads, writes)) => format!("transaction of {} reads and {} writes", reads.len(), writes.len()),
+        Message::SiblingWriteRequest((_, key, data, _)) => format!("sibling write {} = {}", key, data),
+        Message::SiblingReadRequest((_, key)) => format!("sibling read {}", key),
+        Message::CrdtWrite((_, key, update)) => format!("crdt write {} = {}", key, update.data),
+        Message::CrdtRead((_, key)) => format!("crdt read {}", key),
+        Message::Reconfigure((_, members)) => format!("reconfigure to {:?}", members.iter().map(|node_id| node_id.0).collect::<Vec<_>>()),
+        _ => return None,
+    };
+    Some(operation)
+}
+
+fn response(delivery: &Delivery) -> Option<String> {
+    if !matches!(delivery.to, Endpoint::Client(_)) {
+        return None;
+    }
+
+    let result = match &delivery.message {
+        Message::WriteAck(_) => "ok".to_string(),
+        Message::SessionWriteAck((_, timestamp)) => format!("ok v{}", timestamp),
+        Message::SessionReadRefused((_, timestamp)) => format!("refused at v{}", timestamp),
+        // Judged at the time of the delivery, not of the export
+        Message::ClientReadResponse(data) => format!("{} v{}", data.value_at(delivery.wall_clock).unwrap_or("absent"), data.version()),
+        Message::TransactionResult(committed) => if *committed { "committed" } else { "aborted" }.to_string(),
+        Message::SiblingReadResponse(value) | Message::CrdtResponse(value) => format!("{:?}", value.values),
+        Message::ReconfigureAck(configuration) => format!("epoch {}", configuration.epoch),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Mermaid ends a statement at ; and a line, # starts its entity codes and labels may hold HTML
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '#' => escaped += "#35;",
+            ';' => escaped += "#59;",
+            '<' => escaped += "#lt;",
+            '>' => escaped += "#gt;",
+            '\n' => escaped += "<br>",
+            '\r' => {},
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use std::{collections::{BTreeMap, HashMap}, time::{Duration, Instant}};
+
+use crate::{node::{NodeData, NodeId, Timestamp}, store::Key};
+
+#[derive(Clone, Debug)]
+pub struct HintConfig {
+    // How often the coordinator tries to hand hints to replicas that are back
+    pub replay_interval: Duration,
+    // Older hints are dropped, the replica then catches up through reads or anti-entropy
+    pub max_age: Duration,
+    pub max_hints_per_node: usize,
+}
+
+impl Default for HintConfig {
+    fn default() -> HintConfig {
+        HintConfig {
+            replay_interval: Duration::from_millis(100),
+            max_age: Duration::from_secs(10),
+            max_hints_per_node: 1024,
+        }
+    }
+}
+
+struct Hint {
+    data: NodeData,
+    stored: Instant,
+}
+
+// Writes an unreachable replica missed, only the newest value per key is kept
+pub struct HintStore {
+    pub config: HintConfig,
+    hints: HashMap<NodeId, BTreeMap<Key, Hint>>,
+    dropped: usize,
+}
+
+impl HintStore {
+    pub fn new(config: HintConfig) -> HintStore {
+        HintStore {
+            config,
+            hints: HashMap::new(),
+            dropped: 0,
+        }
+    }
+
+    // False when the node already has the maximum number of hints
+    pub fn add(&mut self, node_id: &NodeId, key: &str, data: NodeData, now: Instant) -> bool {
+        let hints = self.hints.entry(node_id.clone()).or_default();
+
+        if let Some(hint) = hints.get(key) {
+            if hint.data.timestamp() >= data.timestamp() {
+                return true;
+            }
+        } else if hints.len() >= self.config.max_hints_per_node {
+            self.dropped += 1;
+            return false;
+        }
+
+        hints.insert(key.to_string(), Hint { data, stored: now });
+        true
+    }
+
+    pub fn expire(&mut self, now: Instant) {
+        let max_age = self.config.max_age;
+        let mut dropped = 0;
+
+        for hints in self.hints.values_mut() {
+            let before = hints.len();
+            hints.retain(|_, hint| now.duration_since(hint.stored) < max_age);
+            dropped += before - hints.len();
+        }
+
+        self.hints.retain(|_, hints| !hints.is_empty());
+        self.dropped += dropped;
+    }
+
+    pub fn nodes(&self) -> Vec<NodeId> {
+        self.hints.keys().cloned().collect()
+    }
+
+    pub fn hints_for(&self, node_id: &NodeId) -> Vec<(Key, NodeData)> {
+        match self.hints.get(node_id) {
+            Some(hints) => hints.iter().map(|(key, hint)| (key.clone(), hint.data.clone())).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Hints replaced by a newer write while the replay was in flight stay
+    pub fn ack(&mut self, node_id: &NodeId, delivered: &[(Key, Timestamp)]) {
+        let hints = match self.hints.get_mut(node_id) {
+            Some(hints) => hints,
+            None => return,
+        };
+
+        for (key, timestamp) in delivered {
+            if hints.get(key).map(|hint| hint.data.timestamp() <= *timestamp).unwrap_or(false) {
+                hints.remove(key);
+            }
+        }
+
+        if hints.is_empty() {
+            self.hints.remove(node_id);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hints.values().map(|hints| hints.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
\ No newline at end of file
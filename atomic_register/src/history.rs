@@ -0,0 +1,236 @@
+use std::{collections::{HashMap, HashSet}, time::{Duration, Instant}};
+
+use crate::{atomic_register_client::ClientId, node::RegisterSemantics};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OperationKind {
+    Write(String),
+    Read(String),
+}
+
+// Times are measured from the creation of the history
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    pub client: ClientId,
+    pub kind: OperationKind,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl Operation {
+    fn precedes(&self, other: &Operation) -> bool {
+        self.end < other.start
+    }
+
+    fn is_concurrent_with(&self, other: &Operation) -> bool {
+        !self.precedes(other) && !other.precedes(self)
+    }
+
+    fn written(&self) -> Option<&str> {
+        match &self.kind {
+            OperationKind::Write(value) => Some(value),
+            OperationKind::Read(_) => None,
+        }
+    }
+
+    fn returned(&self) -> Option<&str> {
+        match &self.kind {
+            OperationKind::Read(value) => Some(value),
+            OperationKind::Write(_) => None,
+        }
+    }
+}
+
+// Completed operations on one register. The checkers expect every write to write a different value,
+// the register starts out with the empty value.
+pub struct History {
+    started: Instant,
+    operations: Vec<Operation>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            started: Instant::now(),
+            operations: Vec::new(),
+        }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn add(&mut self, client: ClientId, kind: OperationKind, start: Duration, end: Duration) {
+        self.operations.push(Operation { client, kind, start, end });
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn check(&self, semantics: RegisterSemantics) -> Result<(), String> {
+        match semantics {
+            RegisterSemantics::Safe => self.check_safe(),
+            RegisterSemantics::Regular => self.check_regular(),
+            RegisterSemantics::Atomic => self.check_linearizable(),
+        }
+    }
+
+    // A read without concurrent writes returns the value of a last preceding write
+    pub fn check_safe(&self) -> Result<(), String> {
+        for read in self.reads() {
+            if self.writes().any(|write| write.is_concurrent_with(read)) {
+                continue;
+            }
+            if !self.last_preceding_values(read).contains(&read.returned().unwrap()) {
+                return Err(format!("Safe read {:?} does not return a last preceding write", read));
+            }
+        }
+        Ok(())
+    }
+
+    // A read returns the value of a last preceding write or of a concurrent write
+    pub fn check_regular(&self) -> Result<(), String> {
+        for read in self.reads() {
+            let mut values = self.last_preceding_values(read);
+            values.extend(self.writes().filter(|write| write.is_concurrent_with(read)).filter_map(|write| write.written()));
+
+            if !values.contains(&read.returned().unwrap()) {
+                return Err(format!("Regular read {:?} returns neither a last preceding nor a concurrent write", read));
+            }
+        }
+        Ok(())
+    }
+
+    // Searches for a total order that respects real time in which every read returns the last write before it.
+    // Longer histories go to check_zones.
+    pub fn check_linearizable(&self) -> Result<(), String> {
+        if self.operations.len() > 128 {
+            return self.check_zones();
+        }
+
+        let operations: Vec<&Operation> = self.operations.iter().collect();
+        let mut visited = HashSet::new();
+
+        if linearize(&operations, 0, "", &mut visited) {
+            Ok(())
+        } else {
+            Err(format!("No linearization of {} operations", operations.len()))
+        }
+    }
+
+    // Linearizability in O(n^2) for histories of any length, after Gibbons and Korach. The write of a value and
+    // the reads returning it form a cluster. A cluster whose first end comes before its last start has a forward
+    // zone between the two, otherwise a backward zone. With unique values the history is linearizable exactly when
+    // every read ends after its write starts, no two forward zones overlap and no backward zone lies inside a forward one.
+    pub fn check_zones(&self) -> Result<(), String> {
+        let initial = Operation {
+            client: ClientId(-1),
+            kind: OperationKind::Write("".to_string()),
+            start: Duration::ZERO,
+            end: Duration::ZERO,
+        };
+
+        let mut clusters: HashMap<&str, (&Operation, Vec<&Operation>)> = HashMap::new();
+        for write in self.writes() {
+            if clusters.insert(write.written().unwrap(), (write, Vec::new())).is_some() {
+                return Err(format!("Value of write {:?} is written twice", write));
+            }
+        }
+        clusters.entry("").or_insert((&initial, Vec::new()));
+
+        for read in self.reads() {
+            let (write, reads) = match clusters.get_mut(read.returned().unwrap()) {
+                Some(cluster) => cluster,
+                None => return Err(format!("Read {:?} returns a value that was never written", read)),
+            };
+            if read.precedes(write) {
+                return Err(format!("Read {:?} ends before its write {:?} starts", read, write));
+            }
+            reads.push(read);
+        }
+
+        let mut forward = Vec::new();
+        let mut backward = Vec::new();
+        for (write, reads) in clusters.values() {
+            let first_end = reads.iter().map(|read| read.end).chain([write.end]).min().unwrap();
+            let last_start = reads.iter().map(|read| read.start).chain([write.start]).max().unwrap();
+            if first_end < last_start {
+                forward.push((first_end, last_start, *write));
+            } else {
+                backward.push((last_start, first_end, *write));
+            }
+        }
+
+        forward.sort_by_key(|(low, _, _)| *low);
+        for pair in forward.windows(2) {
+            if pair[1].0 < pair[0].1 {
+                return Err(format!("Values of {:?} and {:?} are read in overlapping orders", pair[0].2, pair[1].2));
+            }
+        }
+        for (low, high, write) in &backward {
+            if let Some((_, _, outer)) = forward.iter().find(|(outer_low, outer_high, _)| outer_low < low && high < outer_high) {
+                return Err(format!("Value of {:?} is overwritten while the value of {:?} is read", outer, write));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reads(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().filter(|operation| operation.returned().is_some())
+    }
+
+    fn writes(&self) -> impl Iterator<Item = &Operation> {
+        self.operations.iter().filter(|operation| operation.written().is_some())
+    }
+
+    // Writes before the read that are not followed by another write before the read
+    fn last_preceding_values(&self, read: &Operation) -> Vec<&str> {
+        let preceding: Vec<&Operation> = self.writes().filter(|write| write.precedes(read)).collect();
+        if preceding.is_empty() {
+            return vec![""];
+        }
+
+        preceding.iter()
+            .filter(|write| !preceding.iter().any(|later| write.precedes(later)))
+            .filter_map(|write| write.written())
+            .collect()
+    }
+}
+
+impl Default for History {
+    fn default() -> History {
+        History::new()
+    }
+}
+
+// Depth first search over the operations that can go next, visited remembers dead ends
+fn linearize(operations: &[&Operation], done: u128, value: &str, visited: &mut HashSet<(u128, String)>) -> bool {
+    if done.count_ones() as usize == operations.len() {
+        return true;
+    }
+    if !visited.insert((done, value.to_string())) {
+        return false;
+    }
+
+    let pending = || (0..operations.len()).filter(move |i| done & (1 << i) == 0);
+    let first_end = pending().map(|i| operations[i].end).min().unwrap();
+
+    for i in pending() {
+        // Only operations that started before every pending one ended can be next
+        if operations[i].start > first_end {
+            continue;
+        }
+
+        let linearized = match &operations[i].kind {
+            OperationKind::Write(written) => linearize(operations, done | (1 << i), written, visited),
+            OperationKind::Read(read) => read == value && linearize(operations, done | (1 << i), value, visited),
+        };
+        if linearized {
+            return true;
+        }
+    }
+
+    false
+}
\ No newline at end of file
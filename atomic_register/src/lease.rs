@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, time::{Duration, Instant}};
+
+use crate::{node::{NodeId, Timestamp}, store::Key};
+
+#[derive(Clone, Debug)]
+pub struct LeaseConfig {
+    pub duration: Duration,
+    // Maximum relative clock drift between two nodes, 0.01 means 1%
+    pub max_clock_drift: f64,
+}
+
+impl Default for LeaseConfig {
+    fn default() -> LeaseConfig {
+        LeaseConfig {
+            duration: Duration::from_millis(500),
+            max_clock_drift: 0.01,
+        }
+    }
+}
+
+struct LeaseRequest {
+    id: u64,
+    started: Instant,
+    grants: Vec<NodeId>,
+}
+
+// A node holding the lease got a promise from a quorum that they will not ack writes
+// of other coordinators, so the values it committed stay the newest values until the lease runs out.
+pub struct Lease {
+    config: LeaseConfig,
+    next_request_id: u64,
+    request: Option<LeaseRequest>,
+    held_until: Option<Instant>,
+    granted: Option<(NodeId, Instant)>,
+    // A write of another coordinator waits for the granted lease, it is not renewed until the write went through
+    writes_waiting: bool,
+    // Timestamps this node saw on a quorum while holding the lease. The local store may be ahead
+    // with a write no quorum has yet, such a value is not read with the lease.
+    committed: BTreeMap<Key, Timestamp>,
+}
+
+impl Lease {
+    pub fn new(config: LeaseConfig) -> Lease {
+        Lease {
+            config,
+            next_request_id: 0,
+            request: None,
+            held_until: None,
+            granted: None,
+            writes_waiting: false,
+            committed: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_held(&self, now: Instant) -> bool {
+        match self.held_until {
+            Some(held_until) => now < held_until,
+            None => false,
+        }
+    }
+
+    // Starts a new lease request when the lease is missing or about to run out.
+    // The node promises itself not to ack other coordinators, like every grantor does.
+    pub fn renew(&mut self, node_id: &NodeId, now: Instant) -> Option<u64> {
+        let renew_at = match self.held_until {
+            Some(held_until) => held_until.checked_sub(self.config.duration / 2).unwrap_or(now),
+            None => now,
+        };
+        if now < renew_at {
+            return None;
+        }
+
+        if let Some(request) = &self.request {
+            if now.duration_since(request.started) < self.config.duration {
+                return None;
+            }
+        }
+
+        if !self.grant(node_id, now) {
+            return None;
+        }
+
+        self.next_request_id += 1;
+        self.request = Some(LeaseRequest {
+            id: self.next_request_id,
+            started: now,
+            grants: vec![node_id.clone()],
+        });
+
+        Some(self.next_request_id)
+    }
+
+    // True once the lease is acquired. Other coordinators may have committed writes while the lease
+    // was not held, so what was committed before does not count after a gap.
+    pub fn add_grant(&mut self, request_id: u64, node_id: NodeId, quorum: usize, now: Instant) -> bool {
+        let request = match self.request.as_mut() {
+            Some(request) if request.id == request_id => request,
+            _ => return false,
+        };
+
+        if request.grants.contains(&node_id) {
+            return false;
+        }
+        request.grants.push(node_id);
+
+        if request.grants.len() < quorum {
+            return false;
+        }
+
+        let request = self.request.take().unwrap();
+        if !self.is_held(now) {
+            self.committed.clear();
+        }
+        // Grantors count from the moment they got the request, so start from when it was sent
+        self.held_until = Some(request.started + self.config.duration.mul_f64(1.0 - self.config.max_clock_drift));
+        true
+    }
+
+    // Grants of the old members say nothing about the new ones, so the lease is given up on reconfiguration
+    pub fn release(&mut self) {
+        self.request = None;
+        self.held_until = None;
+        self.committed.clear();
+    }
+
+    pub fn commit(&mut self, key: &str, timestamp: Timestamp, now: Instant) {
+        if !self.is_held(now) {
+            return;
+        }
+        let committed = self.committed.entry(key.to_string()).or_default();
+        if timestamp > *committed {
+            *committed = timestamp;
+        }
+    }
+
+    // Whether a local value is known to be on a quorum
+    pub fn is_committed(&self, key: &str, timestamp: &Timestamp) -> bool {
+        self.committed.get(key) == Some(timestamp)
+    }
+
+    pub fn grant(&mut self, holder: &NodeId, now: Instant) -> bool {
+        if self.writes_waiting {
+            return false;
+        }
+        if let Some((granted_to, until)) = &self.granted {
+            if granted_to != holder && now < *until {
+                return false;
+            }
+        }
+
+        self.granted = Some((holder.clone(), now + self.config.duration.mul_f64(1.0 + self.config.max_clock_drift)));
+        true
+    }
+
+    pub fn blocks_writes_from(&mut self, node_id: &NodeId, now: Instant) -> bool {
+        let (blocked, granted) = match &self.granted {
+            Some((granted_to, until)) => (granted_to != node_id && now < *until, now < *until),
+            None => (false, false),
+        };
+
+        if blocked {
+            self.writes_waiting = true;
+        } else if !granted {
+            self.writes_waiting = false;
+        }
+        blocked
+    }
+}
\ No newline at end of file
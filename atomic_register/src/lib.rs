@@ -1,4 +1,25 @@
 pub mod node;
 pub mod atomic_register_client;
 pub mod network;
-pub mod quorum;
\ No newline at end of file
+pub mod quorum;
+pub mod lease;
+pub mod anti_entropy;
+pub mod merkle;
+pub mod store;
+pub mod membership;
+pub mod ring;
+pub mod hint;
+pub mod watch;
+pub mod transaction;
+pub mod crdt;
+pub mod history;
+pub mod admin;
+pub mod trace;
+pub mod metrics;
+pub mod replay;
+pub mod diagram;
+pub mod model;
+pub mod raft;
+pub mod raft_client;
+pub mod sibling_client;
+pub mod session;
\ No newline at end of file
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::node::NodeId;
+
+// Replica set of one epoch, every reconfiguration installs a configuration with a higher epoch
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Configuration {
+    pub epoch: u64,
+    pub members: Vec<NodeId>,
+}
+
+impl Configuration {
+    pub fn new(epoch: u64, mut members: Vec<NodeId>) -> Configuration {
+        members.sort_by_key(|node_id| node_id.0);
+        members.dedup();
+        Configuration { epoch, members }
+    }
+
+    pub fn contains(&self, node_id: &NodeId) -> bool {
+        self.members.contains(node_id)
+    }
+
+    // Majority of the members, any two quorums of the same configuration intersect
+    pub fn quorum(&self) -> usize {
+        self.members.len() / 2 + 1
+    }
+
+    pub fn next(&self, members: Vec<NodeId>) -> Configuration {
+        Configuration::new(self.epoch + 1, members)
+    }
+}
\ No newline at end of file
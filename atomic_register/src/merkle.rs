@@ -0,0 +1,125 @@
+use crate::node::NodeData;
+
+// Keys are spread over 2^MERKLE_DEPTH leaves, two replicas find a divergent leaf in MERKLE_DEPTH round trips
+pub const MERKLE_DEPTH: u32 = 8;
+pub const MERKLE_ROOT: usize = 1;
+
+const LEAF_COUNT: usize = 1 << MERKLE_DEPTH;
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+// Binary tree in array form: node i has children 2i and 2i + 1, leaves are LEAF_COUNT..2 * LEAF_COUNT.
+// A leaf hash is the xor of its entry hashes so a single update does not need the other entries.
+pub struct MerkleTree {
+    hashes: Vec<u64>,
+}
+
+impl MerkleTree {
+    pub fn new() -> MerkleTree {
+        MerkleTree { hashes: vec![0; 2 * LEAF_COUNT] }
+    }
+
+    pub fn leaf_of(key: &str) -> usize {
+        LEAF_COUNT + (Fnv::new().write(key.as_bytes()).finish() % LEAF_COUNT as u64) as usize
+    }
+
+    pub fn is_leaf(index: usize) -> bool {
+        index >= LEAF_COUNT
+    }
+
+    pub fn children(index: usize) -> [usize; 2] {
+        [2 * index, 2 * index + 1]
+    }
+
+    pub fn hash(&self, index: usize) -> u64 {
+        self.hashes[index]
+    }
+
+    pub fn root(&self) -> u64 {
+        self.hashes[MERKLE_ROOT]
+    }
+
+    pub fn update(&mut self, key: &str, old: Option<&NodeData>, new: &NodeData) {
+        let index = MerkleTree::leaf_of(key);
+
+        if let Some(old) = old {
+            self.hashes[index] ^= entry_hash(key, old);
+        }
+        self.hashes[index] ^= entry_hash(key, new);
+
+        self.update_path(index);
+    }
+
+    pub fn remove(&mut self, key: &str, old: &NodeData) {
+        let index = MerkleTree::leaf_of(key);
+        self.hashes[index] ^= entry_hash(key, old);
+
+        self.update_path(index);
+    }
+
+    fn update_path(&mut self, mut index: usize) {
+        while index > MERKLE_ROOT {
+            index /= 2;
+            let [left, right] = MerkleTree::children(index);
+            self.hashes[index] = combine(self.hashes[left], self.hashes[right]);
+        }
+    }
+}
+
+impl Default for MerkleTree {
+    fn default() -> MerkleTree {
+        MerkleTree::new()
+    }
+}
+
+// Replicas holding the same version with different values must not compare equal
+fn entry_hash(key: &str, data: &NodeData) -> u64 {
+    Fnv::new()
+        .write_field(key.as_bytes())
+        .write(&data.version().to_le_bytes())
+        .write(&[data.is_tombstone() as u8])
+        .write(&data.expires_at().unwrap_or(0).to_le_bytes())
+        .write(&data.writer().map(|writer| writer.0 as i64).unwrap_or(-1).to_le_bytes())
+        .write_field(data.data().as_bytes())
+        .finish()
+}
+
+// Empty subtrees keep the hash 0 so they compare equal without hashing
+fn combine(left: u64, right: u64) -> u64 {
+    if left == 0 && right == 0 {
+        return 0;
+    }
+    Fnv::new().write(&left.to_le_bytes()).write(&right.to_le_bytes()).finish()
+}
+
+// FNV-1a, unlike DefaultHasher its output is the same on every build and platform
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Fnv {
+        Fnv::new()
+    }
+}
+
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(FNV_OFFSET_BASIS)
+    }
+
+    pub fn write(mut self, bytes: &[u8]) -> Fnv {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+        self
+    }
+
+    // Length prefixed so adjacent fields cannot shift bytes between each other
+    pub fn write_field(self, bytes: &[u8]) -> Fnv {
+        self.write(&(bytes.len() as u64).to_le_bytes()).write(bytes)
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
\ No newline at end of file
@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, io::{self, Read, Write}, net::{SocketAddr, TcpListener}, path::Path, sync::{Arc, Mutex}, time::Duration};
+
+pub const CLIENT_OPERATION_SECONDS: &str = "atomic_register_client_operation_seconds";
+pub const QUORUM_ROUND_TRIP_SECONDS: &str = "atomic_register_quorum_round_trip_seconds";
+pub const MESSAGES_SENT_TOTAL: &str = "atomic_register_messages_sent_total";
+pub const MESSAGES_RECEIVED_TOTAL: &str = "atomic_register_messages_received_total";
+pub const WRITE_BACKS_TOTAL: &str = "atomic_register_write_backs_total";
+pub const STALE_WRITES_IGNORED_TOTAL: &str = "atomic_register_stale_writes_ignored_total";
+
+// Upper bounds in seconds, the default buckets of the Prometheus clients
+const BUCKETS: [f64; 14] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub type Labels = Vec<(String, String)>;
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    // Not cumulative, export sums them up
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+enum Values {
+    Counter(BTreeMap<Labels, u64>),
+    Histogram(BTreeMap<Labels, Histogram>),
+}
+
+struct Metric {
+    help: String,
+    values: Values,
+}
+
+// Counters and histograms shared by the nodes and clients of a network, see Network::metrics
+pub struct MetricsRegistry {
+    metrics: Mutex<BTreeMap<String, Metric>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> MetricsRegistry {
+        let registry = MetricsRegistry { metrics: Mutex::new(BTreeMap::new()) };
+        registry.describe_histogram(CLIENT_OPERATION_SECONDS, "Latency of client operations, from request to response.");
+        registry.describe_histogram(QUORUM_ROUND_TRIP_SECONDS, "Time a coordinator waits for a quorum to answer.");
+        registry.describe_counter(MESSAGES_SENT_TOTAL, "Messages handed to the network, dropped ones included.");
+        registry.describe_counter(MESSAGES_RECEIVED_TOTAL, "Messages taken out of the network by nodes and clients.");
+        registry.describe_counter(WRITE_BACKS_TOTAL, "Reads that wrote the newest value back to a quorum.");
+        registry.describe_counter(STALE_WRITES_IGNORED_TOTAL, "Replicated writes not stored because the replica had a newer version.");
+        registry
+    }
+
+    pub fn describe_counter(&self, name: &str, help: &str) {
+        self.describe(name, help, Values::Counter(BTreeMap::new()));
+    }
+
+    pub fn describe_histogram(&self, name: &str, help: &str) {
+        self.describe(name, help, Values::Histogram(BTreeMap::new()));
+    }
+
+    fn describe(&self, name: &str, help: &str, values: Values) {
+        self.metrics.lock().unwrap().entry(name.to_string()).or_insert(Metric { help: help.to_string(), values });
+    }
+
+    pub fn inc(&self, name: &str, labels: &[(&str, &str)]) {
+        self.inc_by(name, labels, 1);
+    }
+
+    pub fn inc_by(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        let mut metrics = self.metrics.lock().unwrap();
+        match &mut metrics.get_mut(name).expect("Unknown metric").values {
+            Values::Counter(values) => *values.entry(to_labels(labels)).or_insert(0) += value,
+            Values::Histogram(_) => panic!("{} is not a counter", name),
+        }
+    }
+
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let mut metrics = self.metrics.lock().unwrap();
+        match &mut metrics.get_mut(name).expect("Unknown metric").values {
+            Values::Histogram(values) => {
+                let histogram = values.entry(to_labels(labels)).or_default();
+                if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+                    histogram.buckets[bucket] += 1;
+                }
+                histogram.sum += seconds;
+                histogram.count += 1;
+            },
+            Values::Counter(_) => panic!("{} is not a histogram", name),
+        }
+    }
+
+    // Sum over all label values
+    pub fn counter(&self, name: &str) -> u64 {
+        match &self.metrics.lock().unwrap().get(name).expect("Unknown metric").values {
+            Values::Counter(values) => values.values().sum(),
+            Values::Histogram(values) => values.values().map(|histogram| histogram.count).sum(),
+        }
+    }
+
+    // Prometheus text exposition format 0.0.4
+    pub fn export(&self) -> String {
+        let mut text = String::new();
+
+        for (name, metric) in self.metrics.lock().unwrap().iter() {
+            match &metric.values {
+                Values::Counter(values) => {
+                    text += &format!("# HELP {} {}\n# TYPE {} counter\n", name, metric.help, name);
+                    for (labels, value) in values {
+                        text += &format!("{}{} {}\n", name, format_labels(labels, None), value);
+                    }
+                },
+                Values::Histogram(values) => {
+                    text += &format!("# HELP {} {}\n# TYPE {} histogram\n", name, metric.help, name);
+                    for (labels, histogram) in values {
+                        let mut cumulative = 0;
+                        for (bound, count) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+                            cumulative += count;
+                            text += &format!("{}_bucket{} {}\n", name, format_labels(labels, Some(&bound.to_string())), cumulative);
+                        }
+                        text += &format!("{}_bucket{} {}\n", name, format_labels(labels, Some("+Inf")), histogram.count);
+                        text += &format!("{}_sum{} {}\n", name, format_labels(labels, None), histogram.sum);
+                        text += &format!("{}_count{} {}\n", name, format_labels(labels, None), histogram.count);
+                    }
+                },
+            }
+        }
+
+        text
+    }
+
+    pub fn dump(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        std::fs::write(path, self.export())
+    }
+
+    // Answers every HTTP request on the address with the export, port 0 picks a free port
+    pub fn serve(self: Arc<Self>, address: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let local_address = listener.local_addr()?;
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+
+                // The request itself does not matter, only its head is read
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+
+                let body = self.export();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        Ok(local_address)
+    }
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> MetricsRegistry {
+        MetricsRegistry::new()
+    }
+}
+
+fn to_labels(labels: &[(&str, &str)]) -> Labels {
+    labels.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels.iter().map(|(name, value)| format!("{}=\"{}\"", name, escape(value))).collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{}\"", le));
+    }
+
+    if pairs.is_empty() {
+        "".to_string()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
\ No newline at end of file
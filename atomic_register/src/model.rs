@@ -0,0 +1,481 @@
+use std::{collections::{hash_map::DefaultHasher, HashSet, VecDeque}, hash::{Hash, Hasher}, time::Duration};
+
+use crate::{atomic_register_client::ClientId, history::{History, OperationKind}, node::{NodeId, RegisterSemantics, Timestamp}};
+
+// Exhaustive model checking of the quorum register on one key. The model follows what Node does:
+// a coordinator holds the quorum for one phase at a time, writes hold the write lock over both phases,
+// the coordinator counts itself in every quorum and stores a new version locally before phase 2,
+// values are ordered by the (version, writer) timestamp, so writes of two coordinators never tie,
+// and requests carry the round of their phase, so late responses of an earlier phase are dropped.
+// Every order of message deliveries and every crash point is explored breadth first,
+// so the first history that is not linearizable comes with a shortest trace.
+// Visited states are kept as 64 bit hashes, a collision could skip a state but is very unlikely
+// at the sizes the model runs at.
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum ModelOperation {
+    // Values are small numbers, 0 is the empty initial value
+    Write(u8),
+    Read,
+}
+
+pub struct ModelConfig {
+    pub nodes: usize,
+    pub quorum: usize,
+    // Coordinator node of each client, the network sends every client to node 0
+    pub coordinators: Vec<usize>,
+    // Operations of each client, run one after another
+    pub workload: Vec<Vec<ModelOperation>>,
+    // Nodes crash and stay down
+    pub max_crashes: usize,
+    pub semantics: RegisterSemantics,
+    // The search stops after this many states and reports itself incomplete
+    pub max_states: usize,
+}
+
+// Two clients with three operations between them, checks in a few seconds. Every operation more multiplies
+// the states, a read for the second client too, so two operations each, takes about 13 million states
+// and minutes in a release build.
+impl Default for ModelConfig {
+    fn default() -> ModelConfig {
+        ModelConfig {
+            nodes: 3,
+            quorum: 2,
+            coordinators: vec![0, 0],
+            workload: vec![
+                vec![ModelOperation::Write(1), ModelOperation::Read],
+                vec![ModelOperation::Write(2)],
+            ],
+            max_crashes: 1,
+            semantics: RegisterSemantics::Atomic,
+            max_states: 1_000_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ModelReport {
+    pub states: usize,
+    // False when the search stopped early, at a violation or at max_states
+    pub complete: bool,
+    pub violation: Option<Violation>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Violation {
+    pub trace: Vec<String>,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+enum ModelMessage {
+    ReadRequest { from: usize, to: usize, round: u32 },
+    ReadResponse { from: usize, to: usize, round: u32, timestamp: Timestamp, value: u8 },
+    WriteRequest { from: usize, to: usize, round: u32, timestamp: Timestamp, value: u8 },
+    WriteAck { from: usize, to: usize, round: u32 },
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    // Phase 1, nodes that answered and timestamps and values seen so far with the own one included
+    Query { round: u32, responders: Vec<usize>, lowest: Timestamp, newest: (Timestamp, u8) },
+    // Stored locally, waits for the quorum to send phase 2
+    Stored { timestamp: Timestamp, value: u8 },
+    Update { round: u32, timestamp: Timestamp, value: u8, acked: Vec<usize> },
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum Event {
+    Invoke(usize),
+    Return(usize, Option<u8>),
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct State {
+    registers: Vec<(Timestamp, u8)>,
+    crashed: Vec<bool>,
+    // Last quorum round of each node
+    rounds: Vec<u32>,
+    next_operation: Vec<usize>,
+    phases: Vec<Phase>,
+    // Sorted, the order messages were sent in does not matter
+    messages: Vec<ModelMessage>,
+    history: Vec<Event>,
+}
+
+pub fn check(config: &ModelConfig) -> ModelReport {
+    let clients = config.workload.len();
+    assert_eq!(config.coordinators.len(), clients, "One coordinator per client");
+
+    let initial = State {
+        registers: vec![(Timestamp::new(0, None), 0); config.nodes],
+        crashed: vec![false; config.nodes],
+        rounds: vec![0; config.nodes],
+        next_operation: vec![0; clients],
+        phases: vec![Phase::Idle; clients],
+        messages: Vec::new(),
+        history: Vec::new(),
+    };
+
+    // Parent and successor number of every state, the trace is rebuilt by taking the same steps again
+    let mut steps: Vec<(u32, u16)> = vec![(0, 0)];
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(fingerprint(&initial));
+    queue.push_back((initial.clone(), 0));
+
+    while let Some((state, index)) = queue.pop_front() {
+        for (choice, (_, next)) in successors(config, &state).into_iter().enumerate() {
+            if !visited.insert(fingerprint(&next)) {
+                continue;
+            }
+            if visited.len() > config.max_states {
+                return ModelReport { states: visited.len() - 1, complete: false, violation: None };
+            }
+
+            let next_index = steps.len();
+            steps.push((index as u32, choice as u16));
+
+            if let Some(Event::Return(_, _)) = next.history.last() {
+                if let Err(error) = history_of(config, &next).check_linearizable() {
+                    return ModelReport {
+                        states: visited.len(),
+                        complete: false,
+                        violation: Some(Violation { trace: trace(config, &initial, &steps, next_index), error }),
+                    };
+                }
+            }
+
+            queue.push_back((next, next_index));
+        }
+    }
+
+    ModelReport { states: visited.len(), complete: true, violation: None }
+}
+
+// Only whether a message belongs to the current round of its coordinator matters, rounds are hashed as that
+fn fingerprint(state: &State) -> u64 {
+    let mut state = state.clone();
+    let current = |node: usize, round: u32| (round == state.rounds[node]) as u32;
+    let mut messages: Vec<ModelMessage> = state.messages.iter().map(|message| match message.clone() {
+        ModelMessage::ReadRequest { from, to, round } => ModelMessage::ReadRequest { from, to, round: current(from, round) },
+        ModelMessage::ReadResponse { from, to, round, timestamp, value } => ModelMessage::ReadResponse { from, to, round: current(to, round), timestamp, value },
+        ModelMessage::WriteRequest { from, to, round, timestamp, value } => ModelMessage::WriteRequest { from, to, round: current(from, round), timestamp, value },
+        ModelMessage::WriteAck { from, to, round } => ModelMessage::WriteAck { from, to, round: current(to, round) },
+    }).collect();
+    messages.sort();
+    state.messages = messages;
+    state.rounds.iter_mut().for_each(|round| *round = 0);
+    for phase in state.phases.iter_mut() {
+        match phase {
+            Phase::Query { round, .. } | Phase::Update { round, .. } => *round = 1,
+            _ => {},
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn trace(config: &ModelConfig, initial: &State, steps: &[(u32, u16)], mut index: usize) -> Vec<String> {
+    let mut choices = Vec::new();
+    while index != 0 {
+        choices.push(steps[index].1 as usize);
+        index = steps[index].0 as usize;
+    }
+    choices.reverse();
+
+    let mut state = initial.clone();
+    let mut trace = Vec::new();
+    for choice in choices {
+        let (step, next) = successors(config, &state).swap_remove(choice);
+        trace.push(step);
+        state = next;
+    }
+    trace
+}
+
+// Logical time is the position in the history. Pending writes may still take effect so they end
+// after everything else, pending reads returned nothing and are left out.
+fn history_of(config: &ModelConfig, state: &State) -> History {
+    let mut history = History::new();
+    let mut started = vec![Vec::new(); config.workload.len()];
+    let mut finished = vec![0; config.workload.len()];
+
+    for (time, event) in state.history.iter().enumerate() {
+        match event {
+            Event::Invoke(client) => started[*client].push(time),
+            Event::Return(client, result) => {
+                let operation = config.workload[*client][finished[*client]];
+                let start = started[*client][finished[*client]];
+                finished[*client] += 1;
+                history.add(ClientId(*client as i32), kind(operation, *result), Duration::from_secs(start as u64), Duration::from_secs(time as u64));
+            },
+        }
+    }
+
+    for (client, starts) in started.iter().enumerate() {
+        if let Some(start) = starts.get(finished[client]) {
+            if let ModelOperation::Write(value) = config.workload[client][finished[client]] {
+                history.add(ClientId(client as i32), kind(ModelOperation::Write(value), None), Duration::from_secs(*start as u64), Duration::MAX);
+            }
+        }
+    }
+
+    history
+}
+
+fn kind(operation: ModelOperation, result: Option<u8>) -> OperationKind {
+    match operation {
+        ModelOperation::Write(value) => OperationKind::Write(value_name(value)),
+        ModelOperation::Read => OperationKind::Read(value_name(result.unwrap())),
+    }
+}
+
+fn value_name(value: u8) -> String {
+    if value == 0 {
+        "".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn successors(config: &ModelConfig, state: &State) -> Vec<(String, State)> {
+    let mut successors = Vec::new();
+
+    for client in 0..config.workload.len() {
+        let coordinator = config.coordinators[client];
+        let operation = config.workload[client].get(state.next_operation[client]).copied();
+
+        match &state.phases[client] {
+            // An operation is invoked when its coordinator can start it. Invoking it earlier only makes it
+            // concurrent with more operations, which never turns a linearizable history into one that is not.
+            Phase::Idle => {
+                let operation = match operation {
+                    Some(operation) if !state.crashed[coordinator] => operation,
+                    _ => continue,
+                };
+
+                if operation == ModelOperation::Read && config.semantics == RegisterSemantics::Safe {
+                    let mut next = state.clone();
+                    let value = state.registers[coordinator].1;
+                    next.history.push(Event::Invoke(client));
+                    finish(&mut next, client, Some(value));
+                    successors.push((format!("client {} reads, node {} answers locally with {}", client, coordinator, value), next));
+                    continue;
+                }
+
+                let writing = matches!(operation, ModelOperation::Write(_));
+                if quorum_busy(config, state, coordinator) || (writing && write_locked(config, state, coordinator)) {
+                    continue;
+                }
+
+                let mut next = state.clone();
+                let own = state.registers[coordinator].clone();
+                next.history.push(Event::Invoke(client));
+                next.rounds[coordinator] += 1;
+                let round = next.rounds[coordinator];
+                next.phases[client] = Phase::Query { round, responders: Vec::new(), lowest: own.0.clone(), newest: own };
+                send_to_others(config, &mut next, coordinator, |to| ModelMessage::ReadRequest { from: coordinator, to, round });
+                successors.push((format!("client {} invokes {:?}, node {} starts phase 1", client, operation, coordinator), next));
+            },
+            Phase::Stored { timestamp, value } => {
+                if state.crashed[coordinator] || quorum_busy(config, state, coordinator) {
+                    continue;
+                }
+
+                let mut next = state.clone();
+                let (timestamp, value) = (timestamp.clone(), *value);
+                next.rounds[coordinator] += 1;
+                let round = next.rounds[coordinator];
+                next.phases[client] = Phase::Update { round, timestamp: timestamp.clone(), value, acked: Vec::new() };
+                send_to_others(config, &mut next, coordinator, |to| ModelMessage::WriteRequest { from: coordinator, to, round, timestamp: timestamp.clone(), value });
+                successors.push((format!("node {} starts phase 2 of client {} with version {}", coordinator, client, timestamp), next));
+            },
+            _ => {},
+        }
+    }
+
+    let mut delivered = Vec::new();
+    for message in &state.messages {
+        if delivered.contains(message) {
+            continue;
+        }
+        delivered.push(message.clone());
+
+        let mut next = state.clone();
+        let position = next.messages.iter().position(|other| other == message).unwrap();
+        next.messages.remove(position);
+        let step = deliver(config, &mut next, message);
+        successors.push((step, next));
+    }
+
+    let crashes = state.crashed.iter().filter(|crashed| **crashed).count();
+    if crashes < config.max_crashes {
+        for node in 0..config.nodes {
+            if !state.crashed[node] {
+                let mut next = state.clone();
+                next.crashed[node] = true;
+                // Nothing is delivered to a node that stays down
+                next.messages.retain(|message| destination(message) != node);
+                successors.push((format!("node {} crashes", node), next));
+            }
+        }
+    }
+
+    successors
+}
+
+fn deliver(config: &ModelConfig, state: &mut State, message: &ModelMessage) -> String {
+    match message.clone() {
+        ModelMessage::ReadRequest { from, to, round } => {
+            let (timestamp, value) = state.registers[to].clone();
+            let step = format!("node {} answers ReadRequest of node {} with version {}", to, from, timestamp);
+            send(state, ModelMessage::ReadResponse { from: to, to: from, round, timestamp, value });
+            step
+        },
+        ModelMessage::WriteRequest { from, to, round, timestamp, value } => {
+            let step = format!("node {} stores version {} of node {}", to, timestamp, from);
+            if timestamp > state.registers[to].0 {
+                state.registers[to] = (timestamp, value);
+            }
+            send(state, ModelMessage::WriteAck { from: to, to: from, round });
+            step
+        },
+        ModelMessage::ReadResponse { from, to, round, timestamp, value } => {
+            let client = match active_client(config, state, to) {
+                Some(client) => client,
+                _ => return format!("node {} drops ReadResponse of node {} with version {}", to, from, timestamp),
+            };
+            let (mut responders, lowest, newest) = match &state.phases[client] {
+                Phase::Query { round: phase_round, responders, lowest, newest } if *phase_round == round => {
+                    let newest = if timestamp > newest.0 { (timestamp.clone(), value) } else { newest.clone() };
+                    (responders.clone(), lowest.clone().min(timestamp.clone()), newest)
+                },
+                _ => return format!("node {} drops ReadResponse of node {} with version {}", to, from, timestamp),
+            };
+            if responders.contains(&from) {
+                return format!("node {} ignores a second ReadResponse of node {}", to, from);
+            }
+            responders.push(from);
+            let count = responders.len() + 1;
+            state.phases[client] = Phase::Query { round, responders, lowest: lowest.clone(), newest: newest.clone() };
+            let mut step = format!("node {} counts ReadResponse of node {} with version {} for client {}", to, from, timestamp, client);
+
+            if count >= config.quorum {
+                step += &complete_query(config, state, client, lowest, newest);
+            }
+            step
+        },
+        ModelMessage::WriteAck { from, to, round } => {
+            let client = match active_client(config, state, to) {
+                Some(client) => client,
+                _ => return format!("node {} drops WriteAck of node {}", to, from),
+            };
+            let (timestamp, value, mut acked) = match &state.phases[client] {
+                Phase::Update { round: phase_round, timestamp, value, acked } if *phase_round == round => (timestamp.clone(), *value, acked.clone()),
+                _ => return format!("node {} drops WriteAck of node {}", to, from),
+            };
+            if acked.contains(&from) {
+                return format!("node {} ignores a second WriteAck of node {}", to, from);
+            }
+            acked.push(from);
+
+            let mut step = format!("node {} counts WriteAck of node {} for client {}", to, from, client);
+            if acked.len() + 1 >= config.quorum {
+                let result = match config.workload[client][state.next_operation[client]] {
+                    ModelOperation::Write(_) => None,
+                    ModelOperation::Read => Some(value),
+                };
+                finish(state, client, result);
+                step += &format!(", client {} returns", client);
+            } else {
+                state.phases[client] = Phase::Update { round, timestamp, value, acked };
+            }
+            step
+        },
+    }
+}
+
+// Writes pick the next version with the coordinator as writer and store it, reads return or write the newest value back
+fn complete_query(config: &ModelConfig, state: &mut State, client: usize, lowest: Timestamp, newest: (Timestamp, u8)) -> String {
+    let coordinator = config.coordinators[client];
+    let own = state.registers[coordinator].clone();
+
+    match config.workload[client][state.next_operation[client]] {
+        ModelOperation::Write(value) => {
+            let timestamp = Timestamp::new(newest.0.version + 1, Some(NodeId(coordinator as i32)));
+            let step = format!(", picks version {}", timestamp);
+            // A newer version stored while phase 1 ran stays, like store_if_newer in Node
+            if timestamp > own.0 {
+                state.registers[coordinator] = (timestamp.clone(), value);
+            }
+            state.phases[client] = Phase::Stored { timestamp, value };
+            step
+        },
+        ModelOperation::Read => {
+            let agrees = lowest == own.0 && newest.0 == own.0;
+            if agrees || config.semantics == RegisterSemantics::Regular {
+                finish(state, client, Some(newest.1));
+                format!(", client {} returns {}", client, newest.1)
+            } else {
+                let step = format!(", writes back version {}", newest.0);
+                if newest.0 > own.0 {
+                    state.registers[coordinator] = newest.clone();
+                }
+                state.phases[client] = Phase::Stored { timestamp: newest.0, value: newest.1 };
+                step
+            }
+        },
+    }
+}
+
+fn finish(state: &mut State, client: usize, result: Option<u8>) {
+    state.phases[client] = Phase::Idle;
+    state.next_operation[client] += 1;
+    state.history.push(Event::Return(client, result));
+}
+
+// The client whose phase holds the quorum of the coordinator
+fn active_client(config: &ModelConfig, state: &State, coordinator: usize) -> Option<usize> {
+    (0..config.workload.len()).find(|client| {
+        config.coordinators[*client] == coordinator && matches!(state.phases[*client], Phase::Query { .. } | Phase::Update { .. })
+    })
+}
+
+fn quorum_busy(config: &ModelConfig, state: &State, coordinator: usize) -> bool {
+    active_client(config, state, coordinator).is_some()
+}
+
+fn write_locked(config: &ModelConfig, state: &State, coordinator: usize) -> bool {
+    (0..config.workload.len()).any(|client| {
+        config.coordinators[client] == coordinator
+            && matches!(config.workload[client].get(state.next_operation[client]), Some(ModelOperation::Write(_)))
+            && state.phases[client] != Phase::Idle
+    })
+}
+
+fn destination(message: &ModelMessage) -> usize {
+    match message {
+        ModelMessage::ReadRequest { to, .. } | ModelMessage::ReadResponse { to, .. } => *to,
+        ModelMessage::WriteRequest { to, .. } | ModelMessage::WriteAck { to, .. } => *to,
+    }
+}
+
+fn send_to_others(config: &ModelConfig, state: &mut State, from: usize, message: impl Fn(usize) -> ModelMessage) {
+    for to in 0..config.nodes {
+        if to != from {
+            send(state, message(to));
+        }
+    }
+}
+
+fn send(state: &mut State, message: ModelMessage) {
+    if state.crashed[destination(&message)] {
+        return;
+    }
+    let position = state.messages.binary_search(&message).unwrap_or_else(|position| position);
+    state.messages.insert(position, message);
+}
\ No newline at end of file
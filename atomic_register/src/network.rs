@@ -1,17 +1,46 @@
-use std::{collections::HashMap, sync::{mpsc, Arc, Mutex}};
+use std::{collections::{HashMap, HashSet}, io, path::Path, sync::{atomic::{AtomicBool, Ordering}, mpsc, Arc, Mutex, RwLock}, time::Duration};
 
-use crate::{atomic_register_client::ClientId, node::{Message, NodeId}};
+use crate::{atomic_register_client::ClientId, metrics::{MetricsRegistry, MESSAGES_RECEIVED_TOTAL, MESSAGES_SENT_TOTAL}, node::{Message, NodeId}, replay::{Endpoint, Recording}, trace::{StdoutSink, TraceEvent, TraceSink}};
 
 // Network simulation
 
 pub struct Network {
-    node_senders: HashMap<NodeId, mpsc::Sender<Message>>,
-    node_receivers: HashMap<NodeId, Arc<Mutex<mpsc::Receiver<Message>>>>,
+    // Nodes can join at runtime, see add_node
+    node_senders: RwLock<HashMap<NodeId, mpsc::Sender<Message>>>,
+    node_receivers: RwLock<HashMap<NodeId, Arc<Mutex<mpsc::Receiver<Message>>>>>,
 
     client_senders: HashMap<ClientId, mpsc::Sender<Message>>,
     client_receivers: HashMap<ClientId, Arc<Mutex<mpsc::Receiver<Message>>>>,
 
+    // Messages to these nodes are lost, like for a crashed or partitioned node
+    unreachable: RwLock<HashSet<NodeId>>,
+    // Directed links that lose every message, only messages with a known sender are affected
+    blocked: RwLock<HashSet<(Endpoint, Endpoint)>>,
+
     coordinator_id: NodeId,
+
+    // Nodes and clients emit their trace events here
+    trace_sink: RwLock<Arc<dyn TraceSink>>,
+
+    metrics: Arc<MetricsRegistry>,
+
+    // Deliveries are written here while recording, the lock also keeps the file in delivery order
+    recording: Mutex<Option<Recording>>,
+    // Checked first so deliveries only take the recording lock while a recording runs
+    is_recording: AtomicBool,
+    // While a Replay runs only its deliveries reach the nodes
+    replaying: AtomicBool,
+    activity: Mutex<HashMap<NodeId, Activity>>,
+}
+
+// What a node does with the messages handed to it. The node is idle once it took every message
+// and each of its threads is done or waits for a message that is not in its inbox.
+#[derive(Default)]
+struct Activity {
+    // Handed to the node, not yet taken off its inbox or dropped
+    pending: usize,
+    threads: usize,
+    waiting: usize,
 }   
 
 impl Network {
@@ -23,16 +52,192 @@ impl Network {
         client_receivers: HashMap<ClientId, Arc<Mutex<mpsc::Receiver<Message>>>>,
     ) -> Network {
         Network {
-            node_senders,
-            node_receivers,
+            node_senders: RwLock::new(node_senders),
+            node_receivers: RwLock::new(node_receivers),
             client_senders,
             client_receivers,
+            unreachable: RwLock::new(HashSet::new()),
+            blocked: RwLock::new(HashSet::new()),
             coordinator_id: NodeId(0),
+            trace_sink: RwLock::new(Arc::new(StdoutSink)),
+            metrics: Arc::new(MetricsRegistry::new()),
+            recording: Mutex::new(None),
+            is_recording: AtomicBool::new(false),
+            replaying: AtomicBool::new(false),
+            activity: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Builds a network with nodes 0..node_count and clients 0..client_count
+    pub fn local(node_count: usize, client_count: usize) -> Network {
+        let mut node_senders = HashMap::new();
+        let mut node_receivers = HashMap::new();
+
+        for i in 0..node_count {
+            let (tx, rx) = mpsc::channel();
+            node_senders.insert(NodeId(i as i32), tx);
+            node_receivers.insert(NodeId(i as i32), Arc::new(Mutex::new(rx)));
+        }
+
+        let mut client_senders = HashMap::new();
+        let mut client_receivers = HashMap::new();
+
+        for i in 0..client_count {
+            let (tx, rx) = mpsc::channel();
+            client_senders.insert(ClientId(i as i32), tx);
+            client_receivers.insert(ClientId(i as i32), Arc::new(Mutex::new(rx)));
         }
+
+        Network::new(node_senders, node_receivers, client_senders, client_receivers)
+    }
+
+    pub fn add_node(&self, node_id: NodeId) {
+        let (tx, rx) = mpsc::channel();
+        self.node_senders.write().unwrap().insert(node_id.clone(), tx);
+        self.node_receivers.write().unwrap().insert(node_id, Arc::new(Mutex::new(rx)));
+    }
+
+    pub fn set_trace_sink(&self, sink: Arc<dyn TraceSink>) {
+        *self.trace_sink.write().unwrap() = sink;
+    }
+
+    pub fn trace(&self, event: TraceEvent) {
+        self.trace_sink.read().unwrap().emit(&event);
+    }
+
+    pub fn metrics(&self) -> &Arc<MetricsRegistry> {
+        &self.metrics
+    }
+
+    pub fn record_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        *self.recording.lock().unwrap() = Some(Recording::create(path)?);
+        self.is_recording.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) {
+        self.is_recording.store(false, Ordering::SeqCst);
+        *self.recording.lock().unwrap() = None;
+    }
+
+    pub fn set_replaying(&self, replaying: bool) {
+        self.replaying.store(replaying, Ordering::SeqCst);
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replaying.load(Ordering::SeqCst)
+    }
+
+    // Hands a replayed message to the node, even while the network drops the messages of the nodes
+    pub fn inject(&self, node_id: &NodeId, message: Message) {
+        self.activity(node_id, |activity| activity.pending += 1);
+        self.node_senders.read().unwrap().get(node_id).unwrap().send(message).unwrap();
+    }
+
+    // Nodes report what they do with their messages, see is_idle
+    pub fn taken(&self, node_id: &NodeId) {
+        self.activity(node_id, |activity| activity.pending = activity.pending.saturating_sub(1));
+    }
+
+    pub fn thread_started(&self, node_id: &NodeId) {
+        self.activity(node_id, |activity| activity.threads += 1);
+    }
+
+    pub fn thread_finished(&self, node_id: &NodeId, waiting: bool) {
+        self.activity(node_id, |activity| {
+            activity.threads -= 1;
+            if waiting {
+                activity.waiting -= 1;
+            }
+        });
+    }
+
+    pub fn set_waiting(&self, node_id: &NodeId, waiting: bool) {
+        self.activity(node_id, |activity| {
+            if waiting {
+                activity.waiting += 1;
+            } else {
+                activity.waiting -= 1;
+            }
+        });
+    }
+
+    // Nothing left to handle until the node gets the next message
+    pub fn is_idle(&self, node_id: &NodeId) -> bool {
+        self.activity(node_id, |activity| activity.pending == 0 && activity.threads == activity.waiting)
+    }
+
+    fn activity<T>(&self, node_id: &NodeId, f: impl FnOnce(&mut Activity) -> T) -> T {
+        f(self.activity.lock().unwrap().entry(node_id.clone()).or_default())
+    }
+
+    pub fn set_reachable(&self, node_id: &NodeId, reachable: bool) {
+        if reachable {
+            self.unreachable.write().unwrap().remove(node_id);
+        } else {
+            self.unreachable.write().unwrap().insert(node_id.clone());
+        }
+    }
+
+    pub fn is_reachable(&self, node_id: &NodeId) -> bool {
+        !self.unreachable.read().unwrap().contains(node_id)
+    }
+
+    pub fn set_link(&self, from: &Endpoint, to: &Endpoint, up: bool) {
+        if up {
+            self.blocked.write().unwrap().remove(&(from.clone(), to.clone()));
+        } else {
+            self.blocked.write().unwrap().insert((from.clone(), to.clone()));
+        }
+    }
+
+    // Nodes only reach the nodes of their own group, clients still reach every node
+    pub fn partition(&self, groups: &[Vec<NodeId>]) {
+        for (i, group) in groups.iter().enumerate() {
+            for (j, other) in groups.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                for from in group {
+                    for to in other {
+                        self.set_link(&Endpoint::Node(from.clone()), &Endpoint::Node(to.clone()), false);
+                    }
+                }
+            }
+        }
+    }
+
+    // Restores every link, unreachable nodes stay unreachable
+    pub fn heal(&self) {
+        self.blocked.write().unwrap().clear();
+    }
+
+    pub fn is_link_up(&self, from: &Endpoint, to: &Endpoint) -> bool {
+        !self.blocked.read().unwrap().contains(&(from.clone(), to.clone()))
+    }
+
+    pub fn client_ids(&self) -> Vec<ClientId> {
+        let mut client_ids: Vec<ClientId> = self.client_senders.keys().cloned().collect();
+        client_ids.sort_by_key(|client_id| client_id.0);
+        client_ids
+    }
+
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        let mut node_ids: Vec<NodeId> = self.node_senders.read().unwrap().keys().cloned().collect();
+        node_ids.sort_by_key(|node_id| node_id.0);
+        node_ids
     }
 
     pub fn get(&self, client_id: &ClientId) -> Option<Message> {
-        self.client_receivers.get(client_id).unwrap().lock().unwrap().recv().ok()
+        self.received(self.client_receivers.get(client_id).unwrap().lock().unwrap().recv().ok())
+    }
+
+    pub fn get_timeout(&self, client_id: &ClientId, timeout: Duration) -> Option<Message> {
+        self.received(self.client_receivers.get(client_id).unwrap().lock().unwrap().recv_timeout(timeout).ok())
+    }
+
+    pub fn coordinator_id(&self) -> &NodeId {
+        &self.coordinator_id
     }
 
     pub fn send(&self, message: Message) {
@@ -40,22 +245,87 @@ impl Network {
     }
 
     pub fn send_to_node(&self, node_id: &NodeId, message: Message) {
-        self.node_senders.get(node_id).unwrap().send(message.clone()).unwrap();
+        self.deliver(None, Endpoint::Node(node_id.clone()), message);
+    }
+
+    // Like send_to_node, the sender ends up in recordings
+    pub fn send_to_node_from(&self, from: &Endpoint, node_id: &NodeId, message: Message) {
+        self.deliver(Some(from.clone()), Endpoint::Node(node_id.clone()), message);
     }
 
     pub fn send_to_nodes(&self, message: Message, node_id: &NodeId) {
-        for (current_node_id, sender) in &self.node_senders {
-            if *current_node_id != *node_id {
-                sender.send(message.clone()).unwrap();
+        for current_node_id in self.node_ids() {
+            if current_node_id != *node_id {
+                self.deliver(Some(Endpoint::Node(node_id.clone())), Endpoint::Node(current_node_id), message.clone());
             }
         }
     }
 
     pub fn get_node_msg(&self, node_id: &NodeId) -> Option<Message> {
-        self.node_receivers.get(node_id).unwrap().lock().unwrap().recv().ok()
+        self.received(self.node_receiver(node_id).lock().unwrap().recv().ok())
+    }
+
+    pub fn get_node_msg_timeout(&self, node_id: &NodeId, timeout: Duration) -> Option<Message> {
+        self.received(self.node_receiver(node_id).lock().unwrap().recv_timeout(timeout).ok())
+    }
+
+    // Cloned out of the map so a blocked receiver does not hold the lock add_node needs
+    fn node_receiver(&self, node_id: &NodeId) -> Arc<Mutex<mpsc::Receiver<Message>>> {
+        Arc::clone(self.node_receivers.read().unwrap().get(node_id).unwrap())
     }
 
     pub fn send_to_client(&self, client_id: &ClientId, message: Message) {
-        self.client_senders.get(client_id).unwrap().send(message.clone()).unwrap();
+        self.deliver(None, Endpoint::Client(client_id.clone()), message);
+    }
+
+    pub fn send_to_client_from(&self, from: &NodeId, client_id: &ClientId, message: Message) {
+        self.deliver(Some(Endpoint::Node(from.clone())), Endpoint::Client(client_id.clone()), message);
+    }
+
+    fn deliver(&self, from: Option<Endpoint>, to: Endpoint, message: Message) {
+        self.sent(&message);
+        // The recorded run already has the answers to the replayed messages, a live send would deliver them
+        // a second time. Clients get no answers either, so a replay is driven by Replay only.
+        if self.replaying.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Some(from) = &from {
+            if !self.is_link_up(from, &to) {
+                return;
+            }
+        }
+
+        let mut recording = if self.is_recording.load(Ordering::SeqCst) {
+            Some(self.recording.lock().unwrap())
+        } else {
+            None
+        };
+        match &to {
+            Endpoint::Node(node_id) => {
+                if !self.is_reachable(node_id) {
+                    return;
+                }
+                self.activity(node_id, |activity| activity.pending += 1);
+                self.node_senders.read().unwrap().get(node_id).unwrap().send(message.clone()).unwrap();
+            },
+            Endpoint::Client(client_id) => {
+                self.client_senders.get(client_id).unwrap().send(message.clone()).unwrap();
+            },
+        }
+
+        if let Some(recording) = recording.as_mut().and_then(|recording| recording.as_mut()) {
+            recording.record(from, to, &message);
+        }
+    }
+
+    fn sent(&self, message: &Message) {
+        self.metrics.inc(MESSAGES_SENT_TOTAL, &[("type", message.type_name())]);
+    }
+
+    fn received(&self, message: Option<Message>) -> Option<Message> {
+        if let Some(message) = &message {
+            self.metrics.inc(MESSAGES_RECEIVED_TOTAL, &[("type", message.type_name())]);
+        }
+        message
     }
 }
\ No newline at end of file
@@ -1,33 +1,333 @@
-use std::sync::{Arc, Mutex};
+use std::{cell::Cell, collections::{BTreeMap, BTreeSet, HashMap}, sync::{Arc, Mutex}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 use rand::Rng;
-use crate::{atomic_register_client::ClientId, network::Network, quorum::{self, Quorum, QuorumState}};
+use serde::{Deserialize, Serialize};
 
+use crate::{admin::{InFlight, NodeStatus, OperationGuard, STATUS_REGISTERS}, anti_entropy::{AntiEntropy, AntiEntropyConfig}, atomic_register_client::ClientId, crdt::{CrdtRegister, CrdtUpdate, CrdtValue, MvRegister, VersionVector}, hint::{HintConfig, HintStore}, lease::{Lease, LeaseConfig}, membership::Configuration, merkle::{MerkleTree, MERKLE_ROOT}, metrics::{QUORUM_ROUND_TRIP_SECONDS, STALE_WRITES_IGNORED_TOTAL, WRITE_BACKS_TOTAL}, network::Network, quorum::{Quorum, QuorumState, Round}, raft::RaftMessage, replay::Endpoint, store::{Key, RegisterStore, ReplicaState, Tombstone}, trace::TraceEvent, transaction::{ReadSet, WriteSet}};
 
-#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub struct NodeId(pub i32);
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+// Replicas drop the data of expired values but keep the version, so older writes can not come back
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_millis(100);
+// Members that did not answer a quorum request by then get it again
+const QUORUM_RETRANSMIT_INTERVAL: Duration = Duration::from_millis(200);
+
+thread_local! {
+    // Whether the current thread of a node last found the inbox empty
+    static WAITING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Values are ordered by version, two writes that picked the same version by the coordinator that wrote them
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub version: u32,
+    pub writer: Option<NodeId>,
+}
+
+impl Timestamp {
+    pub fn new(version: u32, writer: Option<NodeId>) -> Timestamp {
+        Timestamp { version, writer }
+    }
+}
+
+// The version, followed by the writer when there is one
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.writer {
+            Some(writer) => write!(f, "{}.{}", self.version, writer.0),
+            None => write!(f, "{}", self.version),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NodeData {
     data: String,
     version: u32,
+    // Milliseconds since the Unix epoch, assigned by the coordinator so every replica expires the value at once
+    expires_at: Option<u64>,
+    // Written by delete, the version keeps older writes out, also after the tombstone is collected
+    tombstone: bool,
+    // Coordinator that picked the version, orders two writes that picked the same one
+    writer: Option<NodeId>,
+}
+
+impl NodeData {
+    pub fn new(data: String, version: u32) -> NodeData {
+        NodeData { data, version, expires_at: None, tombstone: false, writer: None }
+    }
+
+    pub fn tombstone(version: u32) -> NodeData {
+        NodeData { data: "".to_string(), version, expires_at: None, tombstone: true, writer: None }
+    }
+
+    pub fn with_writer(self, writer: &NodeId) -> NodeData {
+        NodeData {
+            writer: Some(writer.clone()),
+            ..self
+        }
+    }
+
+    pub fn with_expiry(self, expires_at: u64) -> NodeData {
+        NodeData {
+            expires_at: Some(expires_at),
+            ..self
+        }
+    }
+
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    pub fn writer(&self) -> Option<&NodeId> {
+        self.writer.as_ref()
+    }
+
+    // Replicas keep the value with the highest timestamp, the same on every replica
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::new(self.version, self.writer.clone())
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.tombstone
+    }
+
+    pub fn is_expired(&self, now_millis: u64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now_millis >= expires_at,
+            None => false,
+        }
+    }
+
+    // None when the value is absent
+    pub fn value(&self) -> Option<&str> {
+        self.value_at(now_millis())
+    }
+
+    pub fn value_at(&self, now_millis: u64) -> Option<&str> {
+        if self.tombstone || self.is_expired(now_millis) {
+            return None;
+        }
+        Some(&self.data)
+    }
+
+    pub fn discard_data(&mut self) {
+        self.data.clear();
+    }
+}
+
+// Bytes an entry takes from the anti-entropy bandwidth
+fn entry_bytes(key: &str, data: &NodeData) -> usize {
+    key.len() + data.data.len() + std::mem::size_of::<u32>()
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+// Value of a client write, with its expiry when it has a time to live
+fn client_data(data: String, version: u32, ttl: Option<Duration>) -> NodeData {
+    match ttl {
+        Some(ttl) => NodeData::new(data, version).with_expiry(now_millis() + ttl.as_millis() as u64),
+        None => NodeData::new(data, version),
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+// How the coordinator answered client reads
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    pub fast_path: usize,
+    pub write_back: usize,
+    pub lease: usize,
+    pub local: usize,
+}
+
+// Guarantee of client reads, every node of the cluster has to run with the same semantics.
+// Safe: the coordinator answers from its local copy.
+// Regular: newest value of a quorum, without the write-back phase.
+// Atomic: newest value of a quorum, written back when the quorum does not agree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterSemantics {
+    Safe,
+    Regular,
+    Atomic,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Message {
-    ClientWriteRequest((ClientId, String)), 
-    ClientReadRequest(ClientId),
+    ClientWriteRequest((ClientId, Key, String)), 
+    ClientWriteWithTtlRequest((ClientId, Key, String, Duration)),
+    ClientDeleteRequest((ClientId, Key)),
+    ClientReadRequest((ClientId, Key)),
     ClientReadResponse(NodeData), 
 
-    CoordinatorWriteRequest((NodeId, NodeData)), 
+    AdminStatusRequest(ClientId),
+    AdminStatusResponse(Box<NodeStatus>),
+
+    // Requests to replicas carry the configuration epoch and the round of the coordinator's quorum phase,
+    // answers repeat them. Replicas in a later epoch answer with a ConfigurationUpdate instead.
+    CoordinatorWriteRequest((NodeId, Key, NodeData, u64, Round)), 
+    // Writes of a transaction, applied by a replica all at once
+    CoordinatorBatchWriteRequest((NodeId, Vec<(Key, NodeData)>, u64, Round)),
+
+    CoordinatorReadRequest((NodeId, Key, u64, Round)), 
+    CoordinatorReadResponse((NodeId, Key, NodeData, u64, Round)), 
+
+    // The epoch and round of the acked request, or the coordinator's epoch and operation id for acks to clients
+    WriteAck((NodeId, u64, Round)),       
+
+    LeaseRequest((NodeId, u64)),
+    LeaseGrant((NodeId, u64)),
+
+    // Anti-entropy walks down the Merkle trees of two replicas until the divergent leaves are found
+    MerkleCompare((NodeId, Vec<(usize, u64)>)),
+    MerkleEntries((NodeId, Vec<usize>, Vec<(Key, NodeData)>)),
+    MerklePush((NodeId, Vec<(Key, NodeData)>)),
+
+    // Membership changes carry the epoch of the configuration being installed
+    Reconfigure((ClientId, Vec<NodeId>)),
+    ReconfigureAck(Configuration),
+    StateRequest((NodeId, u64)),
+    StateResponse((NodeId, u64, ReplicaState)),
+    StateTransfer((NodeId, u64, ReplicaState)),
+    StateTransferAck((NodeId, u64)),
+    ConfigurationUpdate(Configuration),
+
+    // Keys stored on the coordinator, used to move registers when the ring changes
+    KeysRequest(ClientId),
+    KeysResponse(Vec<Key>),
 
-    CoordinatorReadRequest(NodeId), 
-    CoordinatorReadResponse(NodeData), 
+    // Moving registers to another group: the old coordinator freezes the keys and returns their newest values,
+    // the new coordinator stores them with their versions, then the old group removes them.
+    // Requests for frozen or removed keys are answered with KeyMoved.
+    MoveOutRequest((ClientId, Vec<Key>)),
+    MoveOutResponse(ReplicaState),
+    MoveInRequest((ClientId, ReplicaState)),
+    RemoveMovedRequest((ClientId, Vec<Key>)),
+    RemoveKeys((NodeId, Vec<Key>, u64, Round)),
+    KeyMoved(Key),
 
-    WriteAck(NodeId),       
+    // Hinted handoff of writes a replica missed while it was unreachable, acked with the delivered timestamps
+    HintReplay((NodeId, Vec<(Key, NodeData)>)),
+    HintAck((NodeId, Vec<(Key, Timestamp)>)),
+
+    // Tombstone collection: a node asks which of its tombstones the other members have,
+    // and once every member has one it is removed everywhere
+    TombstoneQuery((NodeId, Vec<Tombstone>)),
+    TombstoneSeen((NodeId, Vec<Tombstone>)),
+    TombstonePurge((NodeId, Vec<Tombstone>)),
+
+    // Clients get a Notify for every value newer than the timestamp they watch from, once a quorum has it
+    Watch((ClientId, Key, Timestamp)),
+    Unwatch((ClientId, Key)),
+    Notify((Key, NodeData)),
+
+    TransactionCommit((ClientId, ReadSet, WriteSet)),
+    TransactionResult(bool),
+
+    // CRDT registers are written and read on any one node and spread to the others by merging
+    CrdtWrite((ClientId, Key, CrdtUpdate)),
+    CrdtRead((ClientId, Key)),
+    CrdtResponse(CrdtValue),
+    CrdtMerge((NodeId, Vec<(Key, CrdtRegister)>)),
+
+    // Quorum registers that keep concurrent writes as siblings, tracked with version vectors.
+    // The client passes the context of its last read, a write replaces the siblings it has seen.
+    SiblingWriteRequest((ClientId, Key, String, VersionVector)),
+    SiblingReadRequest((ClientId, Key)),
+    SiblingReadResponse(CrdtValue),
+    CoordinatorSiblingReadRequest((NodeId, Key, u64, Round)),
+    CoordinatorSiblingReadResponse((NodeId, Key, MvRegister, u64, Round)),
+    CoordinatorSiblingWriteRequest((NodeId, Key, MvRegister, u64, Round)),
+
+    // Session reads return at least the given timestamp, the coordinator refuses with the newest one it found.
+    // Session writes and deletes are acked with the timestamp they got.
+    SessionWriteRequest((ClientId, Key, String, Option<Duration>)),
+    SessionDeleteRequest((ClientId, Key)),
+    SessionWriteAck((Key, Timestamp)),
+    SessionReadRequest((ClientId, Key, Timestamp)),
+    SessionReadRefused((Key, Timestamp)),
+
+    Raft(RaftMessage),
+}
+
+impl Message {
+    // Name of the variant, for metrics and traces
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Message::ClientWriteRequest(_) => "ClientWriteRequest",
+            Message::ClientWriteWithTtlRequest(_) => "ClientWriteWithTtlRequest",
+            Message::ClientDeleteRequest(_) => "ClientDeleteRequest",
+            Message::ClientReadRequest(_) => "ClientReadRequest",
+            Message::ClientReadResponse(_) => "ClientReadResponse",
+            Message::AdminStatusRequest(_) => "AdminStatusRequest",
+            Message::AdminStatusResponse(_) => "AdminStatusResponse",
+            Message::CoordinatorWriteRequest(_) => "CoordinatorWriteRequest",
+            Message::CoordinatorBatchWriteRequest(_) => "CoordinatorBatchWriteRequest",
+            Message::CoordinatorReadRequest(_) => "CoordinatorReadRequest",
+            Message::CoordinatorReadResponse(_) => "CoordinatorReadResponse",
+            Message::WriteAck(_) => "WriteAck",
+            Message::LeaseRequest(_) => "LeaseRequest",
+            Message::LeaseGrant(_) => "LeaseGrant",
+            Message::MerkleCompare(_) => "MerkleCompare",
+            Message::MerkleEntries(_) => "MerkleEntries",
+            Message::MerklePush(_) => "MerklePush",
+            Message::Reconfigure(_) => "Reconfigure",
+            Message::ReconfigureAck(_) => "ReconfigureAck",
+            Message::StateRequest(_) => "StateRequest",
+            Message::StateResponse(_) => "StateResponse",
+            Message::StateTransfer(_) => "StateTransfer",
+            Message::StateTransferAck(_) => "StateTransferAck",
+            Message::ConfigurationUpdate(_) => "ConfigurationUpdate",
+            Message::KeysRequest(_) => "KeysRequest",
+            Message::KeysResponse(_) => "KeysResponse",
+            Message::MoveOutRequest(_) => "MoveOutRequest",
+            Message::MoveOutResponse(_) => "MoveOutResponse",
+            Message::MoveInRequest(_) => "MoveInRequest",
+            Message::RemoveMovedRequest(_) => "RemoveMovedRequest",
+            Message::RemoveKeys(_) => "RemoveKeys",
+            Message::KeyMoved(_) => "KeyMoved",
+            Message::HintReplay(_) => "HintReplay",
+            Message::HintAck(_) => "HintAck",
+            Message::TombstoneQuery(_) => "TombstoneQuery",
+            Message::TombstoneSeen(_) => "TombstoneSeen",
+            Message::TombstonePurge(_) => "TombstonePurge",
+            Message::Watch(_) => "Watch",
+            Message::Unwatch(_) => "Unwatch",
+            Message::Notify(_) => "Notify",
+            Message::TransactionCommit(_) => "TransactionCommit",
+            Message::TransactionResult(_) => "TransactionResult",
+            Message::CrdtWrite(_) => "CrdtWrite",
+            Message::CrdtRead(_) => "CrdtRead",
+            Message::CrdtResponse(_) => "CrdtResponse",
+            Message::CrdtMerge(_) => "CrdtMerge",
+            Message::SiblingWriteRequest(_) => "SiblingWriteRequest",
+            Message::SiblingReadRequest(_) => "SiblingReadRequest",
+            Message::SiblingReadResponse(_) => "SiblingReadResponse",
+            Message::CoordinatorSiblingReadRequest(_) => "CoordinatorSiblingReadRequest",
+            Message::CoordinatorSiblingReadResponse(_) => "CoordinatorSiblingReadResponse",
+            Message::CoordinatorSiblingWriteRequest(_) => "CoordinatorSiblingWriteRequest",
+            Message::SessionWriteRequest(_) => "SessionWriteRequest",
+            Message::SessionDeleteRequest(_) => "SessionDeleteRequest",
+            Message::SessionWriteAck(_) => "SessionWriteAck",
+            Message::SessionReadRequest(_) => "SessionReadRequest",
+            Message::SessionReadRefused(_) => "SessionReadRefused",
+            Message::Raft(_) => "Raft",
+        }
+    }
 }
 
-#[derive(Clone, PartialEq, Eq)]
-enum NodeState {
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeState {
     Working,
     Restrtart, 
     Breaking, 
@@ -36,11 +336,26 @@ enum NodeState {
 #[derive(Clone)]
 pub struct Node {
     id: NodeId,
-    node_state: NodeState,
-    data: Arc<Mutex<NodeData>>,
+    node_state: Arc<Mutex<NodeState>>,
+    store: Arc<Mutex<RegisterStore>>,
     quorum: Arc<Mutex<Quorum>>,
     network: Arc<Network>,
     messages: Arc<Mutex<Vec<Message>>>,
+    lease: Arc<Mutex<Option<Lease>>>,
+    read_stats: Arc<Mutex<ReadStats>>,
+    anti_entropy: Arc<Mutex<Option<AntiEntropy>>>,
+    configuration: Arc<Mutex<Configuration>>,
+    hints: Arc<Mutex<Option<HintStore>>>,
+    watchers: Arc<Mutex<HashMap<Key, Vec<ClientId>>>>,
+    // Client writes and transaction commits of the coordinator run one at a time
+    write_lock: Arc<Mutex<()>>,
+    crdts: Arc<Mutex<BTreeMap<Key, CrdtRegister>>>,
+    siblings: Arc<Mutex<BTreeMap<Key, MvRegister>>>,
+    semantics: Arc<Mutex<RegisterSemantics>>,
+    tombstone_gc: Arc<Mutex<Option<Duration>>>,
+    // Members that reported each tombstone of this node
+    tombstone_seen: Arc<Mutex<HashMap<Tombstone, Vec<NodeId>>>>,
+    in_flight: Arc<Mutex<InFlight>>,
 }
 
 impl Node {
@@ -51,76 +366,456 @@ impl Node {
     ) -> Node {
         Node { 
             id, 
-            data: Arc::new(Mutex::new(NodeData { data: "".to_string(), version: 0 })), 
-            node_state: NodeState::Working,
+            store: Arc::new(Mutex::new(RegisterStore::new())), 
+            node_state: Arc::new(Mutex::new(NodeState::Working)),
             quorum: Arc::new(Mutex::new(Quorum::new(quorum))),
-            network,
+            network: Arc::clone(&network),
             messages: Arc::new(Mutex::new(Vec::new())),
+            lease: Arc::new(Mutex::new(None)),
+            read_stats: Arc::new(Mutex::new(ReadStats::default())),
+            anti_entropy: Arc::new(Mutex::new(None)),
+            // Nodes added later learn their configuration from the first ConfigurationUpdate
+            configuration: Arc::new(Mutex::new(Configuration::new(0, network.node_ids()))),
+            hints: Arc::new(Mutex::new(None)),
+            watchers: Arc::new(Mutex::new(HashMap::new())),
+            write_lock: Arc::new(Mutex::new(())),
+            crdts: Arc::new(Mutex::new(BTreeMap::new())),
+            siblings: Arc::new(Mutex::new(BTreeMap::new())),
+            semantics: Arc::new(Mutex::new(RegisterSemantics::Atomic)),
+            tombstone_gc: Arc::new(Mutex::new(None)),
+            tombstone_seen: Arc::new(Mutex::new(HashMap::new())),
+            in_flight: Arc::new(Mutex::new(InFlight::default())),
+        }
+    }
+
+    // Lease-based fast reads, every node of the cluster has to run with the same config
+    pub fn with_lease(self, config: LeaseConfig) -> Node {
+        *self.lease.lock().unwrap() = Some(Lease::new(config));
+        self
+    }
+
+    pub fn with_semantics(self, semantics: RegisterSemantics) -> Node {
+        *self.semantics.lock().unwrap() = semantics;
+        self
+    }
+
+    // Tombstones every member has are removed, checked every interval
+    pub fn with_tombstone_gc(self, interval: Duration) -> Node {
+        *self.tombstone_gc.lock().unwrap() = Some(interval);
+        self
+    }
+
+    // Member of a replica group instead of the whole network, the first member coordinates
+    pub fn with_members(self, members: Vec<NodeId>) -> Node {
+        let configuration = Configuration::new(0, members);
+        self.quorum.lock().unwrap().acks = configuration.quorum();
+        *self.configuration.lock().unwrap() = configuration;
+        self
+    }
+
+    // Periodic gossip with random peers so replicas converge without client reads
+    pub fn with_anti_entropy(self, config: AntiEntropyConfig) -> Node {
+        *self.anti_entropy.lock().unwrap() = Some(AntiEntropy::new(config));
+        self
+    }
+
+    // The coordinator keeps the writes unreachable members missed and replays them once they are back
+    pub fn with_hinted_handoff(self, config: HintConfig) -> Node {
+        *self.hints.lock().unwrap() = Some(HintStore::new(config));
+        self
+    }
+
+    // Local copy of a register, without asking the other replicas
+    pub fn stored(&self, key: &str) -> NodeData {
+        self.store.lock().unwrap().get(key)
+    }
+
+    pub fn stored_siblings(&self, key: &str) -> CrdtValue {
+        self.siblings.lock().unwrap().get(key).map(|register| register.value()).unwrap_or_default()
+    }
+
+    pub fn stored_crdt(&self, key: &str) -> CrdtValue {
+        self.crdts.lock().unwrap().get(key).map(|register| register.value()).unwrap_or_default()
+    }
+
+    // Pending and dropped hints of this node
+    pub fn hint_counts(&self) -> (usize, usize) {
+        match self.hints.lock().unwrap().as_ref() {
+            Some(hints) => (hints.len(), hints.dropped()),
+            None => (0, 0),
         }
     }
 
+    pub fn status(&self) -> NodeStatus {
+        let configuration = self.configuration();
+        let peers = configuration.members.iter()
+            .filter(|node_id| **node_id != self.id)
+            .map(|node_id| (node_id.clone(), self.network.is_reachable(node_id)))
+            .collect();
+        let (quorum_state, quorum_round) = {
+            let quorum = self.quorum.lock().unwrap();
+            (quorum.quorum_state.clone(), quorum.round)
+        };
+        let (register_count, registers) = {
+            let store = self.store.lock().unwrap();
+            (store.len(), store.first_registers(STATUS_REGISTERS))
+        };
+
+        NodeStatus {
+            id: self.id.clone(),
+            node_state: self.node_state.lock().unwrap().clone(),
+            quorum_state,
+            quorum_round,
+            configuration,
+            inbox_len: self.messages.lock().unwrap().len(),
+            in_flight: self.in_flight.lock().unwrap().descriptions(),
+            peers,
+            register_count,
+            registers,
+        }
+    }
+
+    pub fn read_stats(&self) -> ReadStats {
+        self.read_stats.lock().unwrap().clone()
+    }
+
+    pub fn configuration(&self) -> Configuration {
+        self.configuration.lock().unwrap().clone()
+    }
+
+    fn epoch(&self) -> u64 {
+        self.configuration.lock().unwrap().epoch
+    }
+
     pub fn run(&mut self) {
         self.start_listen();
+        self.start_anti_entropy();
+        self.start_hint_replay();
+        self.start_expiry_sweep();
+        self.start_tombstone_gc();
+        // The run loop counts as a thread of the node, it waits whenever the inbox is empty
+        self.network.thread_started(&self.id);
 
         loop {
            // self.generate_random_state();
 
-            if self.node_state == NodeState::Breaking {
+            let node_state = self.node_state.lock().unwrap().clone();
+            if node_state == NodeState::Breaking {
                 continue;
-            } else if self.node_state == NodeState::Restrtart {
-                self.node_state = NodeState::Working;
+            } else if node_state == NodeState::Restrtart {
+                *self.node_state.lock().unwrap() = NodeState::Working;
                 continue;
             } 
-            let message = if let Some(message) = self.messages.lock().unwrap().first().cloned() {
+            let message = if let Some(message) = self.get_first_msg() {
                 message
             } else {
+                std::thread::yield_now();
                 continue
             };
 
             let node = self.clone();
 
-            if node.id.0 == 0 {
-                println!("Node {:?} got messages: {:?}", node.id, node.messages.lock().unwrap());
-            }
-            
             match message.clone() {
-                Message::ClientWriteRequest((client_id, data)) => {
-                    node.remove_first_message();
+                Message::ClientWriteRequest((client_id, key, data)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_client_write_request(&client_id, key, data, None);
+                    });
+                },
+                Message::ClientWriteWithTtlRequest((client_id, key, data, ttl)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_client_write_request(&client_id, key, data, Some(ttl));
+                    });
+                },
+                Message::AdminStatusRequest(client_id) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.send_to_client(&client_id, Message::AdminStatusResponse(Box::new(node.status())));
+                    });
+                },
+                Message::ClientDeleteRequest((client_id, key)) => {
+                    node.remove_first_message(&message);
 
-                    std::thread::spawn(move || {
-                        node.handle_client_write_request(&client_id, data);
+                    self.spawn_handler(move || {
+                        node.handle_client_delete_request(&client_id, key);
                     });
                 },
-                Message::ClientReadRequest(client_id) => {
-                    node.remove_first_message();
+                Message::ClientReadRequest((client_id, key)) => {
+                    node.remove_first_message(&message);
 
-                    std::thread::spawn(move || {
-                        node.handle_client_read_request(client_id);
+                    self.spawn_handler(move || {
+                        node.handle_client_read_request(client_id, key);
                     });
                 }, 
-                Message::CoordinatorWriteRequest((node_id, data)) => {
-                    node.remove_first_message();
+                Message::SessionWriteRequest((client_id, key, data, ttl)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_session_write_request(&client_id, key, data, ttl);
+                    });
+                },
+                Message::SessionDeleteRequest((client_id, key)) => {
+                    node.remove_first_message(&message);
 
-                    std::thread::spawn(move || {
-                        node.handle_coordinator_write_request(node_id, data);
+                    self.spawn_handler(move || {
+                        node.handle_session_delete_request(&client_id, key);
                     });
                 },
-                Message::CoordinatorReadRequest(node_id) => {
-                    node.remove_first_message();
+                Message::SessionReadRequest((client_id, key, min_timestamp)) => {
+                    node.remove_first_message(&message);
 
-                    std::thread::spawn(move || {
-                        node.handle_coordinator_read_request(node_id);
+                    self.spawn_handler(move || {
+                        node.handle_session_read_request(client_id, key, min_timestamp);
                     });
                 },
-                Message::CoordinatorReadResponse(_) => {
-                    if !node.quorum.lock().unwrap().is_read_coordinator() {
-                        node.remove_first_message();
+                Message::CoordinatorWriteRequest((node_id, key, data, epoch, round)) => {
+                    if node.lease_blocks_writes_from(&node_id) {
+                        node.defer_first_message(&message);
+                        continue;
+                    }
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_coordinator_write_request(node_id, key, data, epoch, round);
+                    });
+                },
+                Message::CoordinatorBatchWriteRequest((node_id, entries, epoch, round)) => {
+                    if node.lease_blocks_writes_from(&node_id) {
+                        node.defer_first_message(&message);
+                        continue;
                     }
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_coordinator_batch_write_request(node_id, entries, epoch, round);
+                    });
                 },
-                Message::WriteAck(_) => {
-                    if !node.quorum.lock().unwrap().is_write_coordinator() {
-                        node.remove_first_message();
+                Message::TransactionCommit((client_id, reads, writes)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_transaction_commit(client_id, reads, writes);
+                    });
+                },
+                Message::CoordinatorReadRequest((node_id, key, epoch, round)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_coordinator_read_request(node_id, key, epoch, round);
+                    });
+                },
+                Message::LeaseRequest((node_id, request_id)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_lease_request(node_id, request_id);
+                    });
+                },
+                Message::LeaseGrant((node_id, request_id)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_lease_grant(node_id, request_id);
+                    });
+                },
+                Message::MerkleCompare((node_id, hashes)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_merkle_compare(node_id, hashes);
+                    });
+                },
+                Message::MerkleEntries((node_id, leaves, entries)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_merkle_entries(node_id, leaves, entries);
+                    });
+                },
+                Message::MerklePush((node_id, entries)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_merkle_push(node_id, entries);
+                    });
+                },
+                Message::Reconfigure((client_id, members)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_reconfigure(client_id, members);
+                    });
+                },
+                Message::StateRequest((node_id, epoch)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_state_request(node_id, epoch);
+                    });
+                },
+                Message::StateTransfer((node_id, epoch, state)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_state_transfer(node_id, epoch, state);
+                    });
+                },
+                Message::ConfigurationUpdate(configuration) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.install_configuration(configuration);
+                    });
+                },
+                Message::HintReplay((node_id, entries)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_hint_replay(node_id, entries);
+                    });
+                },
+                Message::HintAck((node_id, delivered)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_hint_ack(node_id, delivered);
+                    });
+                },
+                Message::Watch((client_id, key, from)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_watch(client_id, key, from);
+                    });
+                },
+                Message::Unwatch((client_id, key)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_unwatch(client_id, key);
+                    });
+                },
+                Message::CrdtWrite((client_id, key, update)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_crdt_write(client_id, key, update);
+                    });
+                },
+                Message::CrdtRead((client_id, key)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_crdt_read(client_id, key);
+                    });
+                },
+                Message::CrdtMerge((node_id, registers)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_crdt_merge(node_id, registers);
+                    });
+                },
+                Message::SiblingWriteRequest((client_id, key, data, context)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_sibling_write_request(client_id, key, data, context);
+                    });
+                },
+                Message::SiblingReadRequest((client_id, key)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_sibling_read_request(client_id, key);
+                    });
+                },
+                Message::CoordinatorSiblingReadRequest((node_id, key, epoch, round)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_coordinator_sibling_read_request(node_id, key, epoch, round);
+                    });
+                },
+                Message::CoordinatorSiblingWriteRequest((node_id, key, register, epoch, round)) => {
+                    if node.lease_blocks_writes_from(&node_id) {
+                        node.defer_first_message(&message);
+                        continue;
                     }
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_coordinator_sibling_write_request(node_id, key, register, epoch, round);
+                    });
+                },
+                Message::TombstoneQuery((node_id, tombstones)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_tombstone_query(node_id, tombstones);
+                    });
+                },
+                Message::TombstoneSeen((node_id, tombstones)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_tombstone_seen(node_id, tombstones);
+                    });
+                },
+                Message::TombstonePurge((node_id, tombstones)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_tombstone_purge(node_id, tombstones);
+                    });
+                },
+                Message::KeysRequest(client_id) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_keys_request(client_id);
+                    });
+                },
+                Message::MoveOutRequest((client_id, keys)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_move_out_request(client_id, keys);
+                    });
+                },
+                Message::MoveInRequest((client_id, state)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_move_in_request(client_id, state);
+                    });
+                },
+                Message::RemoveMovedRequest((client_id, keys)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_remove_moved_request(client_id, keys);
+                    });
+                },
+                Message::RemoveKeys((node_id, keys, epoch, round)) => {
+                    node.remove_first_message(&message);
+
+                    self.spawn_handler(move || {
+                        node.handle_remove_keys(node_id, keys, epoch, round);
+                    });
+                },
+                Message::CoordinatorReadResponse(_) | Message::CoordinatorSiblingReadResponse(_) if !node.quorum.lock().unwrap().is_read_coordinator() => {
+                    node.remove_first_message(&message);
+                },
+                Message::StateResponse(_) | Message::StateTransferAck(_) if !node.quorum.lock().unwrap().is_reconfiguring() => {
+                    node.remove_first_message(&message);
+                },
+                Message::WriteAck(_) if !node.quorum.lock().unwrap().is_write_coordinator() => {
+                    node.remove_first_message(&message);
                 },
                 _ => { }
             }
@@ -137,214 +832,1327 @@ impl Node {
             while let Some(message) = network.get_node_msg(&node_id) {
                 match message {
                     Message::ClientWriteRequest(_) |  
+                    Message::ClientWriteWithTtlRequest(_) |
+                    Message::ClientDeleteRequest(_) |
+                    Message::AdminStatusRequest(_) |
+                    Message::TombstoneQuery(_) |
+                    Message::TombstoneSeen(_) |
+                    Message::TombstonePurge(_) |
                     Message::ClientReadRequest(_) |
+                    Message::SessionWriteRequest(_) |
+                    Message::SessionDeleteRequest(_) |
+                    Message::SessionReadRequest(_) |
                     Message::CoordinatorWriteRequest(_) |
-                    Message::CoordinatorReadRequest(_) => {
+                    Message::CoordinatorBatchWriteRequest(_) |
+                    Message::TransactionCommit(_) |
+                    Message::CoordinatorReadRequest(_) |
+                    Message::LeaseRequest(_) |
+                    Message::LeaseGrant(_) |
+                    Message::MerkleCompare(_) |
+                    Message::MerkleEntries(_) |
+                    Message::MerklePush(_) |
+                    Message::Reconfigure(_) |
+                    Message::StateRequest(_) |
+                    Message::StateTransfer(_) |
+                    Message::ConfigurationUpdate(_) |
+                    Message::KeysRequest(_) |
+                    Message::MoveOutRequest(_) |
+                    Message::MoveInRequest(_) |
+                    Message::RemoveMovedRequest(_) |
+                    Message::RemoveKeys(_) |
+                    Message::HintReplay(_) |
+                    Message::HintAck(_) |
+                    Message::Watch(_) |
+                    Message::Unwatch(_) |
+                    Message::CrdtWrite(_) |
+                    Message::CrdtRead(_) |
+                    Message::CrdtMerge(_) |
+                    Message::SiblingWriteRequest(_) |
+                    Message::SiblingReadRequest(_) |
+                    Message::CoordinatorSiblingReadRequest(_) |
+                    Message::CoordinatorSiblingWriteRequest(_) => {
+                        messages.lock().unwrap().push(message);
+                    },
+                    Message::StateResponse(_) |
+                    Message::StateTransferAck(_) if quorum.lock().unwrap().is_reconfiguring() => {
                         messages.lock().unwrap().push(message);
                     },
-                    Message::CoordinatorReadResponse(_) => {
-                        if quorum.lock().unwrap().is_read_coordinator() {
-                            messages.lock().unwrap().push(message);
-                        }
+                    Message::CoordinatorReadResponse(_) |
+                    Message::CoordinatorSiblingReadResponse(_) if quorum.lock().unwrap().is_read_coordinator() => {
+                        messages.lock().unwrap().push(message);
                     },
-                    Message::WriteAck(_) => {
-                        if quorum.lock().unwrap().is_write_coordinator() {
-                            messages.lock().unwrap().push(message);
-                        }
+                    Message::WriteAck(_) if quorum.lock().unwrap().is_write_coordinator() => {
+                        messages.lock().unwrap().push(message);
                     },
-                    _ => { }
+                    _ => {
+                        network.taken(&node_id);
+                    }
                 }
             }
         });
     }
 
-    fn remove_first_message(&self) {
-        if self.messages.lock().unwrap().is_empty() {
-            return;
-        }
-        self.messages.lock().unwrap().remove(0);
-    }
+    fn start_anti_entropy(&self) {
+        let interval = match self.anti_entropy.lock().unwrap().as_ref() {
+            Some(anti_entropy) => anti_entropy.config.interval,
+            None => return,
+        };
+        let node = self.clone();
 
-    fn get_first_msg(&self) -> Option<Message> {
-        self.messages.lock().unwrap().first().cloned()
-    }
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            // Timers are off while replaying, a replayed node only does what the recorded deliveries make it do
+            if node.network.is_replaying() {
+                continue;
+            }
 
-    fn generate_random_state(&mut self) {
-        let mut rng = rand::thread_rng();
-        let rnd_number = rng.gen_range(0..=100000000);
+            let configuration = node.configuration();
+            let peers: Vec<NodeId> = configuration.members.iter().filter(|node_id| **node_id != node.id).cloned().collect();
+            if peers.is_empty() || !configuration.contains(&node.id) {
+                continue;
+            }
+            let peer = &peers[rand::thread_rng().gen_range(0..peers.len())];
 
-        if rnd_number <= 5 {
-            println!("Node {:?} is breaking.", self.id);
-            self.node_state = NodeState::Breaking;
-        } else if rnd_number <= 20 {
-            println!("Node {:?} is restarting.", self.id);
-            self.node_state = NodeState::Restrtart;
-        }  
-    }
+            let root = node.store.lock().unwrap().tree().root();
+            node.send_to_node(peer, Message::MerkleCompare((node.id.clone(), vec![(MERKLE_ROOT, root)])));
 
-    fn handle_client_write_request(&self, client_id: &ClientId, data: String) {
-        println!("Coordinator {:?} got write request from client {:?}", self.id, client_id);
+            // CRDT registers are merged as a whole, merging is idempotent so resending is harmless
+            let registers: Vec<(Key, CrdtRegister)> = node.crdts.lock().unwrap().iter().map(|(key, register)| (key.clone(), register.clone())).collect();
+            if !registers.is_empty() {
+                node.send_to_node(peer, Message::CrdtMerge((node.id.clone(), registers)));
+            }
+        });
+    }
 
-        // Phase 1
-        let max_version = self.get_new_data_version();
+    fn start_expiry_sweep(&self) {
+        let node = self.clone();
 
-        // Phase 2
-        self.send_write_request(Message::CoordinatorWriteRequest((self.id.clone(), NodeData { data, version: max_version })));
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EXPIRY_SWEEP_INTERVAL);
+            if node.network.is_replaying() {
+                continue;
+            }
 
-        self.network.send_to_client(client_id, Message::WriteAck(self.id.clone()));
+            let discarded = node.store.lock().unwrap().discard_expired(now_millis());
+            if discarded > 0 {
+                node.trace(TraceEvent::new("expiry.discard").detail(format!("{} expired values", discarded)));
+            }
+        });
     }
 
-    fn get_new_data_version(&self) -> u32 {
-        self.network.send_to_nodes(Message::CoordinatorReadRequest(self.id.clone()), &self.id);
-
-        let mut max_version = self.data.lock().unwrap().version;
-        self.change_quorum_state(QuorumState::WaitingForReadResponse(1));
+    fn start_tombstone_gc(&self) {
+        let interval = match *self.tombstone_gc.lock().unwrap() {
+            Some(interval) => interval,
+            None => return,
+        };
+        let node = self.clone();
 
-        while !self.quorum.lock().unwrap().done_read_quorum() {
-            let message = if let Some(message) = self.get_first_msg() {
-                message.clone()
-            } else {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if node.network.is_replaying() {
                 continue;
-            };
+            }
+
+            let tombstones = node.store.lock().unwrap().tombstones();
+            node.tombstone_seen.lock().unwrap().retain(|tombstone, _| tombstones.contains(tombstone));
+            if !tombstones.is_empty() {
+                node.send_to_members(Message::TombstoneQuery((node.id.clone(), tombstones)));
+            }
+        });
+    }
+
+    fn start_hint_replay(&self) {
+        let interval = match self.hints.lock().unwrap().as_ref() {
+            Some(hints) => hints.config.replay_interval,
+            None => return,
+        };
+        let node = self.clone();
 
-            match message {
-                Message::CoordinatorReadResponse(data) => {
-                    self.remove_first_message();
-                    if data.version > max_version {
-                        max_version = data.version;
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if node.network.is_replaying() {
+                continue;
+            }
+
+            let mut replays = Vec::new();
+            if let Some(hints) = node.hints.lock().unwrap().as_mut() {
+                hints.expire(Instant::now());
+                for node_id in hints.nodes() {
+                    if node.network.is_reachable(&node_id) {
+                        replays.push((node_id.clone(), hints.hints_for(&node_id)));
                     }
+                }
+            }
 
-                    self.quorum.lock().unwrap().increase_read_ack_count();
-                },
-                _ => {}
+            for (node_id, entries) in replays {
+                node.trace(TraceEvent::new("hint.replay").message_type("HintReplay").detail(format!("{} hints to node {:?}", entries.len(), node_id)));
+                node.send_to_node(&node_id, Message::HintReplay((node.id.clone(), entries)));
             }
+        });
+    }
+
+    fn trace(&self, event: TraceEvent) {
+        self.network.trace(event.node(&self.id));
+    }
+
+    fn start_operation(&self, description: String) -> OperationGuard {
+        InFlight::start(&self.in_flight, description)
+    }
+
+    // Removes the message only if it is still the first one, the run loop and the handlers both pop from the front
+    fn remove_first_message(&self, message: &Message) -> bool {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.first() != Some(message) {
+            return false;
         }
+        messages.remove(0);
+        self.network.taken(&self.id);
+        true
+    }
+
+    // Moves the first message to the end of the queue so the ones behind it are not blocked
+    fn defer_first_message(&self, message: &Message) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.first() == Some(message) {
+            let message = messages.remove(0);
+            messages.push(message);
+        }
+        drop(messages);
+        std::thread::yield_now();
+    }
 
-        max_version += 1;
+    fn send_to_members(&self, message: Message) {
+        let members = self.configuration.lock().unwrap().members.clone();
+        self.send_to(&members, message);
+    }
+
+    fn send_to(&self, node_ids: &[NodeId], message: Message) {
+        for node_id in node_ids {
+            if *node_id != self.id {
+                self.send_to_node(node_id, message.clone());
+            }
+        }
+    }
+
+    fn send_to_node(&self, node_id: &NodeId, message: Message) {
+        self.network.send_to_node_from(&Endpoint::Node(self.id.clone()), node_id, message);
+    }
+
+    fn send_to_client(&self, client_id: &ClientId, message: Message) {
+        self.network.send_to_client_from(&self.id, client_id, message);
+    }
+
+    // The calling thread waits while the inbox is empty, see Network::is_idle
+    fn get_first_msg(&self) -> Option<Message> {
+        let message = self.messages.lock().unwrap().first().cloned();
+        WAITING.with(|waiting| {
+            if waiting.get() != message.is_none() {
+                waiting.set(message.is_none());
+                self.network.set_waiting(&self.id, message.is_none());
+            }
+        });
+        message
+    }
+
+    fn spawn_handler(&self, handler: impl FnOnce() + Send + 'static) {
+        self.network.thread_started(&self.id);
+        let network = Arc::clone(&self.network);
+        let node_id = self.id.clone();
+
+        std::thread::spawn(move || {
+            handler();
+            network.thread_finished(&node_id, WAITING.with(|waiting| waiting.get()));
+        });
+    }
+
+    #[allow(dead_code)]
+    fn generate_random_state(&mut self) {
+        let mut rng = rand::thread_rng();
+        let rnd_number = rng.gen_range(0..=100000000);
+
+        if rnd_number <= 5 {
+            self.trace(TraceEvent::new("state.breaking"));
+            *self.node_state.lock().unwrap() = NodeState::Breaking;
+        } else if rnd_number <= 20 {
+            self.trace(TraceEvent::new("state.restarting"));
+            *self.node_state.lock().unwrap() = NodeState::Restrtart;
+        }  
+    }
 
+    fn handle_client_write_request(&self, client_id: &ClientId, key: Key, data: String, ttl: Option<Duration>) {
+        let operation = self.start_operation(format!("write {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("write.start").client(client_id).operation(operation.id()).message_type("ClientWriteRequest").detail(format!("key {:?}", key)));
+
+        match self.write_new_version(key.clone(), operation.id(), |version| client_data(data, version, ttl)) {
+            Some(_) => self.send_to_client(client_id, Message::WriteAck((self.id.clone(), self.epoch(), operation.id()))),
+            None => self.send_to_client(client_id, Message::KeyMoved(key)),
+        }
+    }
+
+    fn handle_session_write_request(&self, client_id: &ClientId, key: Key, data: String, ttl: Option<Duration>) {
+        let operation = self.start_operation(format!("session write {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("write.start").client(client_id).operation(operation.id()).message_type("SessionWriteRequest").detail(format!("key {:?}", key)));
+
+        match self.write_new_version(key.clone(), operation.id(), |version| client_data(data, version, ttl)) {
+            Some(timestamp) => self.send_to_client(client_id, Message::SessionWriteAck((key, timestamp))),
+            None => self.send_to_client(client_id, Message::KeyMoved(key)),
+        }
+    }
+
+    fn handle_session_delete_request(&self, client_id: &ClientId, key: Key) {
+        let operation = self.start_operation(format!("session delete {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("delete.start").client(client_id).operation(operation.id()).message_type("SessionDeleteRequest").detail(format!("key {:?}", key)));
+
+        match self.write_new_version(key.clone(), operation.id(), NodeData::tombstone) {
+            Some(timestamp) => self.send_to_client(client_id, Message::SessionWriteAck((key, timestamp))),
+            None => self.send_to_client(client_id, Message::KeyMoved(key)),
+        }
+    }
+
+    fn handle_client_delete_request(&self, client_id: &ClientId, key: Key) {
+        let operation = self.start_operation(format!("delete {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("delete.start").client(client_id).operation(operation.id()).message_type("ClientDeleteRequest").detail(format!("key {:?}", key)));
+
+        match self.write_new_version(key.clone(), operation.id(), NodeData::tombstone) {
+            Some(_) => self.send_to_client(client_id, Message::WriteAck((self.id.clone(), self.epoch(), operation.id()))),
+            None => self.send_to_client(client_id, Message::KeyMoved(key)),
+        }
+    }
+
+    // Timestamp of the written value, None when the key moved to another replica group
+    fn write_new_version(&self, key: Key, operation: u64, new_data: impl FnOnce(u32) -> NodeData) -> Option<Timestamp> {
+        let _write_lock = self.write_lock.lock().unwrap();
+        if self.store.lock().unwrap().is_moved(&key) {
+            return None;
+        }
+
+        // Phase 1
+        let max_version = self.get_new_data_version(&key);
+        self.trace(TraceEvent::new("write.version").operation(operation).detail(format!("key {:?} version {}", key, max_version)));
+
+        // Phase 2
+        let new_data = new_data(max_version).with_writer(&self.id);
+        // Watchers are notified once the value is committed on a quorum
+        self.store.lock().unwrap().store_if_newer(&key, new_data.clone());
+        self.send_write_request(|epoch, round| Message::CoordinatorWriteRequest((self.id.clone(), key.clone(), new_data.clone(), epoch, round)));
+        self.trace(TraceEvent::new("write.commit").operation(operation).detail(format!("key {:?} version {}", key, max_version)));
+        self.commit_to_lease(&key, &new_data);
+        self.notify_watchers(&key, &new_data);
+
+        Some(new_data.timestamp())
+    }
+
+    fn get_new_data_version(&self, key: &Key) -> u32 {
+        self.newest_timestamp(key).version + 1
+    }
+
+    // Newest timestamp of the key on a quorum, the coordinator included
+    fn newest_timestamp(&self, key: &Key) -> Timestamp {
+        let mut newest = self.store.lock().unwrap().get(key).timestamp();
+        let (epoch, mut round) = self.change_quorum_state(QuorumState::WaitingForReadResponse);
+
+        let started = Instant::now();
+        let build = |epoch, round| Message::CoordinatorReadRequest((self.id.clone(), key.clone(), epoch, round));
+        let mut request = build(epoch, round);
+        let mut retransmit_at = started + QUORUM_RETRANSMIT_INTERVAL;
+        self.send_to_members(request.clone());
+
+        while !self.quorum.lock().unwrap().done_read_quorum() {
+            self.restart_if_reconfigured(&mut round, &mut request, &build);
+            self.retransmit(&request, &mut retransmit_at);
+            let message = if let Some(message) = self.get_first_msg() {
+                message.clone()
+            } else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            if let Message::CoordinatorReadResponse((node_id, response_key, data, response_epoch, response_round)) = message.clone() {
+                if !self.remove_first_message(&message) || response_key != *key || !self.quorum.lock().unwrap().add_response(&node_id, response_epoch, response_round, data.clone()) {
+                    continue;
+                }
+                if data.timestamp() > newest {
+                    newest = data.timestamp();
+                }
+            }
+            // Late answers of a sibling read, the run loop keeps them while this node coordinates a read
+            if let Message::CoordinatorSiblingReadResponse(_) = message {
+                self.remove_first_message(&message);
+            }
+        }
+
+        self.network.metrics().observe(QUORUM_ROUND_TRIP_SECONDS, &[("phase", "version")], started.elapsed());
         self.quorum.lock().unwrap().go_to_waiting_requst();
 
-        max_version
+        newest
     }
 
-    fn change_quorum_state(&self, quorum_state: QuorumState) {
-        while !self.quorum.lock().unwrap().is_waiting_request() {
-            continue;
+    // Requests or answers may have been lost, replicas handle a request twice the same way
+    fn retransmit(&self, request: &Message, retransmit_at: &mut Instant) {
+        let now = Instant::now();
+        if now < *retransmit_at || self.network.is_replaying() {
+            return;
         }
+        *retransmit_at = now + QUORUM_RETRANSMIT_INTERVAL;
+
+        let answered = self.quorum.lock().unwrap().node_ids.clone();
+        let missing: Vec<NodeId> = self.configuration.lock().unwrap().members.iter()
+            .filter(|node_id| !answered.contains(node_id))
+            .cloned()
+            .collect();
+        self.trace(TraceEvent::new("quorum.retransmit").message_type(request.type_name()).detail(format!("to {:?}", missing)));
+        self.send_to(&missing, request.clone());
+    }
 
-        self.quorum.lock().unwrap().quorum_state = quorum_state;
+    // Enter the new state before sending requests, otherwise early answers are dropped by start_listen.
+    // The state gets the coordinator's own answer, which only counts while it is a member.
+    // Returns the epoch and round of the new phase.
+    fn change_quorum_state(&self, quorum_state: fn(usize) -> QuorumState) -> (u64, Round) {
+        loop {
+            // Same lock order as install_configuration
+            let configuration = self.configuration.lock().unwrap();
+            let mut quorum = self.quorum.lock().unwrap();
+            if let Some(round) = quorum.start(quorum_state(configuration.contains(&self.id) as usize)) {
+                return (quorum.epoch, round);
+            }
+            drop(quorum);
+            drop(configuration);
+            std::thread::yield_now();
+        }
     }
 
-    fn send_write_request(&self, message: Message) {
-        self.network.send_to_nodes(message, &self.id);
+    // A configuration installed during the phase restarts it under a new round, the request goes out again to the new members
+    fn restart_if_reconfigured(&self, round: &mut Round, request: &mut Message, build: &impl Fn(u64, Round) -> Message) {
+        let (epoch, current) = {
+            let quorum = self.quorum.lock().unwrap();
+            (quorum.epoch, quorum.round)
+        };
+        if current == *round {
+            return;
+        }
+        *round = current;
+        *request = build(epoch, current);
+        self.trace(TraceEvent::new("quorum.restart").message_type(request.type_name()).detail(format!("epoch {} round {}", epoch, current)));
+        self.send_to_members(request.clone());
+    }
+
+    fn send_write_request(&self, build: impl Fn(u64, Round) -> Message) {
+        let (epoch, mut round) = self.change_quorum_state(QuorumState::WaitingForWriteAck);
+        let mut request = build(epoch, round);
+        match &request {
+            Message::CoordinatorWriteRequest((_, key, data, _, _)) => self.store_hints(key, data),
+            Message::CoordinatorBatchWriteRequest((_, entries, _, _)) => {
+                for (key, data) in entries {
+                    self.store_hints(key, data);
+                }
+            },
+            _ => {}
+        }
 
-        self.change_quorum_state(QuorumState::WaitingForWriteAck(1));
+        let started = Instant::now();
+        let mut retransmit_at = started + QUORUM_RETRANSMIT_INTERVAL;
+        self.send_to_members(request.clone());
 
         while !self.quorum.lock().unwrap().done_write_quorum() {
+            self.restart_if_reconfigured(&mut round, &mut request, &build);
+            self.retransmit(&request, &mut retransmit_at);
             let message = if let Some(message) = self.get_first_msg() {
                 message.clone()
             } else {
+                std::thread::yield_now();
                 continue;
             };
 
-            match message {
-                Message::WriteAck(node_id) => {
-                    self.remove_first_message();
-                    if self.quorum.lock().unwrap().node_ids.contains(&node_id) {
-                        continue;
-                    }
-                    self.quorum.lock().unwrap().node_ids.push(node_id);
-                    self.quorum.lock().unwrap().increase_write_ack_count();
-                },
-                _ => {
-                    self.messages.lock().unwrap().retain(|msg| {
-                        match msg {
-                            Message::CoordinatorReadResponse(_) => false,
-                            _ => true,
-                        }
-                    });    
+            // Stale read responses are dropped by the run loop
+            if let Message::WriteAck((node_id, ack_epoch, ack_round)) = message.clone() {
+                if self.remove_first_message(&message) {
+                    self.quorum.lock().unwrap().add_ack(&node_id, ack_epoch, ack_round);
                 }
             }
         }
 
+        self.network.metrics().observe(QUORUM_ROUND_TRIP_SECONDS, &[("phase", "write")], started.elapsed());
         self.quorum.lock().unwrap().go_to_waiting_requst();
     }
     
-    fn handle_client_read_request(&self, client_id: ClientId) {
-        println!("Coordinator {:?} got read request from client {:?}", self.id, client_id);
+    fn handle_client_read_request(&self, client_id: ClientId, key: Key) {
+        let operation = self.start_operation(format!("read {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("read.start").client(&client_id).operation(operation.id()).message_type("ClientReadRequest").detail(format!("key {:?}", key)));
+
+        if self.refuse_moved(&client_id, &key) || self.read_with_lease(&client_id, &key) {
+            return;
+        }
+
+        let semantics = *self.semantics.lock().unwrap();
+        if semantics == RegisterSemantics::Safe {
+            self.read_stats.lock().unwrap().local += 1;
+            let data = self.store.lock().unwrap().get(&key);
+            self.trace(TraceEvent::new("read.local").operation(operation.id()).detail(format!("key {:?} version {}", key, data.version)));
+            self.send_to_client(&client_id, Message::ClientReadResponse(data));
+            return;
+        }
+
+        let newest_data = self.quorum_read(&key, operation.id(), semantics);
+        // The key may have been removed while the quorum answered
+        if !self.refuse_moved(&client_id, &key) {
+            self.send_to_client(&client_id, Message::ClientReadResponse(newest_data));
+        }
+    }
+    
+    // Below the timestamp of the session a node does not answer locally, and when the quorum has nothing that new
+    // either the read is refused, the session has seen a write the quorum has not finished yet.
+    // Versions alone are not enough, another writer's value of the same version may be older.
+    fn handle_session_read_request(&self, client_id: ClientId, key: Key, min_timestamp: Timestamp) {
+        let operation = self.start_operation(format!("session read {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("read.start").client(&client_id).operation(operation.id()).message_type("SessionReadRequest").detail(format!("key {:?} min timestamp {:?}", key, min_timestamp)));
+        if self.refuse_moved(&client_id, &key) {
+            return;
+        }
 
+        let semantics = *self.semantics.lock().unwrap();
+        let local_data = self.store.lock().unwrap().get(&key);
+        let data = if semantics == RegisterSemantics::Safe && local_data.timestamp() >= min_timestamp {
+            self.read_stats.lock().unwrap().local += 1;
+            self.trace(TraceEvent::new("read.local").operation(operation.id()).detail(format!("key {:?} version {}", key, local_data.version)));
+            local_data
+        } else {
+            self.quorum_read(&key, operation.id(), semantics)
+        };
+
+        if self.refuse_moved(&client_id, &key) {
+            return;
+        }
+        if data.timestamp() >= min_timestamp {
+            self.send_to_client(&client_id, Message::ClientReadResponse(data));
+        } else {
+            self.trace(TraceEvent::new("read.session_refused").operation(operation.id()).detail(format!("key {:?} timestamp {:?} below {:?}", key, data.timestamp(), min_timestamp)));
+            self.send_to_client(&client_id, Message::SessionReadRefused((key, data.timestamp())));
+        }
+    }
+
+    // Safe reads that go to a quorum read like regular ones
+    fn quorum_read(&self, key: &Key, operation: u64, semantics: RegisterSemantics) -> NodeData {
         // Phase 1
-        self.network.send_to_nodes(Message::CoordinatorReadRequest(self.id.clone()), &self.id);
+        let (epoch, mut round) = self.change_quorum_state(QuorumState::WaitingForReadResponse);
 
-        self.change_quorum_state(QuorumState::WaitingForReadResponse(1));
+        let started = Instant::now();
+        let build = |epoch, round| Message::CoordinatorReadRequest((self.id.clone(), key.clone(), epoch, round));
+        let mut request = build(epoch, round);
+        let mut retransmit_at = started + QUORUM_RETRANSMIT_INTERVAL;
+        self.send_to_members(request.clone());
 
         while !self.quorum.lock().unwrap().done_read_quorum() {
+            self.restart_if_reconfigured(&mut round, &mut request, &build);
+            self.retransmit(&request, &mut retransmit_at);
             let message = if let Some(message) = self.get_first_msg() {
                 message.clone()
             } else {
+                std::thread::yield_now();
                 continue;
             };
-            match message {
-                Message::CoordinatorReadResponse(data) => {
-                    self.remove_first_message();
-                    self.quorum.lock().unwrap().node_datas.push(data);
-                    self.quorum.lock().unwrap().increase_read_ack_count();
-                },
-                _ => {}
+            if let Message::CoordinatorReadResponse((node_id, response_key, data, response_epoch, response_round)) = message.clone() {
+                if self.remove_first_message(&message) && response_key == *key {
+                    self.quorum.lock().unwrap().add_response(&node_id, response_epoch, response_round, data);
+                }
+            }
+            if let Message::CoordinatorSiblingReadResponse(_) = message {
+                self.remove_first_message(&message);
             }
         }
 
-        let mut newest_data = self.data.lock().unwrap().clone();
-        let mut nodes_havent_newest_data = false;
-        let data_version = self.data.lock().unwrap().version;
+        self.network.metrics().observe(QUORUM_ROUND_TRIP_SECONDS, &[("phase", "read")], started.elapsed());
+
+        // Fast path when every replica of the quorum, the coordinator included, has the same version
+        let own_data = self.store.lock().unwrap().get(key);
+        let mut newest_data = own_data.clone();
+        let mut quorum_agrees = true;
 
         for data in self.quorum.lock().unwrap().node_datas.iter() {
-            if data.version > data_version {
+            if data.timestamp() != own_data.timestamp() {
+                quorum_agrees = false;
+            }
+            if data.timestamp() > newest_data.timestamp() {
                 newest_data = data.clone();
-            } else if data.version != data_version {
-                nodes_havent_newest_data = true;
             }
         }
 
         self.quorum.lock().unwrap().go_to_waiting_requst();
 
         // Phase 2
-        if nodes_havent_newest_data {
-            self.change_quorum_state(QuorumState::WaitingForWriteAck(1));       
-            self.network.send_to_nodes(Message::CoordinatorWriteRequest((self.id.clone(), newest_data.clone())), &self.id);
-
-            while !self.quorum.lock().unwrap().done_write_quorum() {
-                let message = if let Some(message) = self.get_first_msg() {
-                    message.clone()
-                } else {
-                    continue;
-                };
+        if quorum_agrees {
+            self.commit_to_lease(key, &newest_data);
+            self.read_stats.lock().unwrap().fast_path += 1;
+            self.trace(TraceEvent::new("read.fast_path").operation(operation).detail(format!("key {:?} version {}", key, newest_data.version)));
+        } else if semantics == RegisterSemantics::Atomic {
+            self.read_stats.lock().unwrap().write_back += 1;
+            self.network.metrics().inc(WRITE_BACKS_TOTAL, &[("node", &self.id.0.to_string())]);
+            self.trace(TraceEvent::new("read.write_back").operation(operation).detail(format!("key {:?} version {}", key, newest_data.version)));
+            self.store.lock().unwrap().store_if_newer(key, newest_data.clone());
+            self.send_write_request(|epoch, round| Message::CoordinatorWriteRequest((self.id.clone(), key.clone(), newest_data.clone(), epoch, round)));
+            self.commit_to_lease(key, &newest_data);
+            self.notify_watchers(key, &newest_data);
+        }
 
-                match message {
-                    Message::WriteAck(_) => {
-                        self.remove_first_message();
-                        self.quorum.lock().unwrap().increase_write_ack_count();
-                    },
-                    _ => {}
+        newest_data
+    }
+    
+    fn handle_coordinator_read_request(&self, node_id: NodeId, key: Key, epoch: u64, round: Round) {
+        self.trace(TraceEvent::new("replica.read").message_type("CoordinatorReadRequest").detail(format!("key {:?} from node {:?}", key, node_id)));
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+
+        let data = self.store.lock().unwrap().get(&key);
+        self.send_to_node(&node_id, Message::CoordinatorReadResponse((self.id.clone(), key, data, epoch, round)));
+    }
+    
+    fn handle_coordinator_write_request(&self, node_id: NodeId, key: Key, new_data: NodeData, epoch: u64, round: Round) {
+        self.trace(TraceEvent::new("replica.write").message_type("CoordinatorWriteRequest").detail(format!("key {:?} from node {:?} with data {:?}", key, node_id, new_data)));
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+
+        if !self.store_if_newer(&key, new_data) {
+            self.network.metrics().inc(STALE_WRITES_IGNORED_TOTAL, &[("node", &self.id.0.to_string())]);
+        }
+        self.send_to_node(&node_id, Message::WriteAck((self.id.clone(), epoch, round)));
+    }
+
+    fn handle_coordinator_batch_write_request(&self, node_id: NodeId, entries: Vec<(Key, NodeData)>, epoch: u64, round: Round) {
+        self.trace(TraceEvent::new("replica.batch_write").message_type("CoordinatorBatchWriteRequest").detail(format!("{} keys from node {:?}", entries.len(), node_id)));
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+
+        // One store lock so a replica never answers a read with half of the transaction
+        let mut store = self.store.lock().unwrap();
+        let count = entries.len();
+        let stored = entries.into_iter().filter(|(key, data)| store.store_if_newer(key, data.clone())).count();
+        drop(store);
+
+        if stored < count {
+            self.network.metrics().inc_by(STALE_WRITES_IGNORED_TOTAL, &[("node", &self.id.0.to_string())], (count - stored) as u64);
+        }
+        self.send_to_node(&node_id, Message::WriteAck((self.id.clone(), epoch, round)));
+    }
+
+    // A coordinator that missed a reconfiguration would count the old members for a quorum.
+    // Its requests are refused and it gets the current configuration instead, which restarts its phase.
+    fn refuse_stale_epoch(&self, node_id: &NodeId, epoch: u64) -> bool {
+        let configuration = self.configuration();
+        if epoch >= configuration.epoch {
+            return false;
+        }
+
+        self.trace(TraceEvent::new("reconfigure.refuse").message_type("ConfigurationUpdate").detail(format!("epoch {} from node {:?}, current {:?}", epoch, node_id, configuration)));
+        self.send_to_node(node_id, Message::ConfigurationUpdate(configuration));
+        true
+    }
+
+    // Validates the read set against the newest timestamps of a quorum and writes all keys in one batch.
+    // The write lock keeps other writes of this coordinator out between validation and commit,
+    // the store check catches writes of other coordinators that reached this node meanwhile.
+    fn handle_transaction_commit(&self, client_id: ClientId, reads: ReadSet, writes: WriteSet) {
+        let operation = self.start_operation(format!("transaction of client {:?}", client_id));
+        self.trace(TraceEvent::new("transaction.start").client(&client_id).operation(operation.id()).message_type("TransactionCommit").detail(format!("reading {} and writing {} keys", reads.len(), writes.len())));
+        let _write_lock = self.write_lock.lock().unwrap();
+
+        let keys: BTreeSet<Key> = reads.iter().map(|(key, _)| key.clone()).chain(writes.iter().map(|(key, _)| key.clone())).collect();
+        // Moved keys are aborted like conflicts, the retry reads them from their new group
+        if keys.iter().any(|key| self.store.lock().unwrap().is_moved(key)) {
+            self.trace(TraceEvent::new("transaction.abort").client(&client_id).operation(operation.id()).detail("moved keys".to_string()));
+            self.send_to_client(&client_id, Message::TransactionResult(false));
+            return;
+        }
+        let mut timestamps = BTreeMap::new();
+        for key in keys {
+            let timestamp = self.newest_timestamp(&key);
+            timestamps.insert(key, timestamp);
+        }
+
+        let conflicts: Vec<&Key> = reads.iter().filter(|(key, timestamp)| timestamps[key] != *timestamp).map(|(key, _)| key).collect();
+        if !conflicts.is_empty() {
+            self.trace(TraceEvent::new("transaction.abort").client(&client_id).operation(operation.id()).detail(format!("changed keys {:?}", conflicts)));
+            self.send_to_client(&client_id, Message::TransactionResult(false));
+            return;
+        }
+
+        let entries: Vec<(Key, NodeData)> = writes.into_iter()
+            .map(|(key, data)| {
+                let version = timestamps[&key].version + 1;
+                (key, NodeData::new(data, version).with_writer(&self.id))
+            })
+            .collect();
+
+        if !self.store.lock().unwrap().store_batch(&entries, &timestamps) {
+            self.trace(TraceEvent::new("transaction.abort").client(&client_id).operation(operation.id()).detail("keys changed during the commit".to_string()));
+            self.send_to_client(&client_id, Message::TransactionResult(false));
+            return;
+        }
+        if !entries.is_empty() {
+            self.send_write_request(|epoch, round| Message::CoordinatorBatchWriteRequest((self.id.clone(), entries.clone(), epoch, round)));
+        }
+
+        for (key, data) in entries.iter() {
+            self.commit_to_lease(key, data);
+            self.notify_watchers(key, data);
+        }
+        self.send_to_client(&client_id, Message::TransactionResult(true));
+    }
+
+    // Late write requests must not roll back a newer value
+    fn store_if_newer(&self, key: &str, new_data: NodeData) -> bool {
+        self.store.lock().unwrap().store_if_newer(key, new_data)
+    }
+
+    // Only called by the coordinator once a quorum stores the value, values that replicas get are not notified
+    fn notify_watchers(&self, key: &str, data: &NodeData) {
+        if let Some(client_ids) = self.watchers.lock().unwrap().get(key) {
+            for client_id in client_ids {
+                self.send_to_client(client_id, Message::Notify((key.to_string(), data.clone())));
+            }
+        }
+    }
+
+    // Registered before the quorum read, so a value committed meanwhile is notified or read.
+    // The local store may hold a value no quorum has yet, so the current value comes from a quorum read.
+    fn handle_watch(&self, client_id: ClientId, key: Key, from: Timestamp) {
+        let mut watchers = self.watchers.lock().unwrap();
+        let client_ids = watchers.entry(key.clone()).or_default();
+        if !client_ids.contains(&client_id) {
+            client_ids.push(client_id.clone());
+        }
+        drop(watchers);
+
+        let operation = self.start_operation(format!("watch {:?} of client {:?}", key, client_id));
+        let data = self.quorum_read(&key, operation.id(), RegisterSemantics::Atomic);
+        if data.timestamp() > from {
+            self.send_to_client(&client_id, Message::Notify((key, data)));
+        }
+    }
+
+    fn handle_unwatch(&self, client_id: ClientId, key: Key) {
+        let mut watchers = self.watchers.lock().unwrap();
+        if let Some(client_ids) = watchers.get_mut(&key) {
+            client_ids.retain(|watcher| *watcher != client_id);
+            if client_ids.is_empty() {
+                watchers.remove(&key);
+            }
+        }
+    }
+
+    // Reachability stands in for a failure detector, messages to unreachable members are lost anyway
+    fn store_hints(&self, key: &str, data: &NodeData) {
+        let mut hints = self.hints.lock().unwrap();
+        let hints = match hints.as_mut() {
+            Some(hints) => hints,
+            None => return,
+        };
+
+        let members = self.configuration.lock().unwrap().members.clone();
+        for node_id in members {
+            if node_id != self.id && !self.network.is_reachable(&node_id) && !hints.add(&node_id, key, data.clone(), Instant::now()) {
+                self.trace(TraceEvent::new("hint.drop").detail(format!("key {:?} of node {:?}", key, node_id)));
+            }
+        }
+    }
+
+    // A replica has seen a tombstone when it stores that value or a newer one
+    fn handle_tombstone_query(&self, node_id: NodeId, tombstones: Vec<Tombstone>) {
+        let store = self.store.lock().unwrap();
+        let seen: Vec<Tombstone> = tombstones.into_iter().filter(|(key, timestamp)| store.get(key).timestamp() >= *timestamp).collect();
+        drop(store);
+
+        self.send_to_node(&node_id, Message::TombstoneSeen((self.id.clone(), seen)));
+    }
+
+    fn handle_tombstone_seen(&self, node_id: NodeId, tombstones: Vec<Tombstone>) {
+        let members = self.configuration();
+        let mut tombstone_seen = self.tombstone_seen.lock().unwrap();
+        let mut purge = Vec::new();
+
+        for tombstone in tombstones {
+            let seen_by = tombstone_seen.entry(tombstone.clone()).or_default();
+            if !seen_by.contains(&node_id) {
+                seen_by.push(node_id.clone());
+            }
+
+            if members.members.iter().all(|member| *member == self.id || seen_by.contains(member)) {
+                tombstone_seen.remove(&tombstone);
+                purge.push(tombstone);
+            }
+        }
+        drop(tombstone_seen);
+
+        if !purge.is_empty() {
+            self.send_to_members(Message::TombstonePurge((self.id.clone(), purge.clone())));
+            self.handle_tombstone_purge(self.id.clone(), purge);
+        }
+    }
+
+    fn handle_tombstone_purge(&self, node_id: NodeId, tombstones: Vec<Tombstone>) {
+        let mut store = self.store.lock().unwrap();
+        let purged = tombstones.iter().filter(|(key, timestamp)| store.remove_tombstone(key, timestamp)).count();
+        drop(store);
+
+        if purged > 0 {
+            self.trace(TraceEvent::new("tombstone.collect").message_type("TombstonePurge").detail(format!("{} tombstones on request of node {:?}", purged, node_id)));
+        }
+    }
+
+    fn handle_hint_replay(&self, node_id: NodeId, entries: Vec<(Key, NodeData)>) {
+        let mut delivered = Vec::new();
+        for (key, data) in entries {
+            delivered.push((key.clone(), data.timestamp()));
+            self.store_if_newer(&key, data);
+        }
+
+        self.send_to_node(&node_id, Message::HintAck((self.id.clone(), delivered)));
+    }
+
+    fn handle_hint_ack(&self, node_id: NodeId, delivered: Vec<(Key, Timestamp)>) {
+        if let Some(hints) = self.hints.lock().unwrap().as_mut() {
+            hints.ack(&node_id, &delivered);
+        }
+    }
+
+    fn handle_merkle_compare(&self, node_id: NodeId, hashes: Vec<(usize, u64)>) {
+        let store = self.store.lock().unwrap();
+        let mut child_hashes = Vec::new();
+        let mut leaves = Vec::new();
+
+        for (index, hash) in hashes {
+            if store.tree().hash(index) == hash {
+                continue;
+            }
+
+            if MerkleTree::is_leaf(index) {
+                leaves.push(index);
+            } else {
+                for child in MerkleTree::children(index) {
+                    child_hashes.push((child, store.tree().hash(child)));
                 }
             }
+        }
 
-            self.quorum.lock().unwrap().go_to_waiting_requst();
+        let mut chunks: Vec<(usize, Vec<(Key, NodeData)>)> = leaves.into_iter().map(|leaf| (leaf, store.entries_in_leaves(&[leaf]))).collect();
+        drop(store);
+
+        if !child_hashes.is_empty() {
+            self.send_to_node(&node_id, Message::MerkleCompare((self.id.clone(), child_hashes)));
+        }
+        // Leaves over the bandwidth of this interval are found again by a later round
+        let sizes: Vec<usize> = chunks.iter().map(|(_, entries)| entries.iter().map(|(key, data)| entry_bytes(key, data)).sum()).collect();
+        chunks.truncate(self.reserve_anti_entropy_bandwidth(&sizes));
+        if !chunks.is_empty() {
+            let leaves = chunks.iter().map(|(leaf, _)| *leaf).collect();
+            let entries = chunks.into_iter().flat_map(|(_, entries)| entries).collect();
+            self.send_to_node(&node_id, Message::MerkleEntries((self.id.clone(), leaves, entries)));
         }
+    }
 
-        self.network.send_to_client(&client_id, Message::ClientReadResponse(newest_data));
+    fn handle_merkle_entries(&self, node_id: NodeId, leaves: Vec<usize>, entries: Vec<(Key, NodeData)>) {
+        let mut store = self.store.lock().unwrap();
+        let mut peer_versions = HashMap::new();
+        let mut repaired = 0;
+
+        for (key, data) in entries {
+            peer_versions.insert(key.clone(), data.clone());
+            if store.store_if_newer(&key, data) {
+                repaired += 1;
+            }
+        }
+
+        // Entries the peer is missing or has older versions of
+        let mut push: Vec<(Key, NodeData)> = store.entries_in_leaves(&leaves).into_iter()
+            .filter(|(key, data)| match peer_versions.get(key) {
+                Some(peer_data) => peer_data.timestamp() < data.timestamp(),
+                None => true,
+            })
+            .collect();
+        drop(store);
+
+        if repaired > 0 {
+            self.trace(TraceEvent::new("anti_entropy.repair").message_type("MerkleEntries").detail(format!("{} keys from node {:?}", repaired, node_id)));
+        }
+        let sizes: Vec<usize> = push.iter().map(|(key, data)| entry_bytes(key, data)).collect();
+        push.truncate(self.reserve_anti_entropy_bandwidth(&sizes));
+        if !push.is_empty() {
+            self.send_to_node(&node_id, Message::MerklePush((self.id.clone(), push)));
+        }
     }
-    
-    fn handle_coordinator_read_request(&self, node_id: NodeId) {
-        println!("Node {:?} got read request from coordinator", self.id);
 
-        self.network.send_to_node(&node_id, Message::CoordinatorReadResponse(self.data.lock().unwrap().clone()));
+    fn handle_merkle_push(&self, node_id: NodeId, entries: Vec<(Key, NodeData)>) {
+        let repaired = entries.into_iter().filter(|(key, data)| self.store_if_newer(key, data.clone())).count();
+
+        if repaired > 0 {
+            self.trace(TraceEvent::new("anti_entropy.repair").message_type("MerklePush").detail(format!("{} keys from node {:?}", repaired, node_id)));
+        }
+    }
+
+    // Number of chunks that fit into the bandwidth of this interval, all of them without a cap
+    fn reserve_anti_entropy_bandwidth(&self, chunk_bytes: &[usize]) -> usize {
+        match self.anti_entropy.lock().unwrap().as_mut() {
+            Some(anti_entropy) => anti_entropy.reserve_prefix(chunk_bytes, Instant::now()),
+            None => chunk_bytes.len(),
+        }
+    }
+
+    fn read_with_lease(&self, client_id: &ClientId, key: &Key) -> bool {
+        let mut lease = self.lease.lock().unwrap();
+        let lease = match lease.as_mut() {
+            Some(lease) => lease,
+            None => return false,
+        };
+
+        let now = Instant::now();
+        let data = self.store.lock().unwrap().get(key);
+
+        if let Some(request_id) = lease.renew(&self.id, now) {
+            self.send_to_members(Message::LeaseRequest((self.id.clone(), request_id)));
+        }
+
+        // A value not known to be on a quorum goes through the quorum read, which commits it
+        if !lease.is_held(now) || !lease.is_committed(key, &data.timestamp()) {
+            return false;
+        }
+
+        self.trace(TraceEvent::new("read.lease").client(client_id).detail(format!("key {:?}", key)));
+        self.read_stats.lock().unwrap().lease += 1;
+        self.send_to_client(client_id, Message::ClientReadResponse(data));
+        true
+    }
+
+    fn lease_blocks_writes_from(&self, node_id: &NodeId) -> bool {
+        match self.lease.lock().unwrap().as_mut() {
+            Some(lease) => lease.blocks_writes_from(node_id, Instant::now()),
+            None => false,
+        }
+    }
+
+    fn handle_lease_request(&self, node_id: NodeId, request_id: u64) {
+        let granted = match self.lease.lock().unwrap().as_mut() {
+            Some(lease) => lease.grant(&node_id, Instant::now()),
+            None => false,
+        };
+
+        if granted {
+            self.send_to_node(&node_id, Message::LeaseGrant((self.id.clone(), request_id)));
+        }
+    }
+
+    fn handle_lease_grant(&self, node_id: NodeId, request_id: u64) {
+        let quorum = self.quorum.lock().unwrap().acks;
+        let mut lease = self.lease.lock().unwrap();
+        let lease = match lease.as_mut() {
+            Some(lease) => lease,
+            None => return,
+        };
+
+        if lease.add_grant(request_id, node_id, quorum, Instant::now()) {
+            self.trace(TraceEvent::new("lease.acquired").message_type("LeaseGrant"));
+        }
+    }
+
+    // Only called by the coordinator once a quorum stores the value
+    fn commit_to_lease(&self, key: &str, data: &NodeData) {
+        if let Some(lease) = self.lease.lock().unwrap().as_mut() {
+            lease.commit(key, data.timestamp(), Instant::now());
+        }
+    }
+
+    // Stop-the-world reconfiguration: client operations wait while the coordinator reads the newest
+    // registers from a quorum of the old members, writes them to a quorum of the new members and
+    // only then switches. Every finished write is on an old quorum, so it survives the change.
+    fn handle_reconfigure(&self, client_id: ClientId, members: Vec<NodeId>) {
+        if !members.contains(&self.id) {
+            self.trace(TraceEvent::new("reconfigure.reject").client(&client_id).message_type("Reconfigure").detail(format!("{:?} does not contain the coordinator", members)));
+            self.send_to_client(&client_id, Message::ReconfigureAck(self.configuration()));
+            return;
+        }
+
+        let operation = self.start_operation(format!("reconfiguration to {:?} of client {:?}", members, client_id));
+        self.change_quorum_state(|_| QuorumState::Reconfiguring);
+
+        let old_configuration = self.configuration();
+        let new_configuration = old_configuration.next(members);
+        let epoch = new_configuration.epoch;
+        self.trace(TraceEvent::new("reconfigure.start").client(&client_id).operation(operation.id()).detail(format!("from {:?} to {:?}", old_configuration, new_configuration)));
+
+        // Phase 1, the coordinator only counts for the old quorum if it is one of the old members
+        let mut newest_state = ReplicaState::default();
+        let mut responders = Vec::new();
+        if old_configuration.contains(&self.id) {
+            newest_state = self.replica_state();
+            responders.push(self.id.clone());
+        }
+
+        self.send_to(&old_configuration.members, Message::StateRequest((self.id.clone(), epoch)));
+
+        while responders.len() < old_configuration.quorum() {
+            let message = if let Some(message) = self.get_first_msg() {
+                message
+            } else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            match message.clone() {
+                Message::StateResponse((node_id, response_epoch, state)) => {
+                    if !self.remove_first_message(&message) || response_epoch != epoch || responders.contains(&node_id) {
+                        continue;
+                    }
+                    responders.push(node_id);
+                    newest_state.merge(state);
+                },
+                // Late acks of the previous reconfiguration
+                Message::StateTransferAck(_) => {
+                    self.remove_first_message(&message);
+                },
+                _ => {}
+            }
+        }
+
+        // Phase 2
+        self.install_state(&newest_state);
+        let mut acks = vec![self.id.clone()];
+
+        self.send_to(&new_configuration.members, Message::StateTransfer((self.id.clone(), epoch, newest_state)));
+
+        while acks.len() < new_configuration.quorum() {
+            let message = if let Some(message) = self.get_first_msg() {
+                message
+            } else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            match message.clone() {
+                Message::StateTransferAck((node_id, ack_epoch)) => {
+                    if !self.remove_first_message(&message) || ack_epoch != epoch || acks.contains(&node_id) {
+                        continue;
+                    }
+                    acks.push(node_id);
+                },
+                // Late answers of phase 1, the run loop keeps them while reconfiguring
+                Message::StateResponse(_) => {
+                    self.remove_first_message(&message);
+                },
+                _ => {}
+            }
+        }
+
+        // Removed members learn that they are out, new members learn who their peers are
+        let mut notified = old_configuration.members.clone();
+        notified.extend(new_configuration.members.iter().cloned());
+        notified.sort_by_key(|node_id| node_id.0);
+        notified.dedup();
+        self.send_to(&notified, Message::ConfigurationUpdate(new_configuration.clone()));
+        self.install_configuration(new_configuration.clone());
+
+        self.quorum.lock().unwrap().go_to_waiting_requst();
+
+        self.send_to_client(&client_id, Message::ReconfigureAck(new_configuration));
+    }
+
+    // The coordinator stores every value it writes, so its keys are all keys of the register group
+    // Accepted without any quorum, the other members get the register on a best effort basis
+    fn handle_crdt_write(&self, client_id: ClientId, key: Key, update: CrdtUpdate) {
+        self.trace(TraceEvent::new("crdt.write").client(&client_id).message_type("CrdtWrite").detail(format!("key {:?}", key)));
+
+        let mut crdts = self.crdts.lock().unwrap();
+        let register = crdts.entry(key.clone()).or_insert_with(|| CrdtRegister::new(update.mode));
+        register.write(&self.id, update);
+        let register = register.clone();
+        drop(crdts);
+
+        self.send_to_client(&client_id, Message::CrdtResponse(register.value()));
+        self.send_to_members(Message::CrdtMerge((self.id.clone(), vec![(key, register)])));
+    }
+
+    fn handle_crdt_read(&self, client_id: ClientId, key: Key) {
+        let value = match self.crdts.lock().unwrap().get(&key) {
+            Some(register) => register.value(),
+            None => CrdtValue::default(),
+        };
+        self.send_to_client(&client_id, Message::CrdtResponse(value));
+    }
+
+    fn handle_crdt_merge(&self, node_id: NodeId, registers: Vec<(Key, CrdtRegister)>) {
+        let mut crdts = self.crdts.lock().unwrap();
+        let mut changed = 0;
+
+        for (key, register) in registers {
+            match crdts.get_mut(&key) {
+                Some(own) => {
+                    let before = own.clone();
+                    own.merge(&register);
+                    if *own != before {
+                        changed += 1;
+                    }
+                },
+                None => {
+                    crdts.insert(key, register);
+                    changed += 1;
+                },
+            }
+        }
+
+        if changed > 0 {
+            self.trace(TraceEvent::new("crdt.merge").message_type("CrdtMerge").detail(format!("{} registers from node {:?}", changed, node_id)));
+        }
+    }
+
+    fn handle_sibling_write_request(&self, client_id: ClientId, key: Key, data: String, context: VersionVector) {
+        let operation = self.start_operation(format!("sibling write {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("sibling.write").client(&client_id).operation(operation.id()).message_type("SiblingWriteRequest").detail(format!("key {:?}", key)));
+        let _write_lock = self.write_lock.lock().unwrap();
+        if self.refuse_moved(&client_id, &key) {
+            return;
+        }
+
+        // Phase 1
+        let (mut register, _) = self.read_siblings(&key);
+
+        // Phase 2
+        register.write(&self.id, data, &context);
+        self.siblings.lock().unwrap().insert(key.clone(), register.clone());
+        self.send_write_request(|epoch, round| Message::CoordinatorSiblingWriteRequest((self.id.clone(), key.clone(), register.clone(), epoch, round)));
+
+        self.send_to_client(&client_id, Message::WriteAck((self.id.clone(), self.epoch(), operation.id())));
+    }
+
+    fn handle_sibling_read_request(&self, client_id: ClientId, key: Key) {
+        let operation = self.start_operation(format!("sibling read {:?} of client {:?}", key, client_id));
+        self.trace(TraceEvent::new("sibling.read").client(&client_id).operation(operation.id()).message_type("SiblingReadRequest").detail(format!("key {:?}", key)));
+
+        // Phase 1
+        let (register, quorum_agrees) = self.read_siblings(&key);
+
+        // Phase 2
+        if !quorum_agrees {
+            self.siblings.lock().unwrap().insert(key.clone(), register.clone());
+            self.send_write_request(|epoch, round| Message::CoordinatorSiblingWriteRequest((self.id.clone(), key.clone(), register.clone(), epoch, round)));
+        }
+
+        self.send_to_client(&client_id, Message::SiblingReadResponse(register.value()));
+    }
+
+    // Merge of the registers of a quorum, and whether they were all equal to the local one
+    fn read_siblings(&self, key: &Key) -> (MvRegister, bool) {
+        let own = self.siblings.lock().unwrap().get(key).cloned().unwrap_or_default();
+        let mut merged = own.clone();
+        let mut quorum_agrees = true;
+
+        let (epoch, mut round) = self.change_quorum_state(QuorumState::WaitingForReadResponse);
+
+        let build = |epoch, round| Message::CoordinatorSiblingReadRequest((self.id.clone(), key.clone(), epoch, round));
+        let mut request = build(epoch, round);
+        let mut retransmit_at = Instant::now() + QUORUM_RETRANSMIT_INTERVAL;
+        self.send_to_members(request.clone());
+
+        while !self.quorum.lock().unwrap().done_read_quorum() {
+            self.restart_if_reconfigured(&mut round, &mut request, &build);
+            self.retransmit(&request, &mut retransmit_at);
+            let message = if let Some(message) = self.get_first_msg() {
+                message
+            } else {
+                std::thread::yield_now();
+                continue;
+            };
+
+            if let Message::CoordinatorSiblingReadResponse((node_id, response_key, register, response_epoch, response_round)) = message.clone() {
+                if !self.remove_first_message(&message) || response_key != *key || !self.quorum.lock().unwrap().add_read(&node_id, response_epoch, response_round) {
+                    continue;
+                }
+                if register != own {
+                    quorum_agrees = false;
+                }
+                merged.merge(&register);
+            }
+            // Late answers of a register read
+            if let Message::CoordinatorReadResponse(_) = message {
+                self.remove_first_message(&message);
+            }
+        }
+
+        self.quorum.lock().unwrap().go_to_waiting_requst();
+
+        (merged, quorum_agrees)
+    }
+
+    fn handle_coordinator_sibling_read_request(&self, node_id: NodeId, key: Key, epoch: u64, round: Round) {
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+        let register = self.siblings.lock().unwrap().get(&key).cloned().unwrap_or_default();
+        self.send_to_node(&node_id, Message::CoordinatorSiblingReadResponse((self.id.clone(), key, register, epoch, round)));
+    }
+
+    // Merging instead of replacing, so a late write request never drops a sibling
+    fn handle_coordinator_sibling_write_request(&self, node_id: NodeId, key: Key, register: MvRegister, epoch: u64, round: Round) {
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+        self.siblings.lock().unwrap().entry(key).or_default().merge(&register);
+        self.send_to_node(&node_id, Message::WriteAck((self.id.clone(), epoch, round)));
+    }
+
+    fn handle_keys_request(&self, client_id: ClientId) {
+        let mut keys: BTreeSet<Key> = self.store.lock().unwrap().keys().into_iter().collect();
+        keys.extend(self.siblings.lock().unwrap().keys().cloned());
+        keys.extend(self.crdts.lock().unwrap().keys().cloned());
+        self.send_to_client(&client_id, Message::KeysResponse(keys.into_iter().collect()));
+    }
+
+    // Writes wait for the write lock, so once it is held no write of the keys is in flight
+    // and the values read afterwards are final
+    fn handle_move_out_request(&self, client_id: ClientId, keys: Vec<Key>) {
+        let operation = self.start_operation(format!("move out of {} keys for client {:?}", keys.len(), client_id));
+        self.trace(TraceEvent::new("move.out").client(&client_id).operation(operation.id()).message_type("MoveOutRequest").detail(format!("{:?}", keys)));
+        let _write_lock = self.write_lock.lock().unwrap();
+
+        for key in &keys {
+            self.store.lock().unwrap().mark_moved(key);
+        }
+        let mut state = ReplicaState::default();
+        for key in keys {
+            let data = self.quorum_read(&key, operation.id(), RegisterSemantics::Regular);
+            if data.version() > 0 {
+                state.registers.insert(key.clone(), data);
+            }
+            if self.siblings.lock().unwrap().contains_key(&key) {
+                state.siblings.insert(key.clone(), self.read_siblings(&key).0);
+            }
+            if let Some(register) = self.crdts.lock().unwrap().get(&key) {
+                state.crdts.insert(key, register.clone());
+            }
+        }
+
+        self.send_to_client(&client_id, Message::MoveOutResponse(state));
+    }
+
+    fn handle_move_in_request(&self, client_id: ClientId, state: ReplicaState) {
+        let operation = self.start_operation(format!("move in of {} keys for client {:?}", state.len(), client_id));
+        self.trace(TraceEvent::new("move.in").client(&client_id).operation(operation.id()).message_type("MoveInRequest").detail(format!("{} keys", state.len())));
+        let _write_lock = self.write_lock.lock().unwrap();
+
+        self.install_state(&state);
+        let entries: Vec<(Key, NodeData)> = state.registers.into_iter().collect();
+        self.send_write_request(|epoch, round| Message::CoordinatorBatchWriteRequest((self.id.clone(), entries.clone(), epoch, round)));
+        for (key, register) in state.siblings {
+            self.send_write_request(|epoch, round| Message::CoordinatorSiblingWriteRequest((self.id.clone(), key.clone(), register.clone(), epoch, round)));
+        }
+        // Like every CRDT write, the other members get the registers on a best effort basis
+        if !state.crdts.is_empty() {
+            self.send_to_members(Message::CrdtMerge((self.id.clone(), state.crdts.into_iter().collect())));
+        }
+        for (key, data) in &entries {
+            self.commit_to_lease(key, data);
+            self.notify_watchers(key, data);
+        }
+
+        self.send_to_client(&client_id, Message::WriteAck((self.id.clone(), self.epoch(), operation.id())));
+    }
+
+    fn handle_remove_moved_request(&self, client_id: ClientId, keys: Vec<Key>) {
+        let operation = self.start_operation(format!("removal of {} moved keys for client {:?}", keys.len(), client_id));
+        self.trace(TraceEvent::new("move.remove").client(&client_id).operation(operation.id()).message_type("RemoveMovedRequest").detail(format!("{:?}", keys)));
+
+        for key in &keys {
+            self.remove_moved(key);
+        }
+        self.send_write_request(|epoch, round| Message::RemoveKeys((self.id.clone(), keys.clone(), epoch, round)));
+
+        self.send_to_client(&client_id, Message::WriteAck((self.id.clone(), self.epoch(), operation.id())));
+    }
+
+    // Replicas refuse later versions of the keys too, so anti-entropy and hints can not bring them back
+    fn handle_remove_keys(&self, node_id: NodeId, keys: Vec<Key>, epoch: u64, round: Round) {
+        self.trace(TraceEvent::new("replica.remove").message_type("RemoveKeys").detail(format!("{:?} from node {:?}", keys, node_id)));
+        if self.refuse_stale_epoch(&node_id, epoch) {
+            return;
+        }
+
+        for key in &keys {
+            self.remove_moved(key);
+        }
+        self.send_to_node(&node_id, Message::WriteAck((self.id.clone(), epoch, round)));
+    }
+
+    fn remove_moved(&self, key: &Key) {
+        self.store.lock().unwrap().remove_moved(key);
+        self.siblings.lock().unwrap().remove(key);
+        self.crdts.lock().unwrap().remove(key);
     }
-    
-    fn handle_coordinator_write_request(&self, node_id: NodeId, new_data: NodeData) {
-        println!("Node {:?} got write request from coordinator with data: {:?}", self.id, new_data);
 
-        *self.data.lock().unwrap() = new_data;
-        self.network.send_to_node(&node_id, Message::WriteAck(self.id.clone()));
+    // The client asks the coordinator of the key's new group instead
+    fn refuse_moved(&self, client_id: &ClientId, key: &Key) -> bool {
+        if !self.store.lock().unwrap().is_moved(key) {
+            return false;
+        }
+
+        self.trace(TraceEvent::new("move.refuse").client(client_id).message_type("KeyMoved").detail(format!("key {:?}", key)));
+        self.send_to_client(client_id, Message::KeyMoved(key.clone()));
+        true
+    }
+
+    fn handle_state_request(&self, node_id: NodeId, epoch: u64) {
+        self.send_to_node(&node_id, Message::StateResponse((self.id.clone(), epoch, self.replica_state())));
+    }
+
+    fn handle_state_transfer(&self, node_id: NodeId, epoch: u64, state: ReplicaState) {
+        self.trace(TraceEvent::new("reconfigure.transfer").message_type("StateTransfer").detail(format!("{} keys for epoch {} from node {:?}", state.len(), epoch, node_id)));
+
+        self.install_state(&state);
+        self.send_to_node(&node_id, Message::StateTransferAck((self.id.clone(), epoch)));
+    }
+
+    fn replica_state(&self) -> ReplicaState {
+        ReplicaState {
+            registers: self.store.lock().unwrap().entries().into_iter().collect(),
+            siblings: self.siblings.lock().unwrap().clone(),
+            crdts: self.crdts.lock().unwrap().clone(),
+        }
+    }
+
+    // Registers only go forward, siblings and CRDT registers are merged into the local ones
+    fn install_state(&self, state: &ReplicaState) {
+        for (key, data) in &state.registers {
+            self.store_if_newer(key, data.clone());
+        }
+
+
+        let mut siblings = self.siblings.lock().unwrap();
+        for (key, register) in &state.siblings {
+            siblings.entry(key.clone()).or_default().merge(register);
+        }
+        drop(siblings);
+
+        let mut crdts = self.crdts.lock().unwrap();
+        for (key, register) in &state.crdts {
+            match crdts.get_mut(key) {
+                Some(own) => own.merge(register),
+                None => {
+                    crdts.insert(key.clone(), register.clone());
+                },
+            }
+        }
+    }
+
+    fn install_configuration(&self, configuration: Configuration) {
+        let mut current = self.configuration.lock().unwrap();
+        if configuration.epoch <= current.epoch {
+            return;
+        }
+        self.trace(TraceEvent::new("reconfigure.install").detail(format!("{:?}", configuration)));
+
+        self.quorum.lock().unwrap().reconfigure(configuration.epoch, configuration.quorum(), configuration.contains(&self.id));
+        *current = configuration;
+        drop(current);
+
+        if let Some(lease) = self.lease.lock().unwrap().as_mut() {
+            lease.release();
+        }
     }
 }
\ No newline at end of file
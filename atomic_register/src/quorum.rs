@@ -1,13 +1,24 @@
+use serde::{Deserialize, Serialize};
+
 use crate::node::{NodeData, NodeId};
 
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QuorumState {
     WaitingForWriteAck(usize), // read acks count
     WaitingForReadResponse(usize), // write acks count
     WaitingForRequest,
+    // Client operations wait until the new configuration is installed
+    Reconfiguring,
 }
 
+// Numbers the quorum phases of a coordinator, requests carry it and answers of another phase are dropped
+pub type Round = u64;
+
 pub struct Quorum {
     pub acks: usize,
+    // Epoch of the configuration the quorum is counted in, answers of other epochs are dropped
+    pub epoch: u64,
+    pub round: Round,
     pub node_datas: Vec<NodeData>,
     pub node_ids: Vec<NodeId>,
     pub quorum_state: QuorumState,
@@ -17,12 +28,38 @@ impl Quorum {
     pub fn new(acks: usize) -> Quorum {
         Quorum { 
             acks, 
+            epoch: 0,
+            round: 0,
             node_datas: Vec::new(), 
             node_ids: Vec::new(), 
             quorum_state: QuorumState::WaitingForRequest 
         }
     }
 
+    // Only a waiting quorum starts a new phase, returns the round of it
+    pub fn start(&mut self, quorum_state: QuorumState) -> Option<Round> {
+        if !self.is_waiting_request() {
+            return None;
+        }
+        self.quorum_state = quorum_state;
+        self.round += 1;
+        Some(self.round)
+    }
+
+    // A new configuration restarts a phase in progress under a new round, answers of the old members do not count.
+    // The coordinator answers for itself only as a member.
+    pub fn reconfigure(&mut self, epoch: u64, acks: usize, member: bool) {
+        self.epoch = epoch;
+        self.acks = acks;
+        match self.quorum_state {
+            QuorumState::WaitingForWriteAck(ref mut count) | QuorumState::WaitingForReadResponse(ref mut count) => *count = member as usize,
+            _ => return,
+        }
+        self.round += 1;
+        self.node_datas.clear();
+        self.node_ids.clear();
+    }
+
     pub fn go_to_waiting_requst(&mut self) {
         self.quorum_state = QuorumState::WaitingForRequest;
         self.node_datas.clear();
@@ -30,24 +67,19 @@ impl Quorum {
     }
 
     pub fn is_read_coordinator(&self) -> bool {
-        match self.quorum_state {
-            QuorumState::WaitingForReadResponse(_) => true,
-            _ => false,
-        }
+        matches!(self.quorum_state, QuorumState::WaitingForReadResponse(_))
+    }
+
+    pub fn is_reconfiguring(&self) -> bool {
+        matches!(self.quorum_state, QuorumState::Reconfiguring)
     }
 
     pub fn is_waiting_request(&self) -> bool {
-        match self.quorum_state {
-            QuorumState::WaitingForRequest => true,
-            _ => false,
-        }
+        matches!(self.quorum_state, QuorumState::WaitingForRequest)
     }
 
     pub fn is_write_coordinator(&self) -> bool {
-        match self.quorum_state {
-            QuorumState::WaitingForWriteAck(_) => true,
-            _ => false,
-        }
+        matches!(self.quorum_state, QuorumState::WaitingForWriteAck(_))
     }
 
     pub fn done_read_quorum(&self) -> bool {
@@ -64,17 +96,42 @@ impl Quorum {
         }
     }
 
-    pub fn increase_write_ack_count(&mut self) {
-        match self.quorum_state {
-            QuorumState::WaitingForWriteAck(ref mut count) => *count += 1,
-            _ => panic!("Not in write ack state"),
+    // Counts a write ack of the current epoch and round once per replica and only while the write phase waits for acks.
+    // False when it does not count.
+    pub fn add_ack(&mut self, node_id: &NodeId, epoch: u64, round: Round) -> bool {
+        if !self.is_write_coordinator() || self.done_write_quorum() {
+            return false;
         }
+        self.count(node_id, epoch, round)
     }
 
-    pub fn increase_read_ack_count(&mut self) {
+    // Same for read responses
+    pub fn add_read(&mut self, node_id: &NodeId, epoch: u64, round: Round) -> bool {
+        if !self.is_read_coordinator() || self.done_read_quorum() {
+            return false;
+        }
+        self.count(node_id, epoch, round)
+    }
+
+    // Read responses that count keep the value of the replica
+    pub fn add_response(&mut self, node_id: &NodeId, epoch: u64, round: Round, data: NodeData) -> bool {
+        if !self.add_read(node_id, epoch, round) {
+            return false;
+        }
+        self.node_datas.push(data);
+        true
+    }
+
+    // Late answers of earlier epochs or rounds and second answers of a replica are dropped
+    fn count(&mut self, node_id: &NodeId, epoch: u64, round: Round) -> bool {
+        if epoch != self.epoch || round != self.round || self.node_ids.contains(node_id) {
+            return false;
+        }
         match self.quorum_state {
-            QuorumState::WaitingForReadResponse(ref mut count) => *count += 1,
-            _ => panic!("Not in read response state"),
+            QuorumState::WaitingForWriteAck(ref mut count) | QuorumState::WaitingForReadResponse(ref mut count) => *count += 1,
+            _ => return false,
         }
+        self.node_ids.push(node_id.clone());
+        true
     }
 }
@@ -0,0 +1,599 @@
+use std::{collections::{BTreeMap, HashMap}, sync::Arc, time::{Duration, Instant}};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{atomic_register_client::ClientId, network::Network, node::{Message, NodeData, NodeId}, replay::Endpoint, store::Key, trace::TraceEvent};
+
+// Raft replicated state machine over the same registers as the ABD nodes
+
+const ELECTION_TIMEOUT_MIN_MS: u64 = 150;
+const ELECTION_TIMEOUT_MAX_MS: u64 = 300;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_ENTRIES_PER_APPEND: usize = 64;
+const SNAPSHOT_THRESHOLD: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaftCommand {
+    Write(Key, String),
+    Read(Key),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub term: u64,
+    pub client_id: ClientId,
+    pub seq: u64,
+    pub command: RaftCommand,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub last_included_index: u64,
+    pub last_included_term: u64,
+    pub registers: BTreeMap<Key, NodeData>,
+    pub client_requests: BTreeMap<ClientId, (u64, NodeData)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RaftMessage {
+    RequestVote {
+        term: u64,
+        candidate_id: NodeId,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    Vote {
+        term: u64,
+        voter_id: NodeId,
+        granted: bool,
+    },
+    AppendEntries {
+        term: u64,
+        leader_id: NodeId,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    },
+    AppendEntriesResponse {
+        term: u64,
+        follower_id: NodeId,
+        success: bool,
+        match_index: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        leader_id: NodeId,
+        snapshot: Snapshot,
+    },
+    // Requests carry a per-client sequence number so retries are applied once
+    ClientRequest {
+        client_id: ClientId,
+        seq: u64,
+        command: RaftCommand,
+    },
+    ClientResponse {
+        seq: u64,
+        data: NodeData,
+    },
+    // Sent to a client that contacted a node which is not the leader
+    Redirect(Option<NodeId>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RaftRole {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+pub struct RaftNode {
+    id: NodeId,
+    peers: Vec<NodeId>,
+    network: Arc<Network>,
+
+    role: RaftRole,
+    current_term: u64,
+    voted_for: Option<NodeId>,
+    leader_id: Option<NodeId>,
+    votes: Vec<NodeId>,
+
+    // Entries after the snapshot, log[0] has index snapshot.last_included_index + 1
+    log: Vec<LogEntry>,
+    snapshot: Snapshot,
+    commit_index: u64,
+    last_applied: u64,
+    registers: BTreeMap<Key, NodeData>,
+    // Last applied request of each client and its answer
+    client_requests: BTreeMap<ClientId, (u64, NodeData)>,
+
+    next_index: HashMap<NodeId, u64>,
+    match_index: HashMap<NodeId, u64>,
+    // Entries appended by this node as leader, the client gets the answer once they are applied
+    pending_clients: HashMap<u64, ClientId>,
+
+    election_deadline: Instant,
+    next_heartbeat: Instant,
+}
+
+impl RaftNode {
+    pub fn new(id: NodeId, peers: Vec<NodeId>, network: Arc<Network>) -> RaftNode {
+        let peers = peers.into_iter().filter(|peer| *peer != id).collect();
+
+        RaftNode {
+            id,
+            peers,
+            network,
+            role: RaftRole::Follower,
+            current_term: 0,
+            voted_for: None,
+            leader_id: None,
+            votes: Vec::new(),
+            log: Vec::new(),
+            snapshot: Snapshot { last_included_index: 0, last_included_term: 0, registers: BTreeMap::new(), client_requests: BTreeMap::new() },
+            commit_index: 0,
+            last_applied: 0,
+            registers: BTreeMap::new(),
+            client_requests: BTreeMap::new(),
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            pending_clients: HashMap::new(),
+            election_deadline: Instant::now() + random_election_timeout(),
+            next_heartbeat: Instant::now(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            let deadline = if self.role == RaftRole::Leader {
+                self.next_heartbeat
+            } else {
+                self.election_deadline
+            };
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            if let Some(message) = self.network.get_node_msg_timeout(&self.id, timeout) {
+                self.handle_message(message);
+            }
+
+            self.tick();
+        }
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+
+        if self.role == RaftRole::Leader {
+            if now >= self.next_heartbeat {
+                self.broadcast_append_entries();
+            }
+        } else if now >= self.election_deadline {
+            self.start_election();
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        let raft_message = if let Message::Raft(raft_message) = message {
+            raft_message
+        } else {
+            return;
+        };
+
+        match raft_message {
+            RaftMessage::ClientRequest { client_id, seq, command } => {
+                self.handle_client_request(client_id, seq, command);
+            },
+            RaftMessage::RequestVote { term, candidate_id, last_log_index, last_log_term } => {
+                self.handle_request_vote(term, candidate_id, last_log_index, last_log_term);
+            },
+            RaftMessage::Vote { term, voter_id, granted } => {
+                self.handle_vote(term, voter_id, granted);
+            },
+            RaftMessage::AppendEntries { term, leader_id, prev_log_index, prev_log_term, entries, leader_commit } => {
+                self.handle_append_entries(term, leader_id, prev_log_index, prev_log_term, entries, leader_commit);
+            },
+            RaftMessage::AppendEntriesResponse { term, follower_id, success, match_index } => {
+                self.handle_append_entries_response(term, follower_id, success, match_index);
+            },
+            RaftMessage::InstallSnapshot { term, leader_id, snapshot } => {
+                self.handle_install_snapshot(term, leader_id, snapshot);
+            },
+            RaftMessage::ClientResponse { .. } | RaftMessage::Redirect(_) => {},
+        }
+    }
+
+    fn handle_client_request(&mut self, client_id: ClientId, seq: u64, command: RaftCommand) {
+        if self.role != RaftRole::Leader {
+            self.network.send_to_client_from(&self.id, &client_id, Message::Raft(RaftMessage::Redirect(self.leader_id.clone())));
+            return;
+        }
+
+        self.log.push(LogEntry { term: self.current_term, client_id: client_id.clone(), seq, command });
+        self.pending_clients.insert(self.last_log_index(), client_id);
+
+        if self.peers.is_empty() {
+            self.advance_commit_index();
+        } else {
+            self.broadcast_append_entries();
+        }
+    }
+
+    fn start_election(&mut self) {
+        self.role = RaftRole::Candidate;
+        self.current_term += 1;
+        self.voted_for = Some(self.id.clone());
+        self.leader_id = None;
+        self.votes = vec![self.id.clone()];
+        self.pending_clients.clear();
+        self.election_deadline = Instant::now() + random_election_timeout();
+
+        self.trace(TraceEvent::new("raft.election").detail(format!("term {}", self.current_term)));
+
+        if self.votes.len() >= self.majority() {
+            self.become_leader();
+            return;
+        }
+
+        let message = Message::Raft(RaftMessage::RequestVote {
+            term: self.current_term,
+            candidate_id: self.id.clone(),
+            last_log_index: self.last_log_index(),
+            last_log_term: self.last_log_term(),
+        });
+        for peer in &self.peers {
+            self.send_to_node(peer, message.clone());
+        }
+    }
+
+    fn become_leader(&mut self) {
+        self.trace(TraceEvent::new("raft.leader").detail(format!("term {}", self.current_term)));
+
+        self.role = RaftRole::Leader;
+        self.leader_id = Some(self.id.clone());
+        self.next_index.clear();
+        self.match_index.clear();
+        for peer in &self.peers {
+            self.next_index.insert(peer.clone(), self.last_log_index() + 1);
+            self.match_index.insert(peer.clone(), 0);
+        }
+
+        self.broadcast_append_entries();
+    }
+
+    fn step_down(&mut self, term: u64) {
+        if term > self.current_term {
+            self.current_term = term;
+            self.voted_for = None;
+        }
+        self.role = RaftRole::Follower;
+        self.votes.clear();
+        self.pending_clients.clear();
+    }
+
+    fn handle_request_vote(&mut self, term: u64, candidate_id: NodeId, last_log_index: u64, last_log_term: u64) {
+        if term > self.current_term {
+            self.step_down(term);
+        }
+
+        let log_is_up_to_date = last_log_term > self.last_log_term()
+            || (last_log_term == self.last_log_term() && last_log_index >= self.last_log_index());
+        let can_vote = match &self.voted_for {
+            Some(voted_for) => *voted_for == candidate_id,
+            None => true,
+        };
+        let granted = term == self.current_term && can_vote && log_is_up_to_date;
+
+        if granted {
+            self.voted_for = Some(candidate_id.clone());
+            self.election_deadline = Instant::now() + random_election_timeout();
+        }
+
+        self.send_to_node(&candidate_id, Message::Raft(RaftMessage::Vote {
+            term: self.current_term,
+            voter_id: self.id.clone(),
+            granted,
+        }));
+    }
+
+    fn handle_vote(&mut self, term: u64, voter_id: NodeId, granted: bool) {
+        if term > self.current_term {
+            self.step_down(term);
+            return;
+        }
+
+        if self.role != RaftRole::Candidate || term != self.current_term || !granted || self.votes.contains(&voter_id) {
+            return;
+        }
+
+        self.votes.push(voter_id);
+        if self.votes.len() >= self.majority() {
+            self.become_leader();
+        }
+    }
+
+    fn handle_append_entries(
+        &mut self,
+        term: u64,
+        leader_id: NodeId,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<LogEntry>,
+        leader_commit: u64,
+    ) {
+        if term < self.current_term {
+            self.send_append_entries_response(&leader_id, false, 0);
+            return;
+        }
+
+        self.step_down(term);
+        self.leader_id = Some(leader_id.clone());
+        self.election_deadline = Instant::now() + random_election_timeout();
+
+        if prev_log_index < self.snapshot.last_included_index {
+            // The prefix is already compacted, ask the leader to continue after the snapshot
+            let match_index = self.snapshot.last_included_index;
+            self.send_append_entries_response(&leader_id, false, match_index);
+            return;
+        }
+
+        if prev_log_index > self.last_log_index() {
+            let match_index = self.last_log_index();
+            self.send_append_entries_response(&leader_id, false, match_index);
+            return;
+        }
+
+        if self.term_at(prev_log_index) != Some(prev_log_term) {
+            self.send_append_entries_response(&leader_id, false, prev_log_index.saturating_sub(1));
+            return;
+        }
+
+        let mut index = prev_log_index;
+        for entry in entries {
+            index += 1;
+            match self.term_at(index) {
+                Some(existing_term) if existing_term == entry.term => continue,
+                Some(_) => {
+                    self.log.truncate(self.log_position(index));
+                    self.log.push(entry);
+                },
+                None => self.log.push(entry),
+            }
+        }
+
+        if leader_commit > self.commit_index {
+            self.commit_index = leader_commit.min(index);
+            self.apply_committed();
+        }
+
+        self.send_append_entries_response(&leader_id, true, index);
+    }
+
+    fn handle_append_entries_response(&mut self, term: u64, follower_id: NodeId, success: bool, match_index: u64) {
+        if term > self.current_term {
+            self.step_down(term);
+            return;
+        }
+
+        if self.role != RaftRole::Leader || term != self.current_term {
+            return;
+        }
+
+        if success {
+            let current_match = self.match_index.get(&follower_id).cloned().unwrap_or(0);
+            if match_index > current_match {
+                self.match_index.insert(follower_id.clone(), match_index);
+            }
+            self.next_index.insert(follower_id, match_index.max(current_match) + 1);
+            self.advance_commit_index();
+        } else {
+            let next_index = self.next_index.get(&follower_id).cloned().unwrap_or(1);
+            let next_index = if match_index >= next_index {
+                // The follower compacted its log past prev_log_index, continue after its snapshot
+                match_index + 1
+            } else {
+                (next_index - 1).min(match_index + 1).max(1)
+            };
+            self.next_index.insert(follower_id.clone(), next_index);
+            self.send_append_entries(&follower_id);
+        }
+    }
+
+    fn handle_install_snapshot(&mut self, term: u64, leader_id: NodeId, snapshot: Snapshot) {
+        if term < self.current_term {
+            self.send_append_entries_response(&leader_id, false, 0);
+            return;
+        }
+
+        self.step_down(term);
+        self.leader_id = Some(leader_id.clone());
+        self.election_deadline = Instant::now() + random_election_timeout();
+
+        let last_included_index = snapshot.last_included_index;
+
+        if last_included_index > self.commit_index {
+            self.trace(TraceEvent::new("raft.snapshot").message_type("InstallSnapshot").detail(format!("up to index {}", last_included_index)));
+
+            if self.term_at(last_included_index) == Some(snapshot.last_included_term) {
+                let position = self.log_position(last_included_index) + 1;
+                self.log.drain(..position);
+            } else {
+                self.log.clear();
+            }
+
+            self.registers = snapshot.registers.clone();
+            self.client_requests = snapshot.client_requests.clone();
+            self.commit_index = last_included_index;
+            self.last_applied = last_included_index;
+            self.snapshot = snapshot;
+        }
+
+        self.send_append_entries_response(&leader_id, true, last_included_index);
+    }
+
+    fn send_append_entries_response(&self, leader_id: &NodeId, success: bool, match_index: u64) {
+        self.send_to_node(leader_id, Message::Raft(RaftMessage::AppendEntriesResponse {
+            term: self.current_term,
+            follower_id: self.id.clone(),
+            success,
+            match_index,
+        }));
+    }
+
+    fn broadcast_append_entries(&mut self) {
+        for peer in self.peers.clone() {
+            self.send_append_entries(&peer);
+        }
+        self.next_heartbeat = Instant::now() + HEARTBEAT_INTERVAL;
+    }
+
+    fn send_append_entries(&self, peer: &NodeId) {
+        let next_index = self.next_index.get(peer).cloned().unwrap_or(self.last_log_index() + 1);
+
+        if next_index <= self.snapshot.last_included_index {
+            self.send_to_node(peer, Message::Raft(RaftMessage::InstallSnapshot {
+                term: self.current_term,
+                leader_id: self.id.clone(),
+                snapshot: self.snapshot.clone(),
+            }));
+            return;
+        }
+
+        let prev_log_index = next_index - 1;
+        let start = self.log_position(next_index);
+        let end = (start + MAX_ENTRIES_PER_APPEND).min(self.log.len());
+
+        self.send_to_node(peer, Message::Raft(RaftMessage::AppendEntries {
+            term: self.current_term,
+            leader_id: self.id.clone(),
+            prev_log_index,
+            prev_log_term: self.term_at(prev_log_index).unwrap_or(0),
+            entries: self.log[start..end].to_vec(),
+            leader_commit: self.commit_index,
+        }));
+    }
+
+    fn advance_commit_index(&mut self) {
+        let mut index = self.last_log_index();
+
+        while index > self.commit_index {
+            // Only entries from the current term are committed by counting replicas
+            if self.term_at(index) == Some(self.current_term) {
+                let replicas = 1 + self.match_index.values().filter(|match_index| **match_index >= index).count();
+                if replicas >= self.majority() {
+                    self.commit_index = index;
+                    self.apply_committed();
+                    return;
+                }
+            }
+            index -= 1;
+        }
+    }
+
+    fn apply_committed(&mut self) {
+        while self.last_applied < self.commit_index {
+            self.last_applied += 1;
+            let entry = self.log[self.log_position(self.last_applied)].clone();
+            let answer = self.apply(&entry);
+
+            if let (Some(client_id), Some(data)) = (self.pending_clients.remove(&self.last_applied), answer) {
+                self.network.send_to_client_from(&self.id, &client_id, Message::Raft(RaftMessage::ClientResponse { seq: entry.seq, data }));
+            }
+        }
+
+        self.compact_log();
+    }
+
+    fn apply(&mut self, entry: &LogEntry) -> Option<NodeData> {
+        if let Some((seq, data)) = self.client_requests.get(&entry.client_id) {
+            // A retried request is answered with the result of its first application
+            if *seq == entry.seq {
+                return Some(data.clone());
+            }
+            if *seq > entry.seq {
+                return None;
+            }
+        }
+
+        let data = match &entry.command {
+            RaftCommand::Write(key, data) => {
+                let version = self.register(key).version() + 1;
+                let data = NodeData::new(data.clone(), version);
+                self.registers.insert(key.clone(), data.clone());
+                data
+            },
+            RaftCommand::Read(key) => self.register(key),
+        };
+
+        self.client_requests.insert(entry.client_id.clone(), (entry.seq, data.clone()));
+        Some(data)
+    }
+
+    fn compact_log(&mut self) {
+        if self.log.len() <= SNAPSHOT_THRESHOLD || self.last_applied <= self.snapshot.last_included_index {
+            return;
+        }
+
+        let last_included_term = self.term_at(self.last_applied).unwrap();
+        let position = self.log_position(self.last_applied) + 1;
+        self.log.drain(..position);
+
+        self.snapshot = Snapshot {
+            last_included_index: self.last_applied,
+            last_included_term,
+            registers: self.registers.clone(),
+            client_requests: self.client_requests.clone(),
+        };
+    }
+
+    fn register(&self, key: &str) -> NodeData {
+        match self.registers.get(key) {
+            Some(data) => data.clone(),
+            None => NodeData::new("".to_string(), 0),
+        }
+    }
+
+    fn send_to_node(&self, node_id: &NodeId, message: Message) {
+        self.network.send_to_node_from(&Endpoint::Node(self.id.clone()), node_id, message);
+    }
+
+    fn trace(&self, event: TraceEvent) {
+        self.network.trace(event.node(&self.id));
+    }
+
+    fn majority(&self) -> usize {
+        let cluster_size = self.peers.len() + 1;
+        cluster_size / 2 + 1
+    }
+
+    fn last_log_index(&self) -> u64 {
+        self.snapshot.last_included_index + self.log.len() as u64
+    }
+
+    fn last_log_term(&self) -> u64 {
+        match self.log.last() {
+            Some(entry) => entry.term,
+            None => self.snapshot.last_included_term,
+        }
+    }
+
+    // Index must be greater than the snapshot index
+    fn log_position(&self, index: u64) -> usize {
+        (index - self.snapshot.last_included_index - 1) as usize
+    }
+
+    fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.last_included_index {
+            return Some(self.snapshot.last_included_term);
+        }
+        if index < self.snapshot.last_included_index || index > self.last_log_index() {
+            return None;
+        }
+
+        Some(self.log[self.log_position(index)].term)
+    }
+}
+
+fn random_election_timeout() -> Duration {
+    let mut rng = rand::thread_rng();
+    Duration::from_millis(rng.gen_range(ELECTION_TIMEOUT_MIN_MS..=ELECTION_TIMEOUT_MAX_MS))
+}
@@ -0,0 +1,102 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use crate::{atomic_register_client::ClientId, network::Network, node::{Message, NodeData, NodeId}, raft::{RaftCommand, RaftMessage}, replay::Endpoint, store::Key, trace::TraceEvent};
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct RaftClient {
+    id: ClientId,
+    network: Arc<Network>,
+    nodes: Vec<NodeId>,
+    leader: NodeId,
+    key: Key,
+    // Sequence number of the last request, retries reuse it
+    seq: u64,
+}
+
+impl RaftClient {
+    pub fn new(id: ClientId, network: Arc<Network>) -> RaftClient {
+        let nodes = network.node_ids();
+        let leader = nodes[0].clone();
+
+        RaftClient {
+            id,
+            network,
+            nodes,
+            leader,
+            key: "".to_string(),
+            seq: 0,
+        }
+    }
+
+    pub fn with_key(&self, key: &str) -> RaftClient {
+        RaftClient {
+            id: self.id.clone(),
+            network: Arc::clone(&self.network),
+            nodes: self.nodes.clone(),
+            leader: self.leader.clone(),
+            key: key.to_string(),
+            seq: self.seq,
+        }
+    }
+
+    pub fn write(&mut self, data: String) -> NodeData {
+        let node_data = self.request(RaftCommand::Write(self.key.clone(), data));
+        self.network.trace(TraceEvent::new("client.raft_write").client(&self.id).message_type("ClientResponse").detail(format!("version {}", node_data.version())));
+        node_data
+    }
+
+    pub fn read(&mut self) -> NodeData {
+        let node_data = self.request(RaftCommand::Read(self.key.clone()));
+        self.network.trace(TraceEvent::new("client.raft_read").client(&self.id).message_type("ClientResponse").detail(format!("{:?}", node_data)));
+        node_data
+    }
+
+    fn request(&mut self, command: RaftCommand) -> NodeData {
+        self.seq += 1;
+
+        loop {
+            let message = Message::Raft(RaftMessage::ClientRequest { client_id: self.id.clone(), seq: self.seq, command: command.clone() });
+            self.network.send_to_node_from(&Endpoint::Client(self.id.clone()), &self.leader, message);
+
+            if let Some(node_data) = self.wait_response() {
+                return node_data;
+            }
+        }
+    }
+
+    // Returns the answer to the current request or None when the request has to be retried
+    fn wait_response(&mut self) -> Option<NodeData> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+
+        loop {
+            match self.network.get_timeout(&self.id, deadline.saturating_duration_since(Instant::now())) {
+                Some(Message::Raft(RaftMessage::ClientResponse { seq, data })) => {
+                    // Late answers to an earlier attempt of a finished request are ignored
+                    if seq == self.seq {
+                        return Some(data);
+                    }
+                },
+                Some(Message::Raft(RaftMessage::Redirect(Some(leader)))) => {
+                    self.leader = leader;
+                    return None;
+                },
+                Some(Message::Raft(RaftMessage::Redirect(None))) => {
+                    // Election in progress
+                    std::thread::sleep(Duration::from_millis(50));
+                    return None;
+                },
+                Some(_) => {},
+                None => {
+                    self.try_next_node();
+                    return None;
+                },
+            }
+        }
+    }
+
+    fn try_next_node(&mut self) {
+        let position = self.nodes.iter().position(|node_id| *node_id == self.leader).unwrap_or(0);
+        self.leader = self.nodes[(position + 1) % self.nodes.len()].clone();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+use std::{fs::File, io::{self, BufRead, BufReader, BufWriter, Write}, path::Path, sync::Arc, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{atomic_register_client::ClientId, network::Network, node::{now_millis, Message, NodeId}};
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Endpoint {
+    Node(NodeId),
+    Client(ClientId),
+}
+
+// A message the network handed to its receiver. The sender is None for messages sent without one,
+// like Network::send_to_node from outside a node or client.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    // Microseconds since the recording started
+    pub at: u64,
+    // Milliseconds since the Unix epoch, the time expiring values in the message are judged at
+    #[serde(default)]
+    pub wall_clock: u64,
+    pub from: Option<Endpoint>,
+    pub to: Endpoint,
+    pub message: Message,
+}
+
+// Writes deliveries as JSON lines, flushed one by one so the file survives a panicking run
+pub struct Recording {
+    started: Instant,
+    writer: BufWriter<File>,
+}
+
+impl Recording {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Recording> {
+        Ok(Recording {
+            started: Instant::now(),
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn record(&mut self, from: Option<Endpoint>, to: Endpoint, message: &Message) {
+        let delivery = Delivery {
+            at: self.started.elapsed().as_micros() as u64,
+            wall_clock: now_millis(),
+            from,
+            to,
+            message: message.clone(),
+        };
+        serde_json::to_writer(&mut self.writer, &delivery).unwrap();
+        self.writer.write_all(b"\n").unwrap();
+        self.writer.flush().unwrap();
+    }
+}
+
+pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<Delivery>> {
+    let mut deliveries = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        deliveries.push(serde_json::from_str(&line).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?);
+    }
+    Ok(deliveries)
+}
+
+// Feeds recorded deliveries to the nodes of a network in the recorded order. While it replays, the network
+// drops every message the nodes send themselves and the timers of the nodes are off, so the nodes only see
+// what they saw in the recorded run. Deliveries to clients are returned by step but not fed anywhere,
+// a replay has no clients.
+pub struct Replay {
+    network: Arc<Network>,
+    deliveries: Vec<Delivery>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn new(network: Arc<Network>, deliveries: Vec<Delivery>) -> Replay {
+        network.set_replaying(true);
+        Replay { network, deliveries, next: 0 }
+    }
+
+    pub fn from_file(network: Arc<Network>, path: impl AsRef<Path>) -> io::Result<Replay> {
+        Ok(Replay::new(network, load(path)?))
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.deliveries.len() - self.next
+    }
+
+    // Delivers the next message and returns once the node handled it, that is when its handlers are done
+    // or wait for a later delivery. None once the recording is done.
+    pub fn step(&mut self) -> Option<&Delivery> {
+        let delivery = self.deliveries.get(self.next)?;
+        self.next += 1;
+
+        if let Endpoint::Node(node_id) = &delivery.to {
+            self.network.inject(node_id, delivery.message.clone());
+            while !self.network.is_idle(node_id) {
+                std::thread::yield_now();
+            }
+        }
+        Some(delivery)
+    }
+
+    pub fn run(&mut self) {
+        while self.step().is_some() {}
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.network.set_replaying(false);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use crate::{merkle::Fnv, node::NodeId};
+
+#[derive(Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct GroupId(pub i32);
+
+// Nodes running one atomic register each for the keys of their part of the ring,
+// the first member coordinates the group
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplicaGroup {
+    pub id: GroupId,
+    pub members: Vec<NodeId>,
+}
+
+impl ReplicaGroup {
+    pub fn new(id: GroupId, members: Vec<NodeId>) -> ReplicaGroup {
+        ReplicaGroup { id, members }
+    }
+
+    pub fn coordinator(&self) -> &NodeId {
+        &self.members[0]
+    }
+}
+
+// Every group owns virtual_nodes points on the ring, a key belongs to the group of the first point after its hash.
+// Adding a group only moves the keys that land on its new points.
+#[derive(Clone, Debug)]
+pub struct HashRing {
+    virtual_nodes: usize,
+    points: BTreeMap<u64, GroupId>,
+    groups: BTreeMap<GroupId, ReplicaGroup>,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: usize) -> HashRing {
+        HashRing {
+            virtual_nodes,
+            points: BTreeMap::new(),
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_group(&mut self, group: ReplicaGroup) {
+        for i in 0..self.virtual_nodes {
+            self.points.insert(point_of(&group.id, i), group.id.clone());
+        }
+        self.groups.insert(group.id.clone(), group);
+    }
+
+    pub fn group_of(&self, key: &str) -> &ReplicaGroup {
+        let hash = spread(Fnv::new().write_field(key.as_bytes()).finish());
+        let (_, group_id) = self.points.range(hash..).next()
+            .or_else(|| self.points.iter().next())
+            .expect("Ring without groups");
+
+        &self.groups[group_id]
+    }
+
+    pub fn groups(&self) -> Vec<ReplicaGroup> {
+        self.groups.values().cloned().collect()
+    }
+}
+
+// Stable hashes, so every client and every build puts keys and groups on the same points
+fn point_of(group_id: &GroupId, virtual_node: usize) -> u64 {
+    spread(Fnv::new().write(&group_id.0.to_le_bytes()).write(&(virtual_node as u64).to_le_bytes()).finish())
+}
+
+// FNV-1a of short inputs differs little in the high bits that order the ring,
+// the MurmurHash3 finalizer spreads every input bit over all of them
+fn spread(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
\ No newline at end of file
@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use crate::{node::{NodeData, Timestamp}, store::Key};
+
+// Newest timestamp a client has written or read of every key. Reads in the session ask the coordinator
+// for at least that timestamp, which keeps them from going back to older values whichever node coordinates.
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    timestamps: HashMap<Key, Timestamp>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { timestamps: HashMap::new() }
+    }
+
+    // A deleted key keeps the timestamp of its tombstone also after it is collected, so a read in the session
+    // never goes back to a value from before the delete
+    pub fn min_timestamp(&self, key: &str) -> Timestamp {
+        self.timestamps.get(key).cloned().unwrap_or_default()
+    }
+
+    pub fn observe(&mut self, key: &str, data: &NodeData) {
+        self.written(key, data.timestamp());
+    }
+
+    pub fn written(&mut self, key: &str, timestamp: Timestamp) {
+        let newest = self.timestamps.entry(key.to_string()).or_default();
+        if timestamp > *newest {
+            *newest = timestamp;
+        }
+    }
+}
\ No newline at end of file
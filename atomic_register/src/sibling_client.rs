@@ -0,0 +1,62 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use crate::{atomic_register_client::{ClientId, RequestError}, crdt::{CrdtValue, VersionVector}, metrics::CLIENT_OPERATION_SECONDS, network::Network, node::Message, replay::Endpoint, store::Key, trace::TraceEvent};
+
+// Reads and writes give up when the coordinator does not answer this long
+const SIBLING_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Client of the quorum registers that keep concurrent writes as siblings.
+// read returns every sibling with their context, write(resolved, context) replaces the siblings of that context.
+pub struct SiblingClient {
+    id: ClientId,
+    network: Arc<Network>,
+    key: Key,
+}
+
+impl SiblingClient {
+    pub fn new(id: ClientId, network: Arc<Network>) -> SiblingClient {
+        SiblingClient {
+            id,
+            network,
+            key: "".to_string(),
+        }
+    }
+
+    pub fn with_key(&self, key: &str) -> SiblingClient {
+        SiblingClient {
+            id: self.id.clone(),
+            network: Arc::clone(&self.network),
+            key: key.to_string(),
+        }
+    }
+
+    pub fn write(&self, resolved: String, context: &VersionVector) -> Result<(), RequestError> {
+        let started = Instant::now();
+        self.network.send_to_node_from(&Endpoint::Client(self.id.clone()), self.network.coordinator_id(), Message::SiblingWriteRequest((self.id.clone(), self.key.clone(), resolved, context.clone())));
+
+        let deadline = started + SIBLING_TIMEOUT;
+        while let Some(message) = self.network.get_timeout(&self.id, deadline.saturating_duration_since(Instant::now())) {
+            if let Message::WriteAck((node_id, _, _)) = message {
+                self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "sibling_write")], started.elapsed());
+                self.network.trace(TraceEvent::new("client.write_ack").client(&self.id).message_type("WriteAck").detail(format!("from node {:?}", node_id)));
+                return Ok(());
+            }
+        }
+        Err(RequestError::Timeout)
+    }
+
+    pub fn read(&self) -> Result<CrdtValue, RequestError> {
+        let started = Instant::now();
+        self.network.send_to_node_from(&Endpoint::Client(self.id.clone()), self.network.coordinator_id(), Message::SiblingReadRequest((self.id.clone(), self.key.clone())));
+
+        let deadline = started + SIBLING_TIMEOUT;
+        while let Some(message) = self.network.get_timeout(&self.id, deadline.saturating_duration_since(Instant::now())) {
+            if let Message::SiblingReadResponse(value) = message {
+                self.network.metrics().observe(CLIENT_OPERATION_SECONDS, &[("operation", "sibling_read")], started.elapsed());
+                self.network.trace(TraceEvent::new("client.sibling_read").client(&self.id).message_type("SiblingReadResponse").detail(format!("{:?}", value.values)));
+                return Ok(value);
+            }
+        }
+        Err(RequestError::Timeout)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+
+use crate::{crdt::{CrdtRegister, MvRegister}, merkle::MerkleTree, node::{NodeData, Timestamp}};
+
+pub type Key = String;
+// Deleted key with the timestamp of its tombstone
+pub type Tombstone = (Key, Timestamp);
+
+// Everything a replica keeps of a set of keys, handed over on reconfiguration and when keys move to another group
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicaState {
+    pub registers: BTreeMap<Key, NodeData>,
+    pub siblings: BTreeMap<Key, MvRegister>,
+    pub crdts: BTreeMap<Key, CrdtRegister>,
+}
+
+impl ReplicaState {
+    // Newest register versions win, siblings and CRDT registers are merged
+    pub fn merge(&mut self, other: ReplicaState) {
+        for (key, data) in other.registers {
+            let newer = match self.registers.get(&key) {
+                Some(newest) => data.timestamp() > newest.timestamp(),
+                None => true,
+            };
+            if newer {
+                self.registers.insert(key, data);
+            }
+        }
+        for (key, register) in other.siblings {
+            self.siblings.entry(key).or_default().merge(&register);
+        }
+        for (key, register) in other.crdts {
+            match self.crdts.get_mut(&key) {
+                Some(own) => own.merge(&register),
+                None => {
+                    self.crdts.insert(key, register);
+                },
+            }
+        }
+    }
+
+    // Number of distinct keys
+    pub fn len(&self) -> usize {
+        self.registers.keys().chain(self.siblings.keys()).chain(self.crdts.keys()).collect::<BTreeSet<_>>().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.siblings.is_empty() && self.crdts.is_empty()
+    }
+}
+
+// Registers of one node, the Merkle tree is kept up to date on every store
+pub struct RegisterStore {
+    registers: BTreeMap<Key, NodeData>,
+    leaves: HashMap<usize, BTreeSet<Key>>,
+    tree: MerkleTree,
+    // Keys handed to another replica group, no version of them is stored again
+    moved: BTreeSet<Key>,
+    // Timestamps of collected tombstones, later writes of the key must go above them.
+    // They stay in the Merkle tree and the entries, so replicas that missed the delete still get it.
+    floors: BTreeMap<Key, Timestamp>,
+}
+
+impl RegisterStore {
+    pub fn new() -> RegisterStore {
+        RegisterStore {
+            registers: BTreeMap::new(),
+            leaves: HashMap::new(),
+            tree: MerkleTree::new(),
+            moved: BTreeSet::new(),
+            floors: BTreeMap::new(),
+        }
+    }
+
+    // Registers that were never written read as the empty value with version 0,
+    // collected tombstones still read as deleted at their version
+    pub fn get(&self, key: &str) -> NodeData {
+        match (self.registers.get(key), self.floors.get(key)) {
+            (Some(data), _) => data.clone(),
+            (None, Some(floor)) => floor_data(floor),
+            (None, None) => NodeData::new("".to_string(), 0),
+        }
+    }
+
+    pub fn store_if_newer(&mut self, key: &str, data: NodeData) -> bool {
+        if self.moved.contains(key) {
+            return false;
+        }
+        let old = self.registers.get(key).cloned().or_else(|| self.floors.get(key).map(floor_data));
+        if old.as_ref().map(|old| data.timestamp() <= old.timestamp()).unwrap_or(data.version() == 0) {
+            return false;
+        }
+        self.floors.remove(key);
+
+        self.tree.update(key, old.as_ref(), &data);
+        self.leaves.entry(MerkleTree::leaf_of(key)).or_default().insert(key.to_string());
+        self.registers.insert(key.to_string(), data);
+        true
+    }
+
+    // Stores all entries or none. None when a key got a value newer than its validated timestamp,
+    // so a transaction never commits part of its writes over a concurrent write.
+    pub fn store_batch(&mut self, entries: &[(Key, NodeData)], validated: &BTreeMap<Key, Timestamp>) -> bool {
+        if validated.iter().any(|(key, timestamp)| self.is_moved(key) || self.get(key).timestamp() > *timestamp) {
+            return false;
+        }
+        for (key, data) in entries {
+            self.store_if_newer(key, data.clone());
+        }
+        true
+    }
+
+    // Only the data goes, the version stays so later version checks are unchanged
+    pub fn discard_expired(&mut self, now_millis: u64) -> usize {
+        let mut discarded = 0;
+
+        for (key, data) in self.registers.iter_mut() {
+            if data.is_expired(now_millis) && !data.data().is_empty() {
+                let old = data.clone();
+                data.discard_data();
+                self.tree.update(key, Some(&old), data);
+                discarded += 1;
+            }
+        }
+
+        discarded
+    }
+
+    pub fn tombstones(&self) -> Vec<Tombstone> {
+        self.registers.iter()
+            .filter(|(_, data)| data.is_tombstone())
+            .map(|(key, data)| (key.clone(), data.timestamp()))
+            .collect()
+    }
+
+    // Only the given tombstone goes, a newer write of the key stays. Its timestamp is kept as the floor of the key,
+    // the floor hashes like the tombstone so the Merkle tree does not change.
+    pub fn remove_tombstone(&mut self, key: &str, timestamp: &Timestamp) -> bool {
+        match self.registers.get(key) {
+            Some(data) if *data == floor_data(timestamp) => {},
+            _ => return false,
+        }
+
+        self.registers.remove(key);
+        self.floors.insert(key.to_string(), timestamp.clone());
+        true
+    }
+
+    // The register stays readable until remove_moved, it only takes no more writes
+    pub fn mark_moved(&mut self, key: &str) {
+        self.moved.insert(key.to_string());
+    }
+
+    pub fn remove_moved(&mut self, key: &str) {
+        self.mark_moved(key);
+        self.remove(key);
+        if let Some(floor) = self.floors.remove(key) {
+            self.tree.remove(key, &floor_data(&floor));
+            self.remove_from_leaf(key);
+        }
+    }
+
+    pub fn is_moved(&self, key: &str) -> bool {
+        self.moved.contains(key)
+    }
+
+    // Registers and floors, a floor goes as the tombstone it was collected from
+    pub fn entries(&self) -> Vec<(Key, NodeData)> {
+        let mut entries = self.registers();
+        entries.extend(self.floors.iter().map(|(key, floor)| (key.clone(), floor_data(floor))));
+        entries
+    }
+
+    pub fn registers(&self) -> Vec<(Key, NodeData)> {
+        self.registers.iter().map(|(key, data)| (key.clone(), data.clone())).collect()
+    }
+
+    pub fn first_registers(&self, limit: usize) -> Vec<(Key, NodeData)> {
+        self.registers.iter().take(limit).map(|(key, data)| (key.clone(), data.clone())).collect()
+    }
+
+    pub fn floors(&self) -> Vec<Tombstone> {
+        self.floors.iter().map(|(key, floor)| (key.clone(), floor.clone())).collect()
+    }
+
+    pub fn keys(&self) -> Vec<Key> {
+        self.registers.keys().chain(self.floors.keys()).cloned().collect()
+    }
+
+    pub fn entries_in_leaves(&self, leaves: &[usize]) -> Vec<(Key, NodeData)> {
+        let mut entries = Vec::new();
+
+        for leaf in leaves {
+            if let Some(keys) = self.leaves.get(leaf) {
+                for key in keys {
+                    entries.push((key.clone(), self.get(key)));
+                }
+            }
+        }
+
+        entries
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.registers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
+
+    fn remove(&mut self, key: &str) {
+        let data = match self.registers.remove(key) {
+            Some(data) => data,
+            None => return,
+        };
+
+        self.tree.remove(key, &data);
+        self.remove_from_leaf(key);
+    }
+
+    fn remove_from_leaf(&mut self, key: &str) {
+        let leaf = MerkleTree::leaf_of(key);
+        if let Some(keys) = self.leaves.get_mut(&leaf) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.leaves.remove(&leaf);
+            }
+        }
+    }
+}
+
+// The tombstone a floor was collected from
+fn floor_data(floor: &Timestamp) -> NodeData {
+    let data = NodeData::tombstone(floor.version);
+    match &floor.writer {
+        Some(writer) => data.with_writer(writer),
+        None => data,
+    }
+}
+
+impl Default for RegisterStore {
+    fn default() -> RegisterStore {
+        RegisterStore::new()
+    }
+}
\ No newline at end of file
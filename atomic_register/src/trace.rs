@@ -0,0 +1,135 @@
+use std::{fs::File, io::{self, BufWriter, Write}, path::Path, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::Serialize;
+
+use crate::{atomic_register_client::ClientId, node::NodeId};
+
+// One step of a node or client. Phases are named <area>.<step>, like write.commit or replica.write.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TraceEvent {
+    // Microseconds since the Unix epoch
+    pub timestamp: u64,
+    pub node: Option<NodeId>,
+    pub client: Option<ClientId>,
+    // Id of the client operation on its coordinator
+    pub operation: Option<u64>,
+    pub phase: String,
+    pub message_type: Option<String>,
+    pub detail: String,
+}
+
+impl TraceEvent {
+    pub fn new(phase: &str) -> TraceEvent {
+        TraceEvent {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64,
+            node: None,
+            client: None,
+            operation: None,
+            phase: phase.to_string(),
+            message_type: None,
+            detail: "".to_string(),
+        }
+    }
+
+    pub fn node(self, node_id: &NodeId) -> TraceEvent {
+        TraceEvent { node: Some(node_id.clone()), ..self }
+    }
+
+    pub fn client(self, client_id: &ClientId) -> TraceEvent {
+        TraceEvent { client: Some(client_id.clone()), ..self }
+    }
+
+    pub fn operation(self, operation: u64) -> TraceEvent {
+        TraceEvent { operation: Some(operation), ..self }
+    }
+
+    pub fn message_type(self, message_type: &str) -> TraceEvent {
+        TraceEvent { message_type: Some(message_type.to_string()), ..self }
+    }
+
+    pub fn detail(self, detail: String) -> TraceEvent {
+        TraceEvent { detail, ..self }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+pub trait TraceSink: Send + Sync {
+    fn emit(&self, event: &TraceEvent);
+}
+
+// Default sink, one readable line per event
+pub struct StdoutSink;
+
+impl TraceSink for StdoutSink {
+    fn emit(&self, event: &TraceEvent) {
+        let mut line = String::new();
+        if let Some(node_id) = &event.node {
+            line += &format!("[node {}] ", node_id.0);
+        }
+        if let Some(client_id) = &event.client {
+            line += &format!("[client {}] ", client_id.0);
+        }
+        line += &event.phase;
+        if let Some(operation) = event.operation {
+            line += &format!(" op {}", operation);
+        }
+        if let Some(message_type) = &event.message_type {
+            line += &format!(" {}", message_type);
+        }
+        if !event.detail.is_empty() {
+            line += &format!(": {}", event.detail);
+        }
+
+        println!("{}", line);
+    }
+}
+
+pub struct NoopSink;
+
+impl TraceSink for NoopSink {
+    fn emit(&self, _event: &TraceEvent) {}
+}
+
+// One JSON object per line, flushed per event so a crashed run still leaves a readable file
+pub struct JsonLinesSink {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl JsonLinesSink {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<JsonLinesSink> {
+        Ok(JsonLinesSink {
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+}
+
+impl TraceSink for JsonLinesSink {
+    fn emit(&self, event: &TraceEvent) {
+        let mut writer = self.writer.lock().unwrap();
+        // Tracing never takes the node down, the event is dropped
+        if let Err(error) = writeln!(writer, "{}", event.to_json()).and_then(|_| writer.flush()) {
+            eprintln!("trace event {} dropped: {}", event.phase, error);
+        }
+    }
+}
+
+// Keeps the events in memory, for tests
+#[derive(Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+impl MemorySink {
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.lock().unwrap().clone()
+    }
+}
+
+impl TraceSink for MemorySink {
+    fn emit(&self, event: &TraceEvent) {
+        self.events.lock().unwrap().push(event.clone());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use crate::{atomic_register_client::AtomicRegisterClinent, node::{NodeData, Timestamp}, store::Key};
+
+// Registers read with the timestamps seen, and the buffered writes
+pub type ReadSet = Vec<(Key, Timestamp)>;
+pub type WriteSet = Vec<(Key, String)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    // Another write changed a read register, nothing was written
+    Conflict,
+    // The keys belong to several replica groups, nothing was sent
+    CrossGroup,
+    // The coordinator did not answer in time, the transaction may still commit
+    Timeout,
+}
+
+// Optimistic transaction: reads go to the registers right away and remember their timestamps,
+// writes are buffered. The coordinator commits the writes only if no read register changed since.
+// All keys have to belong to the same replica group.
+pub struct Transaction<'a> {
+    client: &'a AtomicRegisterClinent,
+    reads: BTreeMap<Key, Timestamp>,
+    writes: BTreeMap<Key, String>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn new(client: &'a AtomicRegisterClinent) -> Transaction<'a> {
+        Transaction {
+            client,
+            reads: BTreeMap::new(),
+            writes: BTreeMap::new(),
+        }
+    }
+
+    // Sees the buffered writes of the transaction itself
+    pub fn read(&mut self, key: &str) -> String {
+        if let Some(data) = self.writes.get(key) {
+            return data.clone();
+        }
+
+        let data: NodeData = self.client.with_key(key).read();
+        self.reads.entry(key.to_string()).or_insert(data.timestamp());
+        data.value().unwrap_or("").to_string()
+    }
+
+    pub fn write(&mut self, key: &str, data: String) {
+        self.writes.insert(key.to_string(), data);
+    }
+
+    pub fn commit(self) -> Result<(), TransactionError> {
+        self.client.commit(self.reads.into_iter().collect(), self.writes.into_iter().collect())
+    }
+}
\ No newline at end of file
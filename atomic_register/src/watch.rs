@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{atomic_register_client::ClientId, network::Network, node::{Message, NodeData, NodeId, Timestamp}, replay::Endpoint, store::Key};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchEvent {
+    pub key: Key,
+    pub data: NodeData,
+}
+
+// Committed values of one register, newest last. The coordinator only keeps the current value,
+// so a watch resumed from an old timestamp gets the current value and not every version in between.
+pub struct Watch {
+    id: ClientId,
+    network: Arc<Network>,
+    coordinator: NodeId,
+    key: Key,
+    last_timestamp: Timestamp,
+}
+
+impl Watch {
+    pub fn new(id: ClientId, network: Arc<Network>, coordinator: NodeId, key: Key, from: Timestamp) -> Watch {
+        network.send_to_node_from(&Endpoint::Client(id.clone()), &coordinator, Message::Watch((id.clone(), key.clone(), from.clone())));
+
+        Watch {
+            id,
+            network,
+            coordinator,
+            key,
+            last_timestamp: from,
+        }
+    }
+
+    // Timestamp to resume from after reconnecting
+    pub fn last_timestamp(&self) -> &Timestamp {
+        &self.last_timestamp
+    }
+
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<WatchEvent> {
+        loop {
+            match self.network.get_timeout(&self.id, timeout) {
+                Some(message) => {
+                    if let Some(event) = self.event_of(message) {
+                        return Some(event);
+                    }
+                },
+                None => return None,
+            }
+        }
+    }
+
+    // Notifications can be sent twice when a write races with the subscription.
+    // Two writers can commit the same version, so the whole timestamp decides what is new.
+    fn event_of(&mut self, message: Message) -> Option<WatchEvent> {
+        match message {
+            Message::Notify((key, data)) if key == self.key && data.timestamp() > self.last_timestamp => {
+                self.last_timestamp = data.timestamp();
+                Some(WatchEvent { key, data })
+            },
+            _ => None,
+        }
+    }
+}
+
+impl Iterator for Watch {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<WatchEvent> {
+        loop {
+            let message = self.network.get(&self.id)?;
+            if let Some(event) = self.event_of(message) {
+                return Some(event);
+            }
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.network.send_to_node_from(&Endpoint::Client(self.id.clone()), &self.coordinator, Message::Unwatch((self.id.clone(), self.key.clone())));
+    }
+}
\ No newline at end of file
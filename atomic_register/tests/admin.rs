@@ -0,0 +1,72 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{admin::STATUS_REGISTERS, atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId, NodeState}, quorum::QuorumState, trace::NoopSink};
+
+fn start_cluster(client_count: usize) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(3, client_count));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    (network, nodes)
+}
+
+#[test]
+fn status_shows_state_operations_and_peers() {
+    let (network, nodes) = start_cluster(2);
+    let admin = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    admin.with_key("cart").write("apple".to_string());
+
+    let status = admin.status(&NodeId(0)).expect("node 0 does not answer");
+    assert_eq!(status.node_state, NodeState::Working);
+    assert_eq!(status.quorum_state, QuorumState::WaitingForRequest);
+    assert_eq!(status.configuration.members, network.node_ids());
+    assert!(status.in_flight.is_empty());
+    assert_eq!(status.peers, vec![(NodeId(1), true), (NodeId(2), true)]);
+    assert_eq!(status.register_count, 1);
+    assert_eq!(status.registers, vec![("cart".to_string(), nodes[0].stored("cart"))]);
+
+    // Without its peers the write of client 1 waits for phase 1 answers
+    network.set_reachable(&NodeId(1), false);
+    network.set_reachable(&NodeId(2), false);
+    let writer = {
+        let client = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_key("cart");
+        std::thread::spawn(move || client.write("pear".to_string()))
+    };
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        let status = admin.status(&NodeId(0)).expect("node 0 does not answer");
+        if !status.in_flight.is_empty() {
+            break status;
+        }
+        assert!(Instant::now() < deadline, "write never in flight");
+    };
+    assert_eq!(status.in_flight, vec!["write \"cart\" of client ClientId(1)".to_string()]);
+    assert_eq!(status.quorum_state, QuorumState::WaitingForReadResponse(1));
+    assert_eq!(status.peers, vec![(NodeId(1), false), (NodeId(2), false)]);
+    assert_eq!(admin.status(&NodeId(2)), None);
+
+    network.set_reachable(&NodeId(1), true);
+    network.set_reachable(&NodeId(2), true);
+    writer.join().unwrap();
+    assert!(admin.status(&NodeId(0)).unwrap().in_flight.is_empty());
+}
+
+#[test]
+fn status_carries_a_limited_number_of_registers() {
+    let (network, _) = start_cluster(1);
+    let admin = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    for i in 0..STATUS_REGISTERS + 5 {
+        admin.with_key(&format!("key-{:03}", i)).write(i.to_string());
+    }
+
+    let status = admin.status(&NodeId(0)).unwrap();
+    assert_eq!(status.register_count, STATUS_REGISTERS + 5);
+    assert_eq!(status.registers.len(), STATUS_REGISTERS);
+    assert_eq!(status.registers[0].0, "key-000");
+}
\ No newline at end of file
@@ -0,0 +1,138 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{anti_entropy::{AntiEntropy, AntiEntropyConfig}, atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId}, trace::NoopSink};
+
+const CONVERGENCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn config(max_bytes_per_interval: usize) -> AntiEntropyConfig {
+    AntiEntropyConfig { interval: Duration::from_millis(20), max_bytes_per_interval }
+}
+
+fn start_cluster(node_count: usize) -> (Arc<Network>, Vec<Node>) {
+    start_cluster_with(node_count, |_| Some(config(16 * 1024)))
+}
+
+fn start_cluster_with(node_count: usize, config_of: impl Fn(&NodeId) -> Option<AntiEntropyConfig>) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(node_count, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    let mut nodes = Vec::new();
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id.clone(), node_count / 2 + 1, Arc::clone(&network));
+        if let Some(config) = config_of(&node_id) {
+            node = node.with_anti_entropy(config);
+        }
+        nodes.push(node.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    (network, nodes)
+}
+
+fn wait_until_converged(nodes: &[Node], keys: &[String]) {
+    let deadline = Instant::now() + CONVERGENCE_TIMEOUT;
+    loop {
+        let diverged: Vec<_> = keys.iter().filter(|key| nodes.iter().any(|node| node.stored(key) != nodes[0].stored(key))).collect();
+        if diverged.is_empty() {
+            return;
+        }
+        assert!(Instant::now() < deadline, "replicas still differ on {:?}", diverged);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn replicas_that_missed_writes_converge() {
+    let (network, nodes) = start_cluster(5);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let keys: Vec<String> = (0..20).map(|i| format!("key-{}", i)).collect();
+
+    // A minority misses every write, the quorum still acks them
+    network.set_reachable(&NodeId(3), false);
+    network.set_reachable(&NodeId(4), false);
+    for (i, key) in keys.iter().enumerate() {
+        client.with_key(key).write(format!("Data {}", i));
+    }
+    network.set_reachable(&NodeId(3), true);
+    network.set_reachable(&NodeId(4), true);
+
+    // A newer write that only reached one of the lagging replicas
+    let data = NodeData::new("Data from node 4".to_string(), 2);
+    network.send_to_node(&NodeId(4), Message::CoordinatorWriteRequest((NodeId(4), keys[0].clone(), data.clone(), 0, 0)));
+
+    wait_until_converged(&nodes, &keys);
+    assert_eq!(nodes[0].stored(&keys[0]).data(), data.data());
+    for (i, key) in keys.iter().enumerate().skip(1) {
+        assert_eq!(nodes[3].stored(key).data(), format!("Data {}", i));
+    }
+}
+
+#[test]
+fn writes_of_the_same_version_converge_to_the_higher_writer() {
+    let (network, nodes) = start_cluster(3);
+    let key = "cart".to_string();
+
+    // Two coordinators picked version 1, each write reached other replicas
+    let first = NodeData::new("Data from node 0".to_string(), 1).with_writer(&NodeId(0));
+    let second = NodeData::new("Data from node 1".to_string(), 1).with_writer(&NodeId(1));
+    network.send_to_node(&NodeId(0), Message::CoordinatorWriteRequest((NodeId(1), key.clone(), second.clone(), 0, 0)));
+    for node_id in [NodeId(1), NodeId(2)] {
+        network.send_to_node(&node_id, Message::CoordinatorWriteRequest((NodeId(0), key.clone(), first.clone(), 0, 0)));
+    }
+
+    let deadline = Instant::now() + CONVERGENCE_TIMEOUT;
+    while nodes.iter().any(|node| node.stored(&key) != second) {
+        assert!(Instant::now() < deadline, "replicas still differ: {:?}", nodes.iter().map(|node| node.stored(&key)).collect::<Vec<_>>());
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// The lagging replica misses every write while the others ack them
+fn write_past(network: &Arc<Network>, lagging: &NodeId, keys: &[String], data: &str) {
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(network));
+    network.set_reachable(lagging, false);
+    for key in keys {
+        client.with_key(key).write(data.to_string());
+    }
+    network.set_reachable(lagging, true);
+}
+
+#[test]
+fn leaves_larger_than_the_cap_still_converge() {
+    let (network, nodes) = start_cluster_with(3, |_| Some(config(64)));
+    let keys: Vec<String> = (0..5).map(|i| format!("key-{}", i)).collect();
+
+    write_past(&network, &NodeId(2), &keys, &"x".repeat(1000));
+    wait_until_converged(&nodes, &keys);
+}
+
+#[test]
+fn nodes_without_config_answer_without_a_cap() {
+    // Only node 0 gossips, the lagging node has no config of its own
+    let (network, nodes) = start_cluster_with(2, |node_id| if *node_id == NodeId(0) { Some(config(16 * 1024)) } else { None });
+    let keys = vec!["cart".to_string()];
+
+    // A write only the node without config has
+    network.send_to_node(&NodeId(1), Message::CoordinatorWriteRequest((NodeId(1), keys[0].clone(), NodeData::new("Data from node 1".to_string(), 1), 0, 0)));
+    while nodes[1].stored(&keys[0]).version() == 0 {
+        std::thread::yield_now();
+    }
+    wait_until_converged(&nodes, &keys);
+    assert_eq!(nodes[0].stored(&keys[0]).data(), "Data from node 1");
+}
+
+#[test]
+fn chunks_over_the_cap_wait_for_the_next_interval() {
+    let mut anti_entropy = AntiEntropy::new(config(100));
+    let now = Instant::now();
+
+    assert_eq!(anti_entropy.reserve_prefix(&[40, 40, 40], now), 2);
+    assert_eq!(anti_entropy.reserve_prefix(&[10], now), 1);
+    assert_eq!(anti_entropy.reserve_prefix(&[40], now), 0);
+
+    // A chunk larger than the cap goes alone at the start of an interval
+    let next = now + Duration::from_millis(20);
+    assert_eq!(anti_entropy.reserve_prefix(&[500, 10], next), 1);
+    assert_eq!(anti_entropy.reserve_prefix(&[10], next), 0);
+}
\ No newline at end of file
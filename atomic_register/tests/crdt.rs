@@ -0,0 +1,129 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, RequestError}, crdt::{CrdtMode, CrdtRegister, CrdtUpdate, CrdtValue, VersionVector}, network::Network, node::{Node, NodeId}, trace::NoopSink};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Replicas take random writes and merge with random peers, once every replica merged every other
+// one they hold the same value, whatever the order
+
+const CASES: u64 = 200;
+const STEPS: usize = 50;
+const REPLICAS: usize = 3;
+
+fn sorted(mut value: CrdtValue) -> CrdtValue {
+    value.values.sort();
+    value
+}
+
+fn update(mode: CrdtMode, data: String, context: VersionVector) -> CrdtUpdate {
+    CrdtUpdate { mode, data, context }
+}
+
+fn run_case(mode: CrdtMode, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut replicas: Vec<CrdtRegister> = (0..REPLICAS).map(|_| CrdtRegister::new(mode)).collect();
+
+    for step in 0..STEPS {
+        let replica = rng.gen_range(0..REPLICAS);
+        if rng.gen_bool(0.5) {
+            // Writes either replace what the replica shows or are blind
+            let context = if rng.gen_bool(0.5) { replicas[replica].value().context } else { VersionVector::new() };
+            replicas[replica].write(&NodeId(replica as i32), update(mode, format!("{}-{}", replica, step), context));
+        } else {
+            let peer = replicas[rng.gen_range(0..REPLICAS)].clone();
+            let before = replicas[replica].clone();
+            replicas[replica].merge(&peer);
+
+            // Merging is idempotent
+            let once = replicas[replica].clone();
+            replicas[replica].merge(&peer);
+            assert_eq!(replicas[replica], once, "seed {}", seed);
+
+            // and commutative
+            let mut other_way = peer.clone();
+            other_way.merge(&before);
+            assert_eq!(sorted(other_way.value()), sorted(once.value()), "seed {}", seed);
+        }
+    }
+
+    for _ in 0..2 {
+        for i in 0..REPLICAS {
+            for j in 0..REPLICAS {
+                let other = replicas[j].clone();
+                replicas[i].merge(&other);
+            }
+        }
+    }
+    let value = sorted(replicas[0].value());
+    for replica in &replicas {
+        assert_eq!(sorted(replica.value()), value, "seed {}", seed);
+    }
+}
+
+#[test]
+fn last_writer_wins_replicas_converge() {
+    for seed in 0..CASES {
+        run_case(CrdtMode::LastWriterWins, seed);
+    }
+}
+
+#[test]
+fn multi_value_replicas_converge() {
+    for seed in 0..CASES {
+        run_case(CrdtMode::MultiValue, seed);
+    }
+}
+
+#[test]
+fn concurrent_values_stay_until_a_write_has_seen_them() {
+    let mut left = CrdtRegister::new(CrdtMode::MultiValue);
+    let mut right = CrdtRegister::new(CrdtMode::MultiValue);
+    left.write(&NodeId(0), update(CrdtMode::MultiValue, "apple".to_string(), VersionVector::new()));
+    right.write(&NodeId(1), update(CrdtMode::MultiValue, "pear".to_string(), VersionVector::new()));
+
+    left.merge(&right);
+    assert_eq!(sorted(left.value()).values, vec!["apple".to_string(), "pear".to_string()]);
+
+    let context = left.value().context;
+    left.write(&NodeId(0), update(CrdtMode::MultiValue, "apple, pear".to_string(), context));
+    right.merge(&left);
+    assert_eq!(right.value().values, vec!["apple, pear".to_string()]);
+}
+
+#[test]
+fn registers_of_another_mode_are_not_merged() {
+    let mut register = CrdtRegister::new(CrdtMode::MultiValue);
+    register.write(&NodeId(0), update(CrdtMode::MultiValue, "kept".to_string(), VersionVector::new()));
+    let mut other = CrdtRegister::new(CrdtMode::LastWriterWins);
+    other.write(&NodeId(1), update(CrdtMode::LastWriterWins, "ignored".to_string(), VersionVector::new()));
+
+    register.merge(&other);
+    assert_eq!(register.value().values, vec!["kept".to_string()]);
+}
+
+#[test]
+fn requests_give_up_when_no_node_answers() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("likes").with_crdt(CrdtMode::MultiValue).with_timeout(Duration::from_millis(300));
+
+    for node_id in network.node_ids() {
+        network.set_reachable(&node_id, false);
+    }
+    let started = Instant::now();
+    assert_eq!(client.crdt_write("1".to_string(), &VersionVector::new()), Err(RequestError::Timeout));
+    assert_eq!(client.crdt_read(), Err(RequestError::Timeout));
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+
+    // Any one node is enough
+    network.set_reachable(&NodeId(2), true);
+    let client = client.with_timeout(Duration::from_secs(5));
+    client.crdt_write("1".to_string(), &VersionVector::new()).unwrap();
+    assert_eq!(client.crdt_read().unwrap().values, vec!["1".to_string()]);
+}
\ No newline at end of file
@@ -0,0 +1,27 @@
+use atomic_register::{atomic_register_client::ClientId, diagram::to_mermaid, node::{Message, NodeData, NodeId}, replay::{Delivery, Endpoint}};
+
+fn delivery(from: Endpoint, to: Endpoint, message: Message) -> Delivery {
+    Delivery { at: 0, wall_clock: 1_000, from: Some(from), to, message }
+}
+
+#[test]
+fn responses_show_the_value_at_delivery_time() {
+    // Long expired by now, not when it was read
+    let data = NodeData::new("token".to_string(), 1).with_expiry(2_000);
+    let diagram = to_mermaid(&[
+        delivery(Endpoint::Client(ClientId(0)), Endpoint::Node(NodeId(0)), Message::ClientReadRequest((ClientId(0), "session".to_string()))),
+        delivery(Endpoint::Node(NodeId(0)), Endpoint::Client(ClientId(0)), Message::ClientReadResponse(data)),
+    ]);
+
+    assert!(diagram.contains("Note over C0: return token v1"), "{}", diagram);
+}
+
+#[test]
+fn mermaid_labels_are_escaped() {
+    let diagram = to_mermaid(&[
+        delivery(Endpoint::Client(ClientId(0)), Endpoint::Node(NodeId(0)), Message::ClientWriteRequest((ClientId(0), "a;b".to_string(), "#1 <b>\nnext".to_string()))),
+    ]);
+
+    assert!(diagram.contains("C0->>N0: ClientWriteRequest a#59;b\n"), "{}", diagram);
+    assert!(diagram.contains("Note over C0: invoke write a#59;b = #35;1 #lt;b#gt;<br>next\n"), "{}", diagram);
+}
\ No newline at end of file
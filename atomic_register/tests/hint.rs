@@ -0,0 +1,98 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, hint::{HintConfig, HintStore}, network::Network, node::{Node, NodeData, NodeId, Timestamp}, trace::NoopSink};
+
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn config(max_hints_per_node: usize) -> HintConfig {
+    HintConfig {
+        replay_interval: Duration::from_millis(20),
+        max_age: Duration::from_secs(5),
+        max_hints_per_node,
+    }
+}
+
+#[test]
+fn replica_back_from_an_outage_gets_the_writes_it_missed() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_hinted_handoff(config(16));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+
+    network.set_reachable(&NodeId(2), false);
+    for i in 0..5 {
+        client.with_key(&format!("key-{}", i)).write(format!("Data {}", i));
+    }
+    // A second write of a key replaces its hint
+    client.with_key("key-0").write("Data 0 again".to_string());
+    assert_eq!(nodes[0].hint_counts(), (5, 0));
+    network.set_reachable(&NodeId(2), true);
+
+    let deadline = Instant::now() + CATCH_UP_TIMEOUT;
+    while nodes[0].hint_counts().0 > 0 {
+        assert!(Instant::now() < deadline, "hints still pending: {:?}", nodes[0].hint_counts());
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(nodes[2].stored("key-0").data(), "Data 0 again");
+    for i in 1..5 {
+        let key = format!("key-{}", i);
+        assert_eq!(nodes[2].stored(&key), nodes[0].stored(&key));
+    }
+}
+
+#[test]
+fn hints_over_the_limit_are_dropped_and_counted() {
+    let mut hints = HintStore::new(config(2));
+    let now = Instant::now();
+
+    assert!(hints.add(&NodeId(1), "a", NodeData::new("1".to_string(), 1), now));
+    assert!(hints.add(&NodeId(1), "b", NodeData::new("1".to_string(), 1), now));
+    assert!(!hints.add(&NodeId(1), "c", NodeData::new("1".to_string(), 1), now));
+    // Newer versions of hinted keys still replace the old hint
+    assert!(hints.add(&NodeId(1), "a", NodeData::new("2".to_string(), 2), now));
+
+    assert_eq!(hints.len(), 2);
+    assert_eq!(hints.dropped(), 1);
+    assert_eq!(hints.hints_for(&NodeId(1))[0].1.data(), "2");
+}
+
+#[test]
+fn acks_of_older_versions_keep_the_newer_hint() {
+    let mut hints = HintStore::new(config(4));
+    let now = Instant::now();
+
+    hints.add(&NodeId(1), "a", NodeData::new("1".to_string(), 1), now);
+    let replayed = hints.hints_for(&NodeId(1));
+    hints.add(&NodeId(1), "a", NodeData::new("2".to_string(), 2), now);
+
+    hints.ack(&NodeId(1), &[(replayed[0].0.clone(), replayed[0].1.timestamp())]);
+    assert_eq!(hints.len(), 1);
+    hints.ack(&NodeId(1), &[("a".to_string(), Timestamp::new(2, None))]);
+    assert!(hints.is_empty());
+
+    // The same version of a later writer is newer too
+    hints.add(&NodeId(1), "a", NodeData::new("3".to_string(), 3).with_writer(&NodeId(0)), now);
+    hints.add(&NodeId(1), "a", NodeData::new("4".to_string(), 3).with_writer(&NodeId(2)), now);
+    hints.ack(&NodeId(1), &[("a".to_string(), Timestamp::new(3, Some(NodeId(0))))]);
+    assert_eq!(hints.hints_for(&NodeId(1))[0].1.data(), "4");
+}
+
+#[test]
+fn old_hints_expire() {
+    let mut hints = HintStore::new(config(4));
+    let now = Instant::now();
+
+    hints.add(&NodeId(1), "a", NodeData::new("1".to_string(), 1), now);
+    hints.expire(now + Duration::from_secs(1));
+    assert_eq!(hints.len(), 1);
+    hints.expire(now + Duration::from_secs(6));
+    assert!(hints.is_empty());
+    assert_eq!(hints.dropped(), 1);
+}
\ No newline at end of file
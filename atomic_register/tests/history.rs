@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use atomic_register::{atomic_register_client::ClientId, history::{History, OperationKind::{self, Read, Write}}};
+
+// Operations with start and end in milliseconds
+fn history(operations: &[(i32, OperationKind, u64, u64)]) -> History {
+    let mut history = History::new();
+    for (client, kind, start, end) in operations {
+        history.add(ClientId(*client), kind.clone(), Duration::from_millis(*start), Duration::from_millis(*end));
+    }
+    history
+}
+
+#[test]
+fn stale_reads_are_regular_but_not_atomic() {
+    // Client 2 starts its read after client 1 read b, and still gets a
+    let history = history(&[
+        (0, Write("a".to_string()), 0, 10),
+        (0, Write("b".to_string()), 20, 60),
+        (1, Read("b".to_string()), 25, 30),
+        (2, Read("a".to_string()), 40, 45),
+    ]);
+
+    assert_eq!(history.check_safe(), Ok(()));
+    assert_eq!(history.check_regular(), Ok(()));
+    assert!(history.check_linearizable().is_err());
+    assert!(history.check_zones().is_err());
+}
+
+#[test]
+fn new_old_inversions_are_found_by_both_atomic_checkers() {
+    // Two concurrent writes, their values are read in the order a, b, a
+    let history = history(&[
+        (0, Write("a".to_string()), 0, 50),
+        (1, Write("b".to_string()), 0, 50),
+        (2, Read("a".to_string()), 10, 15),
+        (2, Read("b".to_string()), 20, 25),
+        (2, Read("a".to_string()), 30, 35),
+    ]);
+
+    assert_eq!(history.check_regular(), Ok(()));
+    assert_eq!(history.check_linearizable(), Err("No linearization of 5 operations".to_string()));
+    // b is read while a, read before and after it, has to stay the value
+    let error = history.check_zones().unwrap_err();
+    assert!(error.contains("is overwritten while"), "{}", error);
+
+    // Without the last read a goes first and b after it
+    let history = history_without_last(&history);
+    assert_eq!(history.check_linearizable(), Ok(()));
+    assert_eq!(history.check_zones(), Ok(()));
+}
+
+#[test]
+fn safe_reads_concurrent_with_a_write_may_return_anything() {
+    let concurrent = history(&[
+        (0, Write("a".to_string()), 0, 10),
+        (0, Write("b".to_string()), 20, 40),
+        (1, Read("garbage".to_string()), 25, 30),
+    ]);
+    assert_eq!(concurrent.check_safe(), Ok(()));
+    assert!(concurrent.check_regular().is_err());
+
+    // Once the write is done the read has to return it
+    let after = history(&[
+        (0, Write("a".to_string()), 0, 10),
+        (0, Write("b".to_string()), 20, 40),
+        (1, Read("a".to_string()), 45, 50),
+    ]);
+    assert!(after.check_safe().is_err());
+    assert!(after.check_regular().is_err());
+}
+
+fn history_without_last(history: &History) -> History {
+    let mut shorter = History::new();
+    let operations = history.operations();
+    for operation in &operations[..operations.len() - 1] {
+        shorter.add(operation.client.clone(), operation.kind.clone(), operation.start, operation.end);
+    }
+    shorter
+}
\ No newline at end of file
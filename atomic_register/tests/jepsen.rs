@@ -0,0 +1,24 @@
+use std::process::Command;
+
+#[test]
+fn a_short_run_with_both_nemeses_passes() {
+    let output = Command::new(env!("CARGO_BIN_EXE_jepsen"))
+        .args(["--workers", "4", "--keys", "2", "--duration", "2", "--interval-ms", "200", "--nemesis", "pause,partition"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    assert!(output.status.success(), "{}\n{}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("nemesis [pause,partition] every 200ms"), "{}", stdout);
+    assert!(stdout.contains("k0: ") && stdout.contains("k1: "), "{}", stdout);
+    assert!(stdout.lines().any(|line| line.contains(" pause NodeId(") || line.contains(" partition [")), "{}", stdout);
+    assert!(stdout.trim_end().ends_with("PASS"), "{}", stdout);
+}
+
+#[test]
+fn unknown_nemeses_are_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_jepsen")).args(["--nemesis", "crash"]).output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown nemesis crash"));
+}
\ No newline at end of file
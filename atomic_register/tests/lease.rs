@@ -0,0 +1,127 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, ReadError}, lease::{Lease, LeaseConfig}, network::Network, node::{Message, Node, NodeId, Timestamp}, quorum::QuorumState, trace::NoopSink};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn lease() -> Lease {
+    Lease::new(LeaseConfig { duration: Duration::from_millis(100), max_clock_drift: 0.0 })
+}
+
+#[test]
+fn waiting_writes_stop_renewals_until_they_went_through() {
+    let mut lease = lease();
+    let now = Instant::now();
+
+    assert!(lease.grant(&NodeId(0), now));
+    assert!(lease.blocks_writes_from(&NodeId(1), now));
+    assert!(!lease.grant(&NodeId(0), now + Duration::from_millis(50)));
+
+    // The grant ran out, the waiting write is accepted and renewals are granted again
+    let expired = now + Duration::from_millis(150);
+    assert!(!lease.grant(&NodeId(0), expired));
+    assert!(!lease.blocks_writes_from(&NodeId(1), expired));
+    assert!(lease.grant(&NodeId(0), expired));
+}
+
+#[test]
+fn renewals_without_waiting_writes_are_granted() {
+    let mut lease = lease();
+    let now = Instant::now();
+
+    assert!(lease.grant(&NodeId(0), now));
+    assert!(!lease.blocks_writes_from(&NodeId(0), now));
+    assert!(lease.grant(&NodeId(0), now + Duration::from_millis(50)));
+    assert!(!lease.grant(&NodeId(1), now + Duration::from_millis(50)));
+}
+
+fn acquire(lease: &mut Lease, now: Instant) {
+    let request_id = lease.renew(&NodeId(0), now).unwrap();
+    assert!(lease.add_grant(request_id, NodeId(1), 2, now));
+    assert!(lease.is_held(now));
+}
+
+#[test]
+fn only_values_committed_under_the_lease_are_read_with_it() {
+    let mut lease = lease();
+    let now = Instant::now();
+
+    // Committed before the lease, another coordinator may have written since
+    lease.commit("a", Timestamp::new(1, Some(NodeId(0))), now);
+    acquire(&mut lease, now);
+    assert!(!lease.is_committed("a", &Timestamp::new(1, Some(NodeId(0)))));
+
+    lease.commit("a", Timestamp::new(1, Some(NodeId(0))), now);
+    assert!(lease.is_committed("a", &Timestamp::new(1, Some(NodeId(0)))));
+    // A local write no quorum acked yet
+    assert!(!lease.is_committed("a", &Timestamp::new(2, Some(NodeId(0)))));
+    // A late commit of an older value does not go back
+    lease.commit("a", Timestamp::new(2, Some(NodeId(0))), now);
+    lease.commit("a", Timestamp::new(1, Some(NodeId(0))), now);
+    assert!(lease.is_committed("a", &Timestamp::new(2, Some(NodeId(0)))));
+}
+
+#[test]
+fn commits_do_not_survive_a_gap_in_the_lease() {
+    let mut lease = lease();
+    let now = Instant::now();
+    acquire(&mut lease, now);
+    lease.commit("a", Timestamp::new(1, None), now);
+
+    // Renewed while held, the commits stay
+    let renewed = now + Duration::from_millis(60);
+    acquire(&mut lease, renewed);
+    assert!(lease.is_committed("a", &Timestamp::new(1, None)));
+
+    let later = renewed + Duration::from_millis(200);
+    assert!(!lease.is_held(later));
+    acquire(&mut lease, later);
+    assert!(!lease.is_committed("a", &Timestamp::new(1, None)));
+}
+
+#[test]
+fn lease_holder_does_not_read_its_own_uncommitted_write() {
+    let network = Arc::new(Network::local(3, 3));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| {
+        let config = LeaseConfig { duration: Duration::from_secs(5), max_clock_drift: 0.0 };
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_lease(config);
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    let writer = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let reader = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network));
+
+    writer.write("committed".to_string());
+    let deadline = Instant::now() + TIMEOUT;
+    while nodes[0].read_stats().lease == 0 {
+        assert!(Instant::now() < deadline, "no read with the lease");
+        assert_eq!(reader.read().data(), "committed");
+    }
+
+    // The coordinator stores the next value once it knows the version, but no quorum acks it
+    network.set_reachable(&NodeId(1), false);
+    network.set_reachable(&NodeId(2), false);
+    let write = std::thread::spawn(move || writer.write("uncommitted".to_string()));
+    while !matches!(nodes[0].status().quorum_state, QuorumState::WaitingForReadResponse(_)) {
+        std::thread::yield_now();
+    }
+    let round = nodes[0].status().quorum_round;
+    network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(1), "".to_string(), nodes[1].stored(""), 0, round)));
+    while nodes[0].stored("").data() != "uncommitted" {
+        std::thread::yield_now();
+    }
+
+    let lease_reads = nodes[0].read_stats().lease;
+    let impatient = AtomicRegisterClinent::new(ClientId(2), Arc::clone(&network)).with_timeout(Duration::from_millis(300));
+    assert_eq!(impatient.try_read(), Err(ReadError::Timeout));
+    assert_eq!(nodes[0].read_stats().lease, lease_reads);
+
+    network.set_reachable(&NodeId(1), true);
+    network.set_reachable(&NodeId(2), true);
+    write.join().unwrap();
+    assert_eq!(reader.read().data(), "uncommitted");
+}
\ No newline at end of file
@@ -0,0 +1,114 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, RequestError}, history::{History, OperationKind}, network::Network, node::{Message, Node, NodeData, NodeId}, trace::NoopSink};
+
+const WRITERS: usize = 2;
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn start_node(network: &Arc<Network>, node_id: NodeId, members: &[NodeId]) -> Node {
+    let mut node = Node::new(node_id, members.len() / 2 + 1, Arc::clone(network)).with_members(members.to_vec());
+    let handle = node.clone();
+    std::thread::spawn(move || {
+        node.run();
+    });
+    handle
+}
+
+#[test]
+fn operations_across_membership_changes_are_linearizable() {
+    let network = Arc::new(Network::local(3, WRITERS + 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let members: Vec<NodeId> = (0..3).map(NodeId).collect();
+    for node_id in &members {
+        start_node(&network, node_id.clone(), &members);
+    }
+    for i in 3..5 {
+        network.add_node(NodeId(i));
+        start_node(&network, NodeId(i), &members);
+    }
+
+    let history = Arc::new(Mutex::new(History::new()));
+    let writers: Vec<_> = (0..WRITERS).map(|i| {
+        let client = AtomicRegisterClinent::new(ClientId(i as i32), Arc::clone(&network)).with_timeout(TIMEOUT);
+        let history = Arc::clone(&history);
+        std::thread::spawn(move || {
+            for n in 0..10 {
+                let start = history.lock().unwrap().now();
+                let kind = if n % 2 == 0 {
+                    let value = format!("{}-{}", i, n);
+                    assert!(client.try_write(value.clone()), "write timed out");
+                    OperationKind::Write(value)
+                } else {
+                    OperationKind::Read(client.try_read().expect("read timed out").data().to_string())
+                };
+                let mut history = history.lock().unwrap();
+                let end = history.now();
+                history.add(ClientId(i as i32), kind, start, end);
+            }
+        })
+    }).collect();
+
+    // Grow to five members, then the old members 1 and 2 leave
+    let admin = AtomicRegisterClinent::new(ClientId(WRITERS as i32), Arc::clone(&network));
+    assert_eq!(admin.reconfigure((0..5).map(NodeId).collect()).unwrap().epoch, 1);
+    assert_eq!(admin.reconfigure(vec![NodeId(0), NodeId(3), NodeId(4)]).unwrap().epoch, 2);
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    let start = history.lock().unwrap().now();
+    let last = admin.read();
+    let end = history.lock().unwrap().now();
+    history.lock().unwrap().add(ClientId(WRITERS as i32), OperationKind::Read(last.data().to_string()), start, end);
+
+    let history = history.lock().unwrap();
+    if let Err(error) = history.check_linearizable() {
+        panic!("{} in {:?}", error, history.operations());
+    }
+}
+
+#[test]
+fn coordinator_outside_the_old_members_waits_for_a_full_old_quorum() {
+    let network = Arc::new(Network::local(4, 2));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let members: Vec<NodeId> = (1..4).map(NodeId).collect();
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| start_node(&network, node_id, &members)).collect();
+
+    // Only node 1 answers the state request. The coordinator's own empty store must not make up the second answer.
+    network.send_to_node(&NodeId(2), Message::CoordinatorWriteRequest((NodeId(2), "".to_string(), NodeData::new("kept".to_string(), 1), 0, 0)));
+    network.set_reachable(&NodeId(2), false);
+    network.set_reachable(&NodeId(3), false);
+
+    let reconfiguration = {
+        let admin = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+        std::thread::spawn(move || admin.reconfigure(vec![NodeId(0), NodeId(1)]))
+    };
+
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!reconfiguration.is_finished());
+    assert_eq!(nodes[1].configuration().epoch, 0);
+
+    // The client gives up once its timeout is over
+    let admin = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_timeout(Duration::from_millis(300));
+    assert_eq!(admin.reconfigure(vec![NodeId(0), NodeId(1)]), Err(RequestError::Timeout));
+}
+
+#[test]
+fn coordinator_that_missed_a_reconfiguration_writes_to_the_new_members() {
+    let network = Arc::new(Network::local(5, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let members: Vec<NodeId> = (0..3).map(NodeId).collect();
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| start_node(&network, node_id, &members)).collect();
+
+    // Node 2 misses the move to members 0, 3 and 4
+    network.set_reachable(&NodeId(2), false);
+    let admin = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_timeout(TIMEOUT);
+    assert_eq!(admin.reconfigure(vec![NodeId(0), NodeId(3), NodeId(4)]).unwrap().epoch, 1);
+    network.set_reachable(&NodeId(2), true);
+
+    // With the old members, node 2 and node 1 would make up a quorum that no quorum of the new members meets
+    network.send_to_node(&NodeId(2), Message::ClientWriteRequest((ClientId(0), "".to_string(), "late".to_string())));
+    assert!(matches!(network.get_timeout(&ClientId(0), TIMEOUT), Some(Message::WriteAck(_))));
+    assert_eq!(nodes[2].configuration().epoch, 1);
+    assert_eq!(admin.try_read().expect("read timed out").data(), "late");
+}
\ No newline at end of file
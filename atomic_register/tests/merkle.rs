@@ -0,0 +1,47 @@
+use atomic_register::{merkle::{MerkleTree, MERKLE_ROOT}, node::NodeData, store::RegisterStore};
+
+fn store_of(entries: &[(&str, NodeData)]) -> RegisterStore {
+    let mut store = RegisterStore::new();
+    for (key, data) in entries {
+        store.store_if_newer(key, data.clone());
+    }
+    store
+}
+
+#[test]
+fn same_version_with_other_values_differs() {
+    let left = store_of(&[("a", NodeData::new("left".to_string(), 1))]);
+    let right = store_of(&[("a", NodeData::new("right".to_string(), 1))]);
+
+    assert_ne!(left.tree().root(), right.tree().root());
+    let leaf = MerkleTree::leaf_of("a");
+    assert_ne!(left.tree().hash(leaf), right.tree().hash(leaf));
+}
+
+#[test]
+fn root_depends_only_on_the_entries() {
+    let entries = [
+        ("a", NodeData::new("1".to_string(), 1)),
+        ("b", NodeData::new("2".to_string(), 3)),
+        ("c", NodeData::tombstone(2)),
+    ];
+    let mut reversed = entries.clone();
+    reversed.reverse();
+
+    let store = store_of(&entries);
+    assert_eq!(store.tree().root(), store_of(&reversed).tree().root());
+    assert_ne!(store.tree().root(), MerkleTree::new().root());
+    assert_eq!(store.tree().hash(MERKLE_ROOT), store.tree().root());
+}
+
+#[test]
+fn discarding_expired_data_keeps_the_tree_consistent() {
+    let expiring = NodeData::new("soon gone".to_string(), 1).with_expiry(10);
+    let mut store = store_of(&[("a", expiring)]);
+
+    assert_eq!(store.discard_expired(20), 1);
+
+    let mut discarded = NodeData::new("soon gone".to_string(), 1).with_expiry(10);
+    discarded.discard_data();
+    assert_eq!(store.tree().root(), store_of(&[("a", discarded)]).tree().root());
+}
\ No newline at end of file
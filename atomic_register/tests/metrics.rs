@@ -0,0 +1,78 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, metrics::{MetricsRegistry, CLIENT_OPERATION_SECONDS, MESSAGES_RECEIVED_TOTAL, MESSAGES_SENT_TOTAL}, network::Network, node::Node, trace::NoopSink};
+
+// Lines of one metric family in the export
+fn family(export: &str, name: &str) -> Vec<String> {
+    export.lines()
+        .filter(|line| line.starts_with(name) || line.starts_with(&format!("# HELP {} ", name)) || line.starts_with(&format!("# TYPE {} ", name)))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+#[test]
+fn export_follows_the_text_format() {
+    let metrics = MetricsRegistry::new();
+    metrics.describe_counter("test_requests_total", "Requests by path.");
+    metrics.describe_histogram("test_latency_seconds", "Request latency.");
+    metrics.inc("test_requests_total", &[("path", "/a")]);
+    metrics.inc_by("test_requests_total", &[("path", "/a")], 2);
+    metrics.inc("test_requests_total", &[("path", "say \"hi\"\n")]);
+    metrics.observe("test_latency_seconds", &[], Duration::from_millis(3));
+    metrics.observe("test_latency_seconds", &[], Duration::from_millis(20));
+
+    let export = metrics.export();
+    assert_eq!(family(&export, "test_requests_total"), vec![
+        "# HELP test_requests_total Requests by path.",
+        "# TYPE test_requests_total counter",
+        "test_requests_total{path=\"/a\"} 3",
+        "test_requests_total{path=\"say \\\"hi\\\"\\n\"} 1",
+    ]);
+
+    // Buckets are cumulative and end with +Inf, which equals the count
+    let latency = family(&export, "test_latency_seconds");
+    assert_eq!(&latency[..2], &["# HELP test_latency_seconds Request latency.", "# TYPE test_latency_seconds histogram"]);
+    assert!(latency.contains(&"test_latency_seconds_bucket{le=\"0.0025\"} 0".to_string()), "{:?}", latency);
+    assert!(latency.contains(&"test_latency_seconds_bucket{le=\"0.005\"} 1".to_string()), "{:?}", latency);
+    assert!(latency.contains(&"test_latency_seconds_bucket{le=\"0.025\"} 2".to_string()), "{:?}", latency);
+    assert!(latency.contains(&"test_latency_seconds_bucket{le=\"10\"} 2".to_string()), "{:?}", latency);
+    assert!(latency.contains(&"test_latency_seconds_bucket{le=\"+Inf\"} 2".to_string()), "{:?}", latency);
+    assert!(latency.contains(&"test_latency_seconds_sum 0.023".to_string()), "{:?}", latency);
+    assert_eq!(latency.last().unwrap(), "test_latency_seconds_count 2");
+}
+
+#[test]
+fn operations_are_counted() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+    for i in 0..3 {
+        client.write(i.to_string());
+    }
+    client.read();
+    client.read();
+
+    let metrics = network.metrics();
+    assert_eq!(metrics.counter(CLIENT_OPERATION_SECONDS), 5);
+    assert!(metrics.counter(MESSAGES_SENT_TOTAL) >= metrics.counter(MESSAGES_RECEIVED_TOTAL));
+
+    let export = metrics.export();
+    let lines = export.lines().collect::<Vec<_>>();
+    for line in [
+        "atomic_register_client_operation_seconds_count{operation=\"write\"} 3",
+        "atomic_register_client_operation_seconds_count{operation=\"read\"} 2",
+        "atomic_register_messages_sent_total{type=\"ClientWriteRequest\"} 3",
+        "atomic_register_messages_sent_total{type=\"ClientReadRequest\"} 2",
+        "atomic_register_messages_received_total{type=\"ClientReadRequest\"} 2",
+        "atomic_register_messages_sent_total{type=\"ClientReadResponse\"} 2",
+    ] {
+        assert!(lines.contains(&line), "{} not in\n{}", line, export);
+    }
+}
\ No newline at end of file
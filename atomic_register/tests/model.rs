@@ -0,0 +1,44 @@
+use atomic_register::{model::{self, ModelConfig, ModelOperation::{Read, Write}}, node::RegisterSemantics};
+
+#[test]
+fn one_coordinator_is_linearizable() {
+    let report = model::check(&ModelConfig {
+        workload: vec![vec![Write(1)], vec![Read]],
+        ..ModelConfig::default()
+    });
+
+    assert!(report.complete, "{:?}", report);
+    assert!(report.violation.is_none(), "{:?}", report);
+}
+
+#[test]
+fn two_coordinators_are_linearizable() {
+    // Both may pick version 1 for their write, the writer orders the two
+    let report = model::check(&ModelConfig {
+        nodes: 2,
+        quorum: 2,
+        coordinators: vec![0, 1],
+        workload: vec![vec![Write(1), Read], vec![Write(2), Read]],
+        max_crashes: 0,
+        ..ModelConfig::default()
+    });
+
+    assert!(report.complete, "{:?}", report);
+    assert!(report.violation.is_none(), "{:?}", report);
+}
+
+#[test]
+fn regular_reads_have_a_counterexample() {
+    // Without the write-back a read can return the new value and the next read the old one
+    let report = model::check(&ModelConfig {
+        coordinators: vec![0, 1],
+        workload: vec![vec![Write(1)], vec![Read, Read]],
+        max_crashes: 0,
+        semantics: RegisterSemantics::Regular,
+        ..ModelConfig::default()
+    });
+
+    let violation = report.violation.expect("regular reads are not linearizable");
+    assert!(!report.complete);
+    assert!(violation.error.starts_with("No linearization"), "{}: {:?}", violation.error, violation.trace);
+}
\ No newline at end of file
@@ -0,0 +1,210 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, ReadError}, history::{History, OperationKind}, network::Network, node::{Message, Node, NodeData, NodeId, ReadStats}, quorum::QuorumState, replay::Endpoint, trace::NoopSink};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+// Random workloads against one cluster per test, every case on a fresh key. While the clients run, a minority
+// of replicas loses messages and late responses for other keys arrive at random nodes.
+
+const CASES: u64 = 20;
+const CLIENTS: usize = 3;
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn start_cluster(node_count: usize) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(node_count, CLIENTS));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    let mut nodes = Vec::new();
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, node_count / 2 + 1, Arc::clone(&network));
+        nodes.push(node.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    (network, nodes)
+}
+
+fn run_case(network: &Arc<Network>, nodes: &[Node], seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let key = format!("case {}", seed);
+    let history = Arc::new(Mutex::new(History::new()));
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let clients: Vec<_> = (0..CLIENTS).map(|i| {
+        let client = AtomicRegisterClinent::new(ClientId(i as i32), Arc::clone(network)).with_timeout(TIMEOUT).with_key(&key);
+        let history = Arc::clone(&history);
+        let operations: Vec<bool> = (0..rng.gen_range(1..8)).map(|_| rng.gen_bool(0.5)).collect();
+        std::thread::spawn(move || {
+            for (n, write) in operations.into_iter().enumerate() {
+                let start = history.lock().unwrap().now();
+                let kind = if write {
+                    let value = format!("{}-{}", i, n);
+                    assert!(client.try_write(value.clone()), "seed {}: write timed out", seed);
+                    OperationKind::Write(value)
+                } else {
+                    let data = client.try_read().unwrap_or_else(|_| panic!("seed {}: read timed out", seed));
+                    OperationKind::Read(data.data().to_string())
+                };
+                let mut history = history.lock().unwrap();
+                let end = history.now();
+                history.add(ClientId(i as i32), kind, start, end);
+            }
+        })
+    }).collect();
+
+    let nemesis = {
+        let network = Arc::clone(network);
+        let stopped = Arc::clone(&stopped);
+        let mut rng = StdRng::seed_from_u64(seed ^ 0xfa17);
+        std::thread::spawn(move || {
+            let node_ids = network.node_ids();
+            let replicas: Vec<_> = node_ids.iter().filter(|node_id| *node_id != network.coordinator_id()).cloned().collect();
+            while !stopped.load(Ordering::SeqCst) {
+                let size = rng.gen_range(0..=(node_ids.len() - 1) / 2);
+                let down: Vec<_> = replicas.choose_multiple(&mut rng, size).cloned().collect();
+                for node_id in &down {
+                    network.set_reachable(node_id, false);
+                }
+
+                let late = Message::CoordinatorReadResponse((NodeId(0), format!("stray {}", rng.gen::<u8>()), NodeData::new("late".to_string(), rng.gen_range(0..100)), 0, rng.gen()));
+                network.inject(node_ids.choose(&mut rng).unwrap(), late);
+
+                std::thread::sleep(Duration::from_millis(rng.gen_range(1..20)));
+                for node_id in &down {
+                    network.set_reachable(node_id, true);
+                }
+            }
+        })
+    };
+
+    for client in clients {
+        client.join().unwrap();
+    }
+    stopped.store(true, Ordering::SeqCst);
+    nemesis.join().unwrap();
+
+    if let Err(error) = history.lock().unwrap().check_linearizable() {
+        panic!("seed {}: {} in {:?}", seed, error, history.lock().unwrap().operations());
+    }
+
+    // Every coordinator goes back to waiting for requests
+    let started = Instant::now();
+    for node in nodes {
+        while node.status().quorum_state != QuorumState::WaitingForRequest {
+            assert!(started.elapsed() < TIMEOUT, "seed {}: node {:?} stuck in {:?}", seed, node.status().id, node.status().quorum_state);
+            std::thread::yield_now();
+        }
+    }
+}
+
+#[test]
+fn random_workloads_on_three_nodes_are_linearizable() {
+    let (network, nodes) = start_cluster(3);
+    for seed in 0..CASES {
+        run_case(&network, &nodes, seed);
+    }
+}
+
+#[test]
+fn random_workloads_on_five_nodes_are_linearizable() {
+    let (network, nodes) = start_cluster(5);
+    for seed in CASES..2 * CASES {
+        run_case(&network, &nodes, seed);
+    }
+}
+
+#[test]
+fn repeated_read_responses_of_one_replica_do_not_make_a_quorum() {
+    let (network, nodes) = start_cluster(5);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("repeated");
+
+    // No earlier phase left late answers behind. Only node 1 answers, and its answer arrives three more times
+    for node_id in [NodeId(2), NodeId(3), NodeId(4)] {
+        network.set_reachable(&node_id, false);
+    }
+    let repeater = {
+        let network = Arc::clone(&network);
+        let coordinator = nodes[0].clone();
+        let data = nodes[1].stored("repeated");
+        std::thread::spawn(move || {
+            while !matches!(coordinator.status().quorum_state, QuorumState::WaitingForReadResponse(_)) {
+                std::thread::yield_now();
+            }
+            let round = coordinator.status().quorum_round;
+            for _ in 0..3 {
+                network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(1), "repeated".to_string(), data.clone(), 0, round)));
+            }
+        })
+    };
+
+    let timed_out = client.with_timeout(Duration::from_millis(500)).try_read();
+    repeater.join().unwrap();
+    assert_eq!(timed_out, Err(ReadError::Timeout));
+
+    // A second replica completes the quorum
+    let round = nodes[0].status().quorum_round;
+    network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(2), "repeated".to_string(), nodes[2].stored("repeated"), 0, round)));
+    let started = Instant::now();
+    while nodes[0].status().quorum_state != QuorumState::WaitingForRequest {
+        assert!(started.elapsed() < TIMEOUT, "read quorum never completed");
+        std::thread::yield_now();
+    }
+}
+
+#[test]
+fn lost_quorum_requests_are_sent_again() {
+    let (network, nodes) = start_cluster(3);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("lost").with_timeout(TIMEOUT);
+
+    // Every request of the coordinator is lost until the links come back
+    for node_id in [NodeId(1), NodeId(2)] {
+        network.set_link(&Endpoint::Node(NodeId(0)), &Endpoint::Node(node_id), false);
+    }
+    let healer = {
+        let network = Arc::clone(&network);
+        let coordinator = nodes[0].clone();
+        std::thread::spawn(move || {
+            while !matches!(coordinator.status().quorum_state, QuorumState::WaitingForReadResponse(_)) {
+                std::thread::yield_now();
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            network.heal();
+        })
+    };
+
+    assert!(client.try_write("kept".to_string()));
+    healer.join().unwrap();
+    assert_eq!(client.read().value(), Some("kept"));
+}
+
+#[test]
+fn reads_are_counted_by_the_path_they_take() {
+    let (network, nodes) = start_cluster(3);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("counted");
+    let wait_until_everywhere = |value: &str| {
+        let started = Instant::now();
+        while nodes.iter().any(|node| node.stored("counted").data() != value) {
+            assert!(started.elapsed() < TIMEOUT, "{} not on every node", value);
+            std::thread::yield_now();
+        }
+    };
+
+    // Every replica agrees with the coordinator
+    client.write("apple".to_string());
+    wait_until_everywhere("apple");
+    assert_eq!(client.read().value(), Some("apple"));
+
+    // Node 2 misses the write and then answers the read in place of node 1
+    network.set_reachable(&NodeId(2), false);
+    client.write("pear".to_string());
+    network.set_reachable(&NodeId(1), false);
+    network.set_reachable(&NodeId(2), true);
+    assert_eq!(client.read().value(), Some("pear"));
+    network.set_reachable(&NodeId(1), true);
+    wait_until_everywhere("pear");
+    assert_eq!(client.read().value(), Some("pear"));
+
+    assert_eq!(nodes[0].read_stats(), ReadStats { fast_path: 2, write_back: 1, lease: 0, local: 0 });
+    assert_eq!(nodes[1].read_stats(), ReadStats::default());
+}
\ No newline at end of file
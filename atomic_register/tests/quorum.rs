@@ -0,0 +1,222 @@
+use atomic_register::{node::{NodeData, NodeId}, quorum::{Quorum, QuorumState, Round}};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Random sequences of quorum events go straight to the Quorum methods the coordinator loops call,
+// answers arrive in any state, twice, after the quorum is done and from earlier configurations.
+
+const CASES: u64 = 500;
+const STEPS: usize = 200;
+
+#[derive(Debug)]
+enum Event {
+    StartWrite,
+    StartRead,
+    StartReconfiguration,
+    WriteAck(NodeId, u64, Round),
+    ReadResponse(NodeId, u64, Round, u32),
+    // A reconfiguration installed by another coordinator changes the quorum size and restarts the phase
+    Install(usize),
+    Finish,
+}
+
+// Answers mostly belong to the current epoch and round, some are late or of a round that did not start yet
+fn random_event(rng: &mut StdRng, nodes: i32, quorum: &Quorum) -> Event {
+    let round = match rng.gen_range(0..10) {
+        0 => quorum.round.saturating_sub(1),
+        1 => quorum.round + 1,
+        _ => quorum.round,
+    };
+    let epoch = if rng.gen_bool(0.1) { quorum.epoch.saturating_sub(1) } else { quorum.epoch };
+    match rng.gen_range(0..10) {
+        0 => Event::StartWrite,
+        1 => Event::StartRead,
+        2 => Event::StartReconfiguration,
+        3 if rng.gen_bool(0.2) => Event::Install(rng.gen_range(1..=nodes as usize / 2 + 1)),
+        3 => Event::Finish,
+        4..=6 => Event::WriteAck(NodeId(rng.gen_range(1..nodes)), epoch, round),
+        _ => Event::ReadResponse(NodeId(rng.gen_range(1..nodes)), epoch, round, rng.gen_range(0..5)),
+    }
+}
+
+fn count(quorum: &Quorum) -> Option<usize> {
+    match quorum.quorum_state {
+        QuorumState::WaitingForWriteAck(count) | QuorumState::WaitingForReadResponse(count) => Some(count),
+        _ => None,
+    }
+}
+
+// Whether an answer counted
+fn apply(quorum: &mut Quorum, event: &Event) -> bool {
+    match event {
+        Event::StartWrite => { quorum.start(QuorumState::WaitingForWriteAck(1)); },
+        Event::StartRead => { quorum.start(QuorumState::WaitingForReadResponse(1)); },
+        Event::StartReconfiguration => { quorum.start(QuorumState::Reconfiguring); },
+        Event::WriteAck(node_id, epoch, round) => return quorum.add_ack(node_id, *epoch, *round),
+        Event::ReadResponse(node_id, epoch, round, version) => return quorum.add_response(node_id, *epoch, *round, NodeData::new(format!("from {}", node_id.0), *version)),
+        Event::Install(acks) => quorum.reconfigure(quorum.epoch + 1, *acks, true),
+        Event::Finish => quorum.go_to_waiting_requst(),
+    }
+    false
+}
+
+// An answer counts in the phase of its epoch and round that waits for it, until the quorum is done
+// and only for the first answer of a replica
+fn should_count(quorum: &Quorum, event: &Event) -> bool {
+    match event {
+        Event::WriteAck(node_id, epoch, round) => *epoch == quorum.epoch && *round == quorum.round && quorum.is_write_coordinator() && !quorum.done_write_quorum() && !quorum.node_ids.contains(node_id),
+        Event::ReadResponse(node_id, epoch, round, _) => *epoch == quorum.epoch && *round == quorum.round && quorum.is_read_coordinator() && !quorum.done_read_quorum() && !quorum.node_ids.contains(node_id),
+        _ => false,
+    }
+}
+
+fn check_invariants(quorum: &Quorum) -> Result<(), String> {
+    let states = [quorum.is_waiting_request(), quorum.is_write_coordinator(), quorum.is_read_coordinator(), quorum.is_reconfiguring()];
+    if states.iter().filter(|state| **state).count() != 1 {
+        return Err(format!("{:?} is not in exactly one state", quorum.quorum_state));
+    }
+
+    let mut distinct = quorum.node_ids.clone();
+    distinct.sort_by_key(|node_id| node_id.0);
+    distinct.dedup();
+    if distinct.len() != quorum.node_ids.len() {
+        return Err(format!("Responders {:?} are not distinct", quorum.node_ids));
+    }
+
+    // The coordinator counts itself
+    if let Some(count) = count(quorum) {
+        if count != quorum.node_ids.len() + 1 {
+            return Err(format!("Count {} but {} distinct responders", count, quorum.node_ids.len()));
+        }
+    } else if !quorum.node_ids.is_empty() || !quorum.node_datas.is_empty() {
+        return Err(format!("Responses {:?} kept in {:?}", quorum.node_ids, quorum.quorum_state));
+    }
+
+    // Done exactly when enough distinct nodes answered, also after the quorum size changed
+    if quorum.done_write_quorum() != (quorum.is_write_coordinator() && quorum.node_ids.len() + 1 == quorum.acks) {
+        return Err(format!("Write quorum done is {} with {} distinct acks of {}", quorum.done_write_quorum(), quorum.node_ids.len() + 1, quorum.acks));
+    }
+    if quorum.done_read_quorum() != (quorum.is_read_coordinator() && quorum.node_ids.len() + 1 == quorum.acks) {
+        return Err(format!("Read quorum done is {} with {} distinct responses of {}", quorum.done_read_quorum(), quorum.node_ids.len() + 1, quorum.acks));
+    }
+    if quorum.is_read_coordinator() && quorum.node_datas.len() != quorum.node_ids.len() {
+        return Err(format!("{} responses from {} responders", quorum.node_datas.len(), quorum.node_ids.len()));
+    }
+    Ok(())
+}
+
+#[test]
+fn random_events_keep_invariants() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes = rng.gen_range(1..=7);
+        let mut quorum = Quorum::new(nodes as usize / 2 + 1);
+        let mut events = Vec::new();
+
+        for _ in 0..STEPS {
+            let event = random_event(&mut rng, nodes.max(2), &quorum);
+            let round = quorum.round;
+            let in_phase = count(&quorum).is_some();
+            let expected = should_count(&quorum, &event);
+            let counted = apply(&mut quorum, &event);
+            events.push(event);
+
+            if counted != expected {
+                panic!("seed {}: counted is {} after {:?}", seed, counted, events);
+            }
+            // Every phase gets a round of its own, a reconfiguration restarts a phase in progress
+            let started = match events.last() {
+                Some(Event::StartWrite | Event::StartRead | Event::StartReconfiguration) => true,
+                Some(Event::Install(_)) => in_phase,
+                _ => false,
+            };
+            if quorum.round != round && (!started || quorum.round != round + 1) {
+                panic!("seed {}: round {} after {} and {:?}", seed, quorum.round, round, events);
+            }
+            if let Err(error) = check_invariants(&quorum) {
+                panic!("seed {}: {} after {:?}", seed, error, events);
+            }
+        }
+    }
+}
+
+#[test]
+fn quorum_is_done_once_enough_distinct_nodes_answer() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes = rng.gen_range(2..=9);
+        let acks = rng.gen_range(1..=nodes as usize);
+        let write = rng.gen_bool(0.5);
+
+        let mut quorum = Quorum::new(acks);
+        apply(&mut quorum, if write { &Event::StartWrite } else { &Event::StartRead });
+
+        let mut answered = vec![NodeId(0)];
+        while answered.len() < acks {
+            let node_id = NodeId(rng.gen_range(1..nodes));
+            let done = quorum.done_write_quorum() || quorum.done_read_quorum();
+            assert!(!done, "seed {}: done after {:?} of {} acks", seed, answered, acks);
+
+            apply(&mut quorum, &if write { Event::WriteAck(node_id.clone(), 0, 1) } else { Event::ReadResponse(node_id.clone(), 0, 1, 0) });
+            if !answered.contains(&node_id) {
+                answered.push(node_id);
+            }
+        }
+
+        // Duplicates and latecomers change nothing
+        for _ in 0..rng.gen_range(0..10) {
+            let node_id = NodeId(rng.gen_range(1..nodes));
+            apply(&mut quorum, &if write { Event::WriteAck(node_id, 0, 1) } else { Event::ReadResponse(node_id, 0, 1, 0) });
+        }
+
+        assert_eq!(quorum.done_write_quorum(), write, "seed {}", seed);
+        assert_eq!(quorum.done_read_quorum(), !write, "seed {}", seed);
+        assert_eq!(count(&quorum), Some(acks), "seed {}", seed);
+    }
+}
+
+#[test]
+fn answers_outside_a_quorum_phase_do_not_count() {
+    for seed in 0..CASES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut quorum = Quorum::new(rng.gen_range(1..=4));
+        for _ in 0..rng.gen_range(0..STEPS) {
+            let event = random_event(&mut rng, 5, &quorum);
+            apply(&mut quorum, &event);
+        }
+        // Never done, so only the state decides
+        quorum.acks = 10;
+        let write_coordinator = quorum.is_write_coordinator();
+        let read_coordinator = quorum.is_read_coordinator();
+        let state = quorum.quorum_state.clone();
+
+        // Node ids no event uses, so they have not answered yet
+        let (epoch, round) = (quorum.epoch, quorum.round);
+        assert!(!quorum.add_ack(&NodeId(9), epoch, round + 1), "seed {}: ack of a later round in {:?}", seed, state);
+        assert!(!quorum.add_ack(&NodeId(9), epoch.wrapping_sub(1), round), "seed {}: ack of an earlier epoch in {:?}", seed, state);
+        assert_eq!(quorum.add_ack(&NodeId(9), epoch, round), write_coordinator, "seed {}: ack in {:?}", seed, state);
+        assert!(!quorum.add_ack(&NodeId(9), epoch, round), "seed {}: second ack in {:?}", seed, state);
+        assert!(!quorum.add_response(&NodeId(8), epoch, round.wrapping_sub(1), NodeData::new("".to_string(), 0)), "seed {}: response of an earlier round in {:?}", seed, state);
+        assert_eq!(quorum.add_response(&NodeId(8), epoch, round, NodeData::new("".to_string(), 0)), read_coordinator, "seed {}: response in {:?}", seed, state);
+        assert!(!quorum.add_response(&NodeId(8), epoch, round, NodeData::new("".to_string(), 0)), "seed {}: second response in {:?}", seed, state);
+        assert_eq!(quorum.quorum_state == state, !write_coordinator && !read_coordinator, "seed {}", seed);
+        assert!(check_invariants(&quorum).is_ok(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn reconfiguration_restarts_the_phase_in_progress() {
+    let mut quorum = Quorum::new(3);
+    apply(&mut quorum, &Event::StartWrite);
+    assert!(quorum.add_ack(&NodeId(1), 0, 1));
+    assert!(quorum.add_ack(&NodeId(2), 0, 1) && quorum.done_write_quorum());
+
+    // Acks of the old members count no more, not even toward the smaller quorum of the new members
+    quorum.reconfigure(1, 2, false);
+    assert_eq!((quorum.epoch, quorum.round, count(&quorum)), (1, 2, Some(0)));
+    assert!(!quorum.done_write_quorum());
+    assert!(!quorum.add_ack(&NodeId(3), 0, 1));
+    assert!(!quorum.add_ack(&NodeId(3), 0, 2));
+    assert!(quorum.add_ack(&NodeId(3), 1, 2));
+    assert!(!quorum.done_write_quorum());
+    assert!(quorum.add_ack(&NodeId(4), 1, 2) && quorum.done_write_quorum());
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::ClientId, network::Network, node::{Message, NodeId}, raft::{RaftCommand, RaftMessage, RaftNode}, raft_client::RaftClient, trace::MemorySink};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn start_cluster(clients: usize) -> (Arc<Network>, Arc<MemorySink>) {
+    let network = Arc::new(Network::local(3, clients));
+    let sink = Arc::new(MemorySink::default());
+    network.set_trace_sink(sink.clone());
+
+    for node_id in network.node_ids() {
+        let mut node = RaftNode::new(node_id, network.node_ids(), Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    (network, sink)
+}
+
+// Leader of the highest term so far, a term has at most one
+fn newest_leader(sink: &MemorySink) -> (NodeId, u64) {
+    sink.events().into_iter()
+        .filter(|event| event.phase == "raft.leader")
+        .map(|event| (event.node.unwrap(), event.detail.trim_start_matches("term ").parse().unwrap()))
+        .max_by_key(|(_, term)| *term)
+        .expect("no leader elected")
+}
+
+// Raft nodes keep sending while unreachable, so the node loses its links both ways
+fn isolate(network: &Network, node_id: &NodeId) {
+    let others: Vec<NodeId> = network.node_ids().into_iter().filter(|other| other != node_id).collect();
+    network.partition(&[vec![node_id.clone()], others]);
+}
+
+fn wait_for_leader_after(sink: &MemorySink, term: u64) -> (NodeId, u64) {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let leader = newest_leader(sink);
+        if leader.1 > term {
+            return leader;
+        }
+        assert!(Instant::now() < deadline, "no leader after term {}", term);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn followers_elect_a_new_leader_when_the_leader_is_cut_off() {
+    let (network, sink) = start_cluster(1);
+    let mut client = RaftClient::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+
+    assert_eq!(client.write("apple".to_string()).version(), 1);
+    let (leader, term) = newest_leader(&sink);
+
+    isolate(&network, &leader);
+    let (new_leader, _) = wait_for_leader_after(&sink, term);
+    assert_ne!(new_leader, leader);
+
+    let data = client.write("pear".to_string());
+    assert_eq!((data.data(), data.version()), ("pear", 2));
+}
+
+#[test]
+fn entries_an_old_leader_could_not_commit_are_replaced() {
+    let (network, sink) = start_cluster(2);
+    let mut client = RaftClient::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+    client.write("apple".to_string());
+    let (old_leader, term) = newest_leader(&sink);
+
+    // The cut off leader appends an entry no follower gets
+    isolate(&network, &old_leader);
+    network.inject(&old_leader, Message::Raft(RaftMessage::ClientRequest { client_id: ClientId(1), seq: 1, command: RaftCommand::Write("cart".to_string(), "lost".to_string()) }));
+    let (new_leader, _) = wait_for_leader_after(&sink, term);
+    assert_eq!(client.write("pear".to_string()).version(), 2);
+    network.heal();
+
+    // The old leader and the remaining follower form the majority, so the old leader's log
+    // has to be repaired from the follower
+    isolate(&network, &new_leader);
+    let data = client.read();
+    assert_eq!((data.data(), data.version()), ("pear", 2));
+    assert_eq!(client.write("plum".to_string()).version(), 3);
+
+    let follower = network.node_ids().into_iter().find(|node_id| *node_id != old_leader && *node_id != new_leader).unwrap();
+    network.heal();
+    isolate(&network, &follower);
+    let data = client.read();
+    assert_eq!((data.data(), data.version()), ("plum", 3));
+}
+
+#[test]
+fn committed_writes_survive_leader_changes() {
+    let (network, sink) = start_cluster(3);
+    // One client per key, a client's requests are told apart by its sequence number only
+    let mut clients: Vec<RaftClient> = (0..3).map(|i| RaftClient::new(ClientId(i), Arc::clone(&network)).with_key(&format!("key-{}", i))).collect();
+    let mut term = 0;
+
+    for round in 0..3 {
+        for (i, client) in clients.iter_mut().enumerate() {
+            client.write(format!("{}-{}", round, i));
+        }
+
+        // The leader that committed the writes goes away until the next one took over
+        let (leader, leader_term) = newest_leader(&sink);
+        assert!(leader_term > term, "round {}: no new leader", round);
+        isolate(&network, &leader);
+        wait_for_leader_after(&sink, leader_term);
+        term = leader_term;
+        network.heal();
+    }
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        let data = client.read();
+        assert_eq!((data.data().to_string(), data.version()), (format!("2-{}", i), 3));
+    }
+}
\ No newline at end of file
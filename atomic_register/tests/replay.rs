@@ -0,0 +1,66 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, replay::{self, Endpoint, Replay}, trace::MemorySink};
+
+fn start_nodes(network: &Arc<Network>) -> Vec<Node> {
+    network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(network));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect()
+}
+
+// Steps of each node in the order the node took them
+fn node_steps(sink: &MemorySink) -> BTreeMap<NodeId, Vec<String>> {
+    let mut steps: BTreeMap<NodeId, Vec<String>> = BTreeMap::new();
+    for event in sink.events() {
+        if let Some(node_id) = event.node {
+            steps.entry(node_id).or_default().push(format!("{} {}", event.phase, event.detail));
+        }
+    }
+    steps
+}
+
+#[test]
+fn a_replay_takes_the_recorded_steps_and_ends_in_the_recorded_state() {
+    let path = std::env::temp_dir().join(format!("atomic_register_replay_test_{}.jsonl", std::process::id()));
+    let network = Arc::new(Network::local(3, 1));
+    let sink = Arc::new(MemorySink::default());
+    network.set_trace_sink(sink.clone());
+    network.record_to(&path).unwrap();
+    let recorded_nodes = start_nodes(&network);
+
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    client.with_key("cart").write("apple".to_string());
+    client.with_key("basket").write("pear".to_string());
+    client.with_key("cart").write("plum".to_string());
+    client.with_key("basket").delete();
+    client.with_key("cart").read();
+    network.stop_recording();
+
+    let replay_network = Arc::new(Network::local(3, 1));
+    let replay_sink = Arc::new(MemorySink::default());
+    replay_network.set_trace_sink(replay_sink.clone());
+    let replayed_nodes = start_nodes(&replay_network);
+
+    let recorded = replay::load(&path).unwrap();
+    let mut replay = Replay::new(Arc::clone(&replay_network), recorded.clone());
+    let mut delivered = Vec::new();
+    while let Some(delivery) = replay.step() {
+        delivered.push(delivery.clone());
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // No sleeps, every step returned once its node was done with the message
+    assert_eq!(delivered, recorded);
+    assert!(delivered.iter().any(|delivery| delivery.to == Endpoint::Client(ClientId(0))));
+    for (recorded, replayed) in recorded_nodes.iter().zip(replayed_nodes.iter()) {
+        for key in ["cart", "basket"] {
+            assert_eq!(recorded.stored(key), replayed.stored(key), "key {} on node {:?}", key, recorded.status().id);
+        }
+    }
+    assert_eq!(node_steps(&replay_sink), node_steps(&sink));
+}
\ No newline at end of file
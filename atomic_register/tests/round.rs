@@ -0,0 +1,54 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, ReadError}, network::Network, node::{Message, Node, NodeId}, quorum::QuorumState, trace::NoopSink};
+
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn start_cluster(node_count: usize) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(node_count, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    let mut nodes = Vec::new();
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, node_count / 2 + 1, Arc::clone(&network));
+        nodes.push(node.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+    (network, nodes)
+}
+
+#[test]
+fn answers_of_an_earlier_round_do_not_count() {
+    let (network, nodes) = start_cluster(3);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("round");
+
+    for node_id in [NodeId(1), NodeId(2)] {
+        network.set_reachable(&node_id, false);
+    }
+    let late = {
+        let network = Arc::clone(&network);
+        let coordinator = nodes[0].clone();
+        let data = nodes[1].stored("round");
+        std::thread::spawn(move || {
+            while !matches!(coordinator.status().quorum_state, QuorumState::WaitingForReadResponse(_)) {
+                std::thread::yield_now();
+            }
+            let round = coordinator.status().quorum_round;
+            network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(1), "round".to_string(), data, 0, round - 1)));
+        })
+    };
+
+    let timed_out = client.with_timeout(Duration::from_millis(500)).try_read();
+    late.join().unwrap();
+    assert_eq!(timed_out, Err(ReadError::Timeout));
+
+    let round = nodes[0].status().quorum_round;
+    network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(1), "round".to_string(), nodes[1].stored("round"), 0, round)));
+    let started = Instant::now();
+    while nodes[0].status().quorum_state != QuorumState::WaitingForRequest {
+        assert!(started.elapsed() < TIMEOUT, "read quorum never completed");
+        std::thread::yield_now();
+    }
+}
\ No newline at end of file
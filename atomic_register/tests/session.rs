@@ -0,0 +1,106 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId, ReadError}, network::Network, node::{Message, Node, NodeData, NodeId, RegisterSemantics, Timestamp}, ring::{GroupId, HashRing, ReplicaGroup}, session::Session, trace::NoopSink};
+
+// Safe reads are answered by the coordinator alone, so a coordinator that missed a write has an old value
+fn start_cluster() -> (Arc<Network>, Arc<Mutex<HashRing>>, Vec<Node>) {
+    let network = Arc::new(Network::local(3, 2));
+    network.set_trace_sink(Arc::new(NoopSink));
+
+    let mut nodes = Vec::new();
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_semantics(RegisterSemantics::Safe);
+        nodes.push(node.clone());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    ring.lock().unwrap().add_group(ReplicaGroup::new(GroupId(0), vec![NodeId(0), NodeId(1), NodeId(2)]));
+    (network, ring, nodes)
+}
+
+fn fail_over_to_node_2(ring: &Mutex<HashRing>) {
+    ring.lock().unwrap().add_group(ReplicaGroup::new(GroupId(0), vec![NodeId(2), NodeId(0), NodeId(1)]));
+}
+
+fn session_client(network: &Arc<Network>, ring: &Arc<Mutex<HashRing>>, session: &Arc<Mutex<Session>>) -> AtomicRegisterClinent {
+    AtomicRegisterClinent::new(ClientId(0), Arc::clone(network))
+        .with_ring(Arc::clone(ring))
+        .with_session(Arc::clone(session))
+        .with_key("profile")
+}
+
+#[test]
+fn reads_see_the_own_writes_after_a_failover() {
+    let (network, ring, _) = start_cluster();
+    let session = Arc::new(Mutex::new(Session::new()));
+    let client = session_client(&network, &ring, &session);
+
+    network.set_reachable(&NodeId(2), false);
+    client.write("v1".to_string());
+    client.write("v2".to_string());
+    network.set_reachable(&NodeId(2), true);
+    assert_eq!(session.lock().unwrap().min_timestamp("profile"), Timestamp::new(2, Some(NodeId(0))));
+
+    fail_over_to_node_2(&ring);
+    let data = client.read();
+    assert_eq!((data.value(), data.version()), (Some("v2"), 2));
+}
+
+#[test]
+fn reads_do_not_go_back_across_a_delete() {
+    let (network, ring, _) = start_cluster();
+    let session = Arc::new(Mutex::new(Session::new()));
+    let client = session_client(&network, &ring, &session);
+
+    client.write("v1".to_string());
+    // Node 2 keeps the value from before the delete
+    network.set_reachable(&NodeId(2), false);
+    client.delete();
+    network.set_reachable(&NodeId(2), true);
+    assert_eq!(session.lock().unwrap().min_timestamp("profile"), Timestamp::new(2, Some(NodeId(0))));
+
+    fail_over_to_node_2(&ring);
+    for _ in 0..3 {
+        let data = client.read();
+        assert!(data.is_tombstone() && data.value().is_none(), "read {:?} after the delete", data);
+    }
+}
+
+#[test]
+fn session_reads_ahead_of_every_replica_give_up() {
+    let (network, ring, _) = start_cluster();
+    let session = Arc::new(Mutex::new(Session::new()));
+    let client = session_client(&network, &ring, &session);
+
+    client.write("v1".to_string());
+    // A version no replica has, without a deadline the read still returns
+    session.lock().unwrap().written("profile", Timestamp::new(5, None));
+    assert_eq!(client.try_read(), Err(ReadError::SessionBehind(Timestamp::new(1, Some(NodeId(0))))));
+}
+
+#[test]
+fn reads_do_not_go_back_to_another_writer_of_the_same_version() {
+    let (network, ring, nodes) = start_cluster();
+    let session = Arc::new(Mutex::new(Session::new()));
+    let client = session_client(&network, &ring, &session);
+
+    // Two coordinators picked version 1, node 2 only has the older write
+    let older = NodeData::new("older".to_string(), 1).with_writer(&NodeId(0));
+    let newer = NodeData::new("newer".to_string(), 1).with_writer(&NodeId(1));
+    network.send_to_node(&NodeId(2), Message::CoordinatorWriteRequest((NodeId(0), "profile".to_string(), older.clone(), 0, 0)));
+    for node_id in [NodeId(0), NodeId(1)] {
+        network.send_to_node(&node_id, Message::CoordinatorWriteRequest((NodeId(1), "profile".to_string(), newer.clone(), 0, 0)));
+    }
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while nodes[0].stored("profile") != newer || nodes[1].stored("profile") != newer || nodes[2].stored("profile") != older {
+        assert!(Instant::now() < deadline, "writes not stored");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(client.read().data(), "newer");
+    fail_over_to_node_2(&ring);
+    assert_eq!(client.read().data(), "newer");
+}
\ No newline at end of file
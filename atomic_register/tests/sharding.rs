@@ -0,0 +1,138 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, crdt::{CrdtMode, VersionVector}, history::{History, OperationKind}, network::Network, node::{Node, NodeId}, ring::{GroupId, HashRing, ReplicaGroup}, sibling_client::SiblingClient, trace::NoopSink};
+
+const KEY_COUNT: usize = 20;
+const WRITERS: usize = 3;
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+fn start_group(network: &Arc<Network>, group: &ReplicaGroup) -> Vec<Node> {
+    group.members.iter().map(|node_id| {
+        let mut node = Node::new(node_id.clone(), 2, Arc::clone(network)).with_members(group.members.clone());
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect()
+}
+
+#[test]
+fn writes_racing_a_rebalance_are_kept() {
+    let network = Arc::new(Network::local(6, WRITERS + 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let groups: Vec<ReplicaGroup> = (0..2).map(|i| ReplicaGroup::new(GroupId(i), (3 * i..3 * i + 3).map(NodeId).collect())).collect();
+    let nodes: Vec<Vec<Node>> = groups.iter().map(|group| start_group(&network, group)).collect();
+
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    ring.lock().unwrap().add_group(groups[0].clone());
+    let keys: Vec<String> = (0..KEY_COUNT).map(|i| format!("key-{}", i)).collect();
+
+    let admin = AtomicRegisterClinent::new(ClientId(WRITERS as i32), Arc::clone(&network)).with_ring(Arc::clone(&ring));
+    for key in &keys {
+        admin.with_key(key).write("initial".to_string());
+    }
+    let old_versions: Vec<u32> = keys.iter().map(|key| nodes[0][0].stored(key).version()).collect();
+
+    // Writers work on keys that move, each on its own
+    let mut future_ring = ring.lock().unwrap().clone();
+    future_ring.add_group(groups[1].clone());
+    let moving: Vec<String> = keys.iter().filter(|key| future_ring.group_of(key).id == groups[1].id).take(WRITERS).cloned().collect();
+    assert_eq!(moving.len(), WRITERS);
+
+    let history = Arc::new(Mutex::new(History::new()));
+    let writers: Vec<_> = (0..WRITERS).map(|i| {
+        let client = AtomicRegisterClinent::new(ClientId(i as i32), Arc::clone(&network)).with_ring(Arc::clone(&ring)).with_timeout(TIMEOUT).with_key(&moving[i]);
+        let history = Arc::clone(&history);
+        std::thread::spawn(move || {
+            for n in 0..20 {
+                let start = history.lock().unwrap().now();
+                let kind = if n % 2 == 0 {
+                    let value = format!("{}-{}", i, n);
+                    assert!(client.try_write(value.clone()), "write timed out");
+                    OperationKind::Write(value)
+                } else {
+                    OperationKind::Read(client.try_read().expect("read timed out").data().to_string())
+                };
+                let mut history = history.lock().unwrap();
+                let end = history.now();
+                history.add(ClientId(i as i32), kind, start, end);
+            }
+        })
+    }).collect();
+
+    let moved = admin.rebalance(groups[1].clone()).expect("rebalance timed out");
+    for writer in writers {
+        writer.join().unwrap();
+    }
+    assert!(moved >= WRITERS);
+
+    let operations = history.lock().unwrap().operations().to_vec();
+    for (i, key) in moving.iter().enumerate() {
+        let mut key_history = History::new();
+        for operation in operations.iter().filter(|operation| operation.client == ClientId(i as i32)) {
+            key_history.add(operation.client.clone(), operation.kind.clone(), operation.start, operation.end);
+        }
+        if let Err(error) = key_history.check_linearizable() {
+            panic!("key {}: {} in {:?}", key, error, key_history.operations());
+        }
+        let last = key_history.operations().iter().rev().find_map(|operation| match &operation.kind {
+            OperationKind::Write(value) => Some(value.clone()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(admin.with_key(key).read().data(), last, "key {}", key);
+    }
+
+    for (key, old_version) in keys.iter().zip(old_versions) {
+        if ring.lock().unwrap().group_of(key).id != groups[1].id {
+            continue;
+        }
+        // Moved keys keep counting from their old version and are gone from the old group
+        assert!(nodes[1][0].stored(key).version() >= old_version, "key {}", key);
+        for node in &nodes[0] {
+            assert_eq!(node.stored(key).version(), 0, "key {} still on node {:?}", key, node.status().id);
+        }
+    }
+}
+
+#[test]
+fn siblings_and_crdt_registers_move_with_their_keys() {
+    let network = Arc::new(Network::local(6, 2));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let groups: Vec<ReplicaGroup> = (0..2).map(|i| ReplicaGroup::new(GroupId(i), (3 * i..3 * i + 3).map(NodeId).collect())).collect();
+    let nodes: Vec<Vec<Node>> = groups.iter().map(|group| start_group(&network, group)).collect();
+
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    ring.lock().unwrap().add_group(groups[0].clone());
+    let mut future_ring = ring.lock().unwrap().clone();
+    future_ring.add_group(groups[1].clone());
+    let key = (0..).map(|i| format!("key-{}", i)).find(|key| future_ring.group_of(key).id == groups[1].id).unwrap();
+
+    // Sibling clients talk to node 0, the coordinator of the first group
+    let siblings = SiblingClient::new(ClientId(0), Arc::clone(&network)).with_key(&key);
+    siblings.write("apple".to_string(), &VersionVector::new()).unwrap();
+    siblings.write("pear".to_string(), &VersionVector::new()).unwrap();
+    let admin = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network)).with_ring(Arc::clone(&ring)).with_key(&key).with_crdt(CrdtMode::MultiValue);
+    admin.crdt_write("1".to_string(), &VersionVector::new()).unwrap();
+
+    assert_eq!(admin.rebalance(groups[1].clone()).expect("rebalance timed out"), 1);
+    let mut moved = nodes[1][0].stored_siblings(&key).values;
+    moved.sort();
+    assert_eq!(moved, vec!["apple", "pear"]);
+    assert_eq!(admin.crdt_read().unwrap().values, vec!["1"]);
+    for node in &nodes[0] {
+        assert!(node.stored_siblings(&key).values.is_empty(), "siblings still on node {:?}", node.status().id);
+        assert!(node.stored_crdt(&key).values.is_empty(), "CRDT register still on node {:?}", node.status().id);
+    }
+}
+
+#[test]
+fn keys_land_on_the_same_groups_on_every_build() {
+    let mut ring = HashRing::new(16);
+    for i in 0..3 {
+        ring.add_group(ReplicaGroup::new(GroupId(i), vec![NodeId(i)]));
+    }
+    // Pinned, a client built elsewhere has to find every key where the nodes put it
+    let owners: Vec<i32> = (0..12).map(|i| ring.group_of(&format!("key-{}", i)).id.0).collect();
+    assert_eq!(owners, vec![2, 2, 2, 2, 1, 1, 0, 2, 2, 0, 1, 2]);
+}
\ No newline at end of file
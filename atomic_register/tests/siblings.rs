@@ -0,0 +1,75 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, crdt::{CrdtMode, CrdtValue, VersionVector}, network::Network, node::{Node, NodeId}, sibling_client::SiblingClient, trace::NoopSink};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn start_cluster(node_count: usize, members: &[NodeId]) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(node_count, 3));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, members.len() / 2 + 1, Arc::clone(&network)).with_members(members.to_vec());
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    (network, nodes)
+}
+
+fn sorted(mut value: CrdtValue) -> Vec<String> {
+    value.values.sort();
+    value.values
+}
+
+#[test]
+fn concurrent_writes_stay_siblings_until_a_covering_write() {
+    let members: Vec<NodeId> = (0..3).map(NodeId).collect();
+    let (network, nodes) = start_cluster(3, &members);
+    let alice = SiblingClient::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+    let bob = SiblingClient::new(ClientId(1), Arc::clone(&network)).with_key("cart");
+
+    // Neither write saw the other one
+    alice.write("apple".to_string(), &VersionVector::new()).unwrap();
+    bob.write("pear".to_string(), &VersionVector::new()).unwrap();
+    let value = alice.read().unwrap();
+    assert_eq!(sorted(value.clone()), vec!["apple", "pear"]);
+
+    // A write with the context of that read replaces both, on a quorum of replicas
+    bob.write("apple and pear".to_string(), &value.context).unwrap();
+    assert_eq!(alice.read().unwrap().values, vec!["apple and pear"]);
+    let replicas = nodes.iter().filter(|node| node.stored_siblings("cart").values == vec!["apple and pear"]).count();
+    assert!(replicas >= 2, "{} replicas have the covering write", replicas);
+
+    // A write with an older context is concurrent to the covering one
+    alice.write("plum".to_string(), &value.context).unwrap();
+    assert_eq!(sorted(bob.read().unwrap()), vec!["apple and pear", "plum"]);
+}
+
+#[test]
+fn siblings_and_crdt_registers_move_to_new_members() {
+    let members: Vec<NodeId> = (0..3).map(NodeId).collect();
+    let (network, nodes) = start_cluster(5, &members);
+    let alice = SiblingClient::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+    let bob = SiblingClient::new(ClientId(1), Arc::clone(&network)).with_key("cart");
+    let admin = AtomicRegisterClinent::new(ClientId(2), Arc::clone(&network)).with_key("likes").with_crdt(CrdtMode::MultiValue);
+
+    alice.write("apple".to_string(), &VersionVector::new()).unwrap();
+    bob.write("pear".to_string(), &VersionVector::new()).unwrap();
+    admin.crdt_write("1".to_string(), &VersionVector::new()).unwrap();
+
+    // Every old member but the coordinator leaves
+    admin.reconfigure(vec![NodeId(0), NodeId(3), NodeId(4)]).unwrap();
+    let deadline = Instant::now() + TIMEOUT;
+    for node in &nodes[3..] {
+        while sorted(node.stored_siblings("cart")) != vec!["apple", "pear"] || node.stored_crdt("likes").values != vec!["1"] {
+            assert!(Instant::now() < deadline, "state not transferred: {:?} {:?}", node.stored_siblings("cart"), node.stored_crdt("likes"));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let value = alice.read().unwrap();
+    bob.write("apple and pear".to_string(), &value.context).unwrap();
+    assert_eq!(alice.read().unwrap().values, vec!["apple and pear"]);
+}
\ No newline at end of file
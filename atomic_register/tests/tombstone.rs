@@ -0,0 +1,107 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId, Timestamp}, store::RegisterStore, trace::NoopSink};
+
+const GC_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn writes_after_collection_build_on_the_tombstone_version() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_tombstone_gc(Duration::from_millis(20));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    let cart = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+
+    cart.write("apple".to_string());
+    cart.delete();
+    let deadline = Instant::now() + GC_TIMEOUT;
+    while nodes.iter().any(|node| !node.status().registers.is_empty()) {
+        assert!(Instant::now() < deadline, "tombstone not collected");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // Still deleted, and a late copy of the first write does not come back
+    assert_eq!(cart.read().value(), None);
+    network.send_to_node(&NodeId(2), Message::CoordinatorWriteRequest((NodeId(0), "cart".to_string(), NodeData::new("apple".to_string(), 1), 0, 0)));
+    assert_eq!(cart.read().value(), None);
+
+    cart.write("pear".to_string());
+    let data = cart.read();
+    assert_eq!((data.value(), data.version()), (Some("pear"), 3));
+    let deadline = Instant::now() + GC_TIMEOUT;
+    while nodes.iter().any(|node| node.stored("cart").data() != "pear") {
+        assert!(Instant::now() < deadline, "write not replicated: {:?}", nodes.iter().map(|node| node.stored("cart")).collect::<Vec<_>>());
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn nodes_that_join_after_the_collection_get_the_delete() {
+    let network = Arc::new(Network::local(4, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let members: Vec<NodeId> = (0..3).map(NodeId).collect();
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network)).with_members(members.clone()).with_tombstone_gc(Duration::from_millis(20));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    let cart = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("cart");
+
+    cart.write("apple".to_string());
+    let apple = nodes[0].stored("cart");
+    cart.delete();
+    let deadline = Instant::now() + GC_TIMEOUT;
+    while nodes[..3].iter().any(|node| !node.status().registers.is_empty()) {
+        assert!(Instant::now() < deadline, "tombstone not collected");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // Node 3 only has the first write, the members hand it the floor when it joins
+    network.send_to_node(&NodeId(3), Message::CoordinatorWriteRequest((NodeId(0), "cart".to_string(), apple, 0, 0)));
+    let deadline = Instant::now() + GC_TIMEOUT;
+    while nodes[3].stored("cart").data() != "apple" {
+        assert!(Instant::now() < deadline, "first write not stored");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    cart.reconfigure((0..4).map(NodeId).collect()).unwrap();
+    // The reconfiguration only waits for a quorum of the new members
+    let deadline = Instant::now() + GC_TIMEOUT;
+    while !nodes[3].stored("cart").is_tombstone() {
+        assert!(Instant::now() < deadline, "delete not handed to node 3");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    cart.reconfigure(vec![NodeId(1), NodeId(3)]).unwrap();
+    assert_eq!(cart.read().value(), None);
+}
+
+#[test]
+fn collected_tombstones_keep_their_version_as_floor() {
+    let mut store = RegisterStore::new();
+    store.store_if_newer("cart", NodeData::tombstone(2));
+
+    // A newer write of the key keeps the tombstone
+    assert!(!store.remove_tombstone("cart", &Timestamp::new(1, None)));
+    assert!(store.remove_tombstone("cart", &Timestamp::new(2, None)));
+    assert!(store.is_empty());
+    assert_eq!(store.get("cart"), NodeData::tombstone(2));
+    assert_eq!(store.entries(), vec![("cart".to_string(), NodeData::tombstone(2))]);
+
+    // The floor stays in the Merkle tree, a replica still holding the tombstone is in sync
+    let mut replica = RegisterStore::new();
+    replica.store_if_newer("cart", NodeData::tombstone(2));
+    assert_eq!(store.tree().root(), replica.tree().root());
+
+    assert!(!store.store_if_newer("cart", NodeData::new("apple".to_string(), 2)));
+    assert!(store.store_if_newer("cart", NodeData::new("pear".to_string(), 3)));
+    assert_eq!(store.get("cart").data(), "pear");
+}
\ No newline at end of file
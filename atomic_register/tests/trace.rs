@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Node, NodeId}, trace::MemorySink};
+
+#[test]
+fn a_write_emits_its_steps_in_order() {
+    let network = Arc::new(Network::local(3, 1));
+    let sink = Arc::new(MemorySink::default());
+    network.set_trace_sink(sink.clone());
+    for node_id in network.node_ids() {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+
+    AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("cart").write("apple".to_string());
+    let events = sink.events();
+
+    let start = events.iter().find(|event| event.phase == "write.start").unwrap();
+    assert_eq!(start.node, Some(NodeId(0)));
+    assert_eq!(start.client, Some(ClientId(0)));
+    assert_eq!(start.message_type.as_deref(), Some("ClientWriteRequest"));
+    assert_eq!(start.detail, "key \"cart\"");
+
+    // The coordinator's steps carry the id of the operation
+    let steps: Vec<&str> = events.iter()
+        .filter(|event| event.node == Some(NodeId(0)) && event.operation == start.operation)
+        .map(|event| event.phase.as_str())
+        .collect();
+    assert_eq!(steps, vec!["write.start", "write.version", "write.commit"]);
+    let commit = events.iter().position(|event| event.phase == "write.commit").unwrap();
+    let ack = events.iter().position(|event| event.phase == "client.write_ack").unwrap();
+    assert!(commit < ack);
+    assert_eq!(events[ack].client, Some(ClientId(0)));
+    assert!(events.iter().any(|event| event.phase == "replica.write" && event.node != Some(NodeId(0))));
+}
\ No newline at end of file
@@ -0,0 +1,138 @@
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId}, ring::{GroupId, HashRing, ReplicaGroup}, trace::NoopSink, transaction::TransactionError};
+
+const TRANSFERS: usize = 10;
+
+fn start_nodes(network: &Arc<Network>, members: &[NodeId]) {
+    for node_id in members {
+        let mut node = Node::new(node_id.clone(), members.len() / 2 + 1, Arc::clone(network)).with_members(members.to_vec());
+        std::thread::spawn(move || {
+            node.run();
+        });
+    }
+}
+
+fn cluster(clients: usize) -> Arc<Network> {
+    let network = Arc::new(Network::local(3, clients));
+    network.set_trace_sink(Arc::new(NoopSink));
+    start_nodes(&network, &network.node_ids());
+    network
+}
+
+#[test]
+fn commit_aborts_when_a_read_register_changed() {
+    let network = cluster(2);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let other = AtomicRegisterClinent::new(ClientId(1), Arc::clone(&network));
+    client.with_key("a").write("1".to_string());
+
+    let mut transaction = client.transaction();
+    assert_eq!(transaction.read("a"), "1");
+    transaction.write("a", "from transaction".to_string());
+    transaction.write("b", "from transaction".to_string());
+    other.with_key("a").write("2".to_string());
+
+    assert_eq!(transaction.commit(), Err(TransactionError::Conflict));
+    // None of the writes was applied
+    assert_eq!(client.with_key("a").read().data(), "2");
+    assert_eq!(client.with_key("b").read().version(), 0);
+
+    let mut transaction = client.transaction();
+    transaction.read("a");
+    transaction.write("b", "from transaction".to_string());
+    assert_eq!(transaction.commit(), Ok(()));
+    assert_eq!(client.with_key("b").read().data(), "from transaction");
+}
+
+#[test]
+fn concurrent_transfers_keep_the_total() {
+    let network = cluster(3);
+    let setup = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    setup.with_key("alice").write("10".to_string());
+    setup.with_key("bob").write("10".to_string());
+
+    let handles: Vec<_> = [("alice", "bob"), ("bob", "alice")].into_iter().enumerate().map(|(i, (from, to))| {
+        let client = AtomicRegisterClinent::new(ClientId(i as i32 + 1), Arc::clone(&network));
+        std::thread::spawn(move || {
+            for _ in 0..TRANSFERS {
+                loop {
+                    let mut transaction = client.transaction();
+                    let from_balance: i32 = transaction.read(from).parse().unwrap();
+                    let to_balance: i32 = transaction.read(to).parse().unwrap();
+                    transaction.write(from, (from_balance - 1).to_string());
+                    transaction.write(to, (to_balance + 1).to_string());
+                    match transaction.commit() {
+                        Ok(()) => break,
+                        Err(error) => assert_eq!(error, TransactionError::Conflict),
+                    }
+                }
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let alice: i32 = setup.with_key("alice").read().data().parse().unwrap();
+    let bob: i32 = setup.with_key("bob").read().data().parse().unwrap();
+    assert_eq!((alice, bob), (10, 10));
+}
+
+#[test]
+fn keys_of_several_groups_are_refused() {
+    let network = Arc::new(Network::local(6, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let ring = Arc::new(Mutex::new(HashRing::new(16)));
+    for i in 0..2 {
+        let group = ReplicaGroup::new(GroupId(i), (3 * i..3 * i + 3).map(NodeId).collect());
+        start_nodes(&network, &group.members);
+        ring.lock().unwrap().add_group(group);
+    }
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_ring(Arc::clone(&ring));
+
+    let first = "key-0".to_string();
+    let group = ring.lock().unwrap().group_of(&first).id.clone();
+    let other = (1..).map(|i| format!("key-{}", i)).find(|key| ring.lock().unwrap().group_of(key).id != group).unwrap();
+
+    let mut transaction = client.transaction();
+    transaction.write(&first, "1".to_string());
+    transaction.write(&other, "1".to_string());
+    assert_eq!(transaction.commit(), Err(TransactionError::CrossGroup));
+    assert_eq!(client.with_key(&first).read().version(), 0);
+    assert_eq!(client.with_key(&other).read().version(), 0);
+}
+
+#[test]
+fn commit_aborts_when_another_writer_wrote_the_same_version() {
+    let network = cluster(1);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    client.with_key("a").write("1".to_string());
+
+    let mut transaction = client.transaction();
+    assert_eq!(transaction.read("a"), "1");
+    transaction.write("b", "from transaction".to_string());
+
+    // Version 1 of a later writer, as another coordinator would write it
+    let other = NodeData::new("other".to_string(), 1).with_writer(&NodeId(2));
+    for node_id in network.node_ids() {
+        network.send_to_node(&node_id, Message::CoordinatorWriteRequest((NodeId(2), "a".to_string(), other.clone(), 0, 0)));
+    }
+    while client.with_key("a").read().data() != "other" {
+        std::thread::yield_now();
+    }
+
+    assert_eq!(transaction.commit(), Err(TransactionError::Conflict));
+    assert_eq!(client.with_key("b").read().version(), 0);
+}
+
+#[test]
+fn commit_times_out_when_the_coordinator_does_not_answer() {
+    let network = cluster(1);
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_timeout(Duration::from_millis(300));
+    network.set_reachable(&NodeId(0), false);
+
+    let mut transaction = client.transaction();
+    transaction.write("a", "1".to_string());
+    assert_eq!(transaction.commit(), Err(TransactionError::Timeout));
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{now_millis, Node, NodeData}, store::RegisterStore, trace::NoopSink};
+
+const TTL: Duration = Duration::from_millis(300);
+const SWEEP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn expired_values_are_absent_everywhere_and_keep_their_version() {
+    let network = Arc::new(Network::local(3, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes: Vec<Node> = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    let client = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).with_key("token");
+
+    let written = Instant::now();
+    client.write_with_ttl("token 1".to_string(), TTL);
+    let data = client.read();
+    if written.elapsed() < TTL {
+        assert_eq!(data.value(), Some("token 1"));
+    }
+
+    // Every replica that got the write sweeps the data away, the version stays
+    let deadline = Instant::now() + SWEEP_TIMEOUT;
+    while nodes.iter().any(|node| !node.stored("token").data().is_empty()) {
+        assert!(Instant::now() < deadline, "expired data still stored");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    let data = client.read();
+    assert_eq!((data.value(), data.version()), (None, 1));
+    for node in &nodes {
+        assert!(node.stored("token").version() <= 1);
+    }
+
+    client.write("token 2".to_string());
+    let data = client.read();
+    assert_eq!((data.value(), data.version()), (Some("token 2"), 2));
+}
+
+#[test]
+fn expiry_is_decided_by_the_assigned_time() {
+    let now = now_millis();
+    let data = NodeData::new("token".to_string(), 1).with_expiry(now + 1_000);
+
+    assert!(!data.is_expired(now));
+    assert!(!data.is_expired(now + 999));
+    assert!(data.is_expired(now + 1_000));
+    assert!(!NodeData::new("token".to_string(), 1).is_expired(u64::MAX));
+}
+
+#[test]
+fn discarding_expired_data_keeps_older_versions_out() {
+    let mut store = RegisterStore::new();
+    store.store_if_newer("token", NodeData::new("token 2".to_string(), 2).with_expiry(1_000));
+    store.store_if_newer("other", NodeData::new("kept".to_string(), 1).with_expiry(5_000));
+    let root = store.tree().root();
+
+    assert_eq!(store.discard_expired(1_000), 1);
+    assert_eq!(store.discard_expired(1_000), 0);
+    assert_eq!((store.get("token").data(), store.get("token").version()), ("", 2));
+    assert_eq!(store.get("other").data(), "kept");
+    assert_ne!(store.tree().root(), root);
+
+    // A replica still holding an older version can not bring the value back
+    assert!(!store.store_if_newer("token", NodeData::new("token 1".to_string(), 1)));
+    assert!(store.store_if_newer("token", NodeData::new("token 3".to_string(), 3)));
+}
\ No newline at end of file
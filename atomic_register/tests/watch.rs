@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use atomic_register::{atomic_register_client::{AtomicRegisterClinent, ClientId}, network::Network, node::{Message, Node, NodeData, NodeId, Timestamp}, quorum::QuorumState, trace::NoopSink, watch::Watch};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const QUIET: Duration = Duration::from_millis(300);
+
+fn start_cluster(clients: usize) -> (Arc<Network>, Vec<Node>) {
+    let network = Arc::new(Network::local(3, clients));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let nodes = network.node_ids().into_iter().map(|node_id| {
+        let mut node = Node::new(node_id, 2, Arc::clone(&network));
+        let handle = node.clone();
+        std::thread::spawn(move || {
+            node.run();
+        });
+        handle
+    }).collect();
+    (network, nodes)
+}
+
+// The first notification tells that the watch is registered and its quorum read is over
+fn watch_after_first_write(network: &Arc<Network>, writer: &AtomicRegisterClinent, watcher: ClientId) -> Watch {
+    writer.write("first".to_string());
+    let mut watch = AtomicRegisterClinent::new(watcher, Arc::clone(network)).watch(Timestamp::default());
+    assert_eq!(watch.next_timeout(TIMEOUT).unwrap().data.data(), "first");
+    watch
+}
+
+#[test]
+fn watchers_hear_of_a_write_once_a_quorum_stores_it() {
+    let (network, nodes) = start_cluster(2);
+    let writer = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let mut watch = watch_after_first_write(&network, &writer, ClientId(1));
+
+    // The coordinator stores the value once it knows the version, but can not reach a quorum with it
+    network.set_reachable(&NodeId(1), false);
+    network.set_reachable(&NodeId(2), false);
+    let write = std::thread::spawn(move || writer.write("committed later".to_string()));
+    while !matches!(nodes[0].status().quorum_state, QuorumState::WaitingForReadResponse(_)) {
+        std::thread::yield_now();
+    }
+    let round = nodes[0].status().quorum_round;
+    network.inject(&NodeId(0), Message::CoordinatorReadResponse((NodeId(1), "".to_string(), nodes[1].stored(""), 0, round)));
+    while nodes[0].stored("").version() == 1 {
+        std::thread::yield_now();
+    }
+    assert_eq!(watch.next_timeout(QUIET), None);
+
+    network.set_reachable(&NodeId(1), true);
+    network.set_reachable(&NodeId(2), true);
+    let event = watch.next_timeout(TIMEOUT).expect("no notification after the quorum write");
+    assert_eq!(event.data.data(), "committed later");
+    write.join().unwrap();
+}
+
+#[test]
+fn values_replicas_receive_are_not_notified() {
+    let (network, _nodes) = start_cluster(2);
+    let writer = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network));
+    let mut watch = watch_after_first_write(&network, &writer, ClientId(1));
+
+    // A write request of another coordinator, as anti-entropy or hints would store it
+    network.send_to_node(&NodeId(0), Message::CoordinatorWriteRequest((NodeId(1), "".to_string(), NodeData::new("replicated".to_string(), 2).with_writer(&NodeId(1)), 0, 0)));
+    assert_eq!(watch.next_timeout(QUIET), None);
+}
+
+#[test]
+fn notifications_of_the_same_version_by_a_later_writer_are_new() {
+    let network = Arc::new(Network::local(1, 1));
+    network.set_trace_sink(Arc::new(NoopSink));
+    let mut watch = AtomicRegisterClinent::new(ClientId(0), Arc::clone(&network)).watch(Timestamp::new(1, Some(NodeId(0))));
+
+    let notify = |data: NodeData| network.send_to_client(&ClientId(0), Message::Notify(("".to_string(), data)));
+    notify(NodeData::new("seen".to_string(), 1).with_writer(&NodeId(0)));
+    notify(NodeData::new("other writer".to_string(), 1).with_writer(&NodeId(1)));
+    notify(NodeData::new("older".to_string(), 1));
+
+    assert_eq!(watch.next_timeout(TIMEOUT).unwrap().data.data(), "other writer");
+    assert_eq!(watch.next_timeout(QUIET), None);
+    assert_eq!(watch.last_timestamp(), &Timestamp::new(1, Some(NodeId(1))));
+}
\ No newline at end of file